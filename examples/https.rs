@@ -0,0 +1,30 @@
+// This requires running with:
+//
+// ```bash
+// cargo run --example https --features ssl
+// ```
+//
+// Generate a key and a self-signed certificate for it with:
+//
+// ```bash
+// openssl req -x509 -newkey rsa:2048 -nodes -keyout key.pem -out cert.pem -days 365 \
+//     -subj /CN=localhost
+// ```
+
+extern crate iron;
+
+use std::path::PathBuf;
+
+use iron::prelude::*;
+use iron::status;
+
+fn main() {
+    let result = Iron::new(|req: &mut Request| {
+        Ok(Response::with((status::Ok, format!("Hello from {}!", req.url.scheme))))
+    }).https("localhost:3000", PathBuf::from("cert.pem"), PathBuf::from("key.pem"));
+
+    match result {
+        Ok(_) => println!("Listening on https://localhost:3000"),
+        Err(e) => println!("Couldn't start the server: {}", e)
+    }
+}
@@ -0,0 +1,376 @@
+//! Reading cookies from requests and setting them on responses.
+//!
+//! ```
+//! # use iron::prelude::*;
+//! # use iron::status;
+//! use iron::cookie::{Cookie, SameSite};
+//!
+//! fn handler(req: &mut Request) -> IronResult<Response> {
+//!     let visits = req.cookies().get("visits").and_then(|v| v.parse::<u32>().ok()).unwrap_or(0);
+//!
+//!     let cookie = Cookie::new("visits", &(visits + 1).to_string()).unwrap()
+//!         .path("/")
+//!         .http_only(true)
+//!         .same_site(SameSite::Lax);
+//!
+//!     Ok(Response::with((status::Ok, cookie, format!("Visit number {}", visits + 1))))
+//! }
+//! ```
+//!
+//! A `CookiePolicy` fills in the security attributes of cookies which don't
+//! set them, so that by default they are `Secure; HttpOnly; SameSite=Lax`:
+//!
+//! ```
+//! # use iron::prelude::*;
+//! # use iron::status;
+//! use iron::cookie::{Cookie, CookiePolicy};
+//!
+//! fn login(req: &mut Request) -> IronResult<Response> {
+//!     let policy = CookiePolicy::default();
+//!     let session = policy.apply(req, Cookie::new("session", "abc123").unwrap().path("/"));
+//!     let theme = policy.apply(req, Cookie::new("theme", "dark").unwrap().http_only(false));
+//!
+//!     Ok(Response::with((status::Ok, session, theme)))
+//! }
+//! ```
+
+use std::fmt;
+
+use modifier::Modifier;
+
+use {Request, Response};
+
+/// The cookies sent with a request.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CookieJar {
+    cookies: Vec<(String, String)>
+}
+
+impl CookieJar {
+    /// Parse the value of a `Cookie` header, such as `a=1; b=2`.
+    ///
+    /// Pairs without a `=` are ignored, and double quotes around a value are
+    /// removed.
+    pub fn parse(header: &str) -> CookieJar {
+        let mut jar = CookieJar::default();
+        jar.add_header(header);
+        jar
+    }
+
+    fn add_header(&mut self, header: &str) {
+        for pair in header.split(';') {
+            let mut parts = pair.splitn(2, '=');
+            let name = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(value) => value.trim(),
+                None => continue
+            };
+
+            if name.is_empty() { continue }
+
+            let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                &value[1..value.len() - 1]
+            } else {
+                value
+            };
+
+            self.cookies.push((name.to_owned(), value.to_owned()));
+        }
+    }
+
+    /// The value of the first cookie called `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.cookies.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref value)| &**value)
+    }
+
+    /// Iterate over the names and values of all the cookies, in the order
+    /// they were sent.
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = (&'a str, &'a str)> + 'a> {
+        Box::new(self.cookies.iter().map(|&(ref name, ref value)| (&**name, &**value)))
+    }
+
+    /// The number of cookies.
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    /// Were no cookies sent?
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+}
+
+impl<'a> Request<'a> {
+    /// The cookies in the `Cookie` headers of the request.
+    pub fn cookies(&self) -> CookieJar {
+        let mut jar = CookieJar::default();
+        for line in self.headers.get_raw("Cookie").unwrap_or(&[]) {
+            if let Ok(header) = ::std::str::from_utf8(line) {
+                jar.add_header(header);
+            }
+        }
+        jar
+    }
+}
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    /// Only send the cookie with requests from the same site.
+    Strict,
+    /// Also send the cookie when navigating to the site from elsewhere.
+    Lax,
+    /// Send the cookie with cross-site requests too. Browsers require such
+    /// cookies to be `Secure`.
+    None
+}
+
+/// A cookie to set on the client, with its attributes.
+///
+/// Used as a modifier, each `Cookie` adds its own `Set-Cookie` header to
+/// the `Response`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<u64>,
+    // `None` for the security attributes means they weren't set, so a
+    // `CookiePolicy` may choose them.
+    secure: Option<bool>,
+    http_only: Option<bool>,
+    same_site: Option<SameSite>
+}
+
+impl Cookie {
+    /// Create a cookie with no attributes.
+    ///
+    /// Fails if `name` is not a valid token or `value` contains characters
+    /// which are not allowed in cookies, such as `;`, `,`, whitespace or
+    /// control characters. Percent-encode such values first.
+    pub fn new(name: &str, value: &str) -> Result<Cookie, String> {
+        if name.is_empty() || !name.bytes().all(is_token) {
+            return Err(format!("Invalid cookie name: `{}`", name));
+        }
+
+        if !value.bytes().all(is_cookie_octet) {
+            return Err(format!("Invalid value for cookie `{}`", name));
+        }
+
+        Ok(Cookie {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            path: None,
+            domain: None,
+            max_age: None,
+            secure: None,
+            http_only: None,
+            same_site: None
+        })
+    }
+
+    /// Set the `Path` attribute.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `path` contains `;` or control characters.
+    pub fn path(mut self, path: &str) -> Cookie {
+        assert!(is_attribute_value(path), "Invalid cookie path: {:?}", path);
+        self.path = Some(path.to_owned());
+        self
+    }
+
+    /// Set the `Domain` attribute.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `domain` contains `;` or control characters.
+    pub fn domain(mut self, domain: &str) -> Cookie {
+        assert!(is_attribute_value(domain), "Invalid cookie domain: {:?}", domain);
+        self.domain = Some(domain.to_owned());
+        self
+    }
+
+    /// Set the `Max-Age` attribute, in seconds.
+    ///
+    /// A `Max-Age` of `0` deletes the cookie.
+    pub fn max_age(mut self, seconds: u64) -> Cookie {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Set whether the cookie is only sent over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Cookie {
+        self.secure = Some(secure);
+        self
+    }
+
+    /// Set whether the cookie is hidden from JavaScript.
+    pub fn http_only(mut self, http_only: bool) -> Cookie {
+        self.http_only = Some(http_only);
+        self
+    }
+
+    /// Set the `SameSite` attribute.
+    pub fn same_site(mut self, same_site: SameSite) -> Cookie {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// The name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for Cookie {
+    /// Formats the cookie as the value of a `Set-Cookie` header.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}={}", self.name, self.value));
+
+        if let Some(ref path) = self.path { try!(write!(f, "; Path={}", path)) }
+        if let Some(ref domain) = self.domain { try!(write!(f, "; Domain={}", domain)) }
+        if let Some(max_age) = self.max_age { try!(write!(f, "; Max-Age={}", max_age)) }
+        if self.secure == Some(true) { try!(f.write_str("; Secure")) }
+        if self.http_only == Some(true) { try!(f.write_str("; HttpOnly")) }
+
+        match self.same_site {
+            Some(SameSite::Strict) => f.write_str("; SameSite=Strict"),
+            Some(SameSite::Lax) => f.write_str("; SameSite=Lax"),
+            Some(SameSite::None) => f.write_str("; SameSite=None"),
+            None => Ok(())
+        }
+    }
+}
+
+impl Modifier<Response> for Cookie {
+    /// Add a `Set-Cookie` header for this cookie, keeping any already set.
+    fn modify(self, res: &mut Response) {
+        // hyper writes the raw values of a header back to back on one line, so
+        // separate cookies into their own lines the way its `SetCookie` does.
+        let separator = b"\r\nSet-Cookie: ";
+        let mut line = res.headers.get_raw("Set-Cookie")
+            .map_or_else(Vec::new, |lines| lines.join(&separator[..]));
+        if !line.is_empty() { line.extend_from_slice(separator) }
+        line.extend_from_slice(self.to_string().as_bytes());
+        res.headers.set_raw("Set-Cookie", vec![line]);
+    }
+}
+
+/// Defaults for the security attributes of cookies, applied with
+/// `CookiePolicy::apply` to those which don't set them.
+///
+/// The default policy makes cookies `Secure`, `HttpOnly` and `SameSite=Lax`.
+/// Whatever the policy, cookies set in response to requests made over TLS
+/// are `Secure` unless they say otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CookiePolicy {
+    /// Whether cookies are only sent over HTTPS.
+    pub secure: bool,
+    /// Whether cookies are hidden from JavaScript.
+    pub http_only: bool,
+    /// The `SameSite` attribute, if any.
+    pub same_site: Option<SameSite>
+}
+
+impl Default for CookiePolicy {
+    fn default() -> CookiePolicy {
+        CookiePolicy { secure: true, http_only: true, same_site: Some(SameSite::Lax) }
+    }
+}
+
+impl CookiePolicy {
+    /// Fill in the attributes which `cookie` hasn't set from this policy,
+    /// for a cookie set in response to `req`.
+    pub fn apply(&self, req: &Request, mut cookie: Cookie) -> Cookie {
+        cookie.secure = cookie.secure.or(Some(self.secure || req.secure));
+        cookie.http_only = cookie.http_only.or(Some(self.http_only));
+        cookie.same_site = cookie.same_site.or(self.same_site);
+        cookie
+    }
+}
+
+fn is_token(b: u8) -> bool {
+    match b {
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' |
+        b'^' | b'_' | b'`' | b'|' | b'~' => true,
+        _ => (b as char).is_ascii_alphanumeric()
+    }
+}
+
+// See `cookie-octet` in RFC 6265.
+fn is_cookie_octet(b: u8) -> bool {
+    match b {
+        0x21 | 0x23...0x2B | 0x2D...0x3A | 0x3C...0x5B | 0x5D...0x7E => true,
+        _ => false
+    }
+}
+
+fn is_attribute_value(value: &str) -> bool {
+    value.bytes().all(|b| b >= 0x20 && b != 0x7F && b != b';')
+}
+
+#[cfg(test)]
+mod test {
+    use modifier::Set;
+
+    use {method, test, Response, Url};
+
+    use super::{Cookie, CookieJar, CookiePolicy, SameSite};
+
+    #[test]
+    fn test_parse_cookies() {
+        let jar = CookieJar::parse("a=1; b=\"two\";c=3=3; broken; =x");
+        assert_eq!(jar.get("a"), Some("1"));
+        assert_eq!(jar.get("b"), Some("two"));
+        assert_eq!(jar.get("c"), Some("3=3"));
+        assert_eq!(jar.len(), 3);
+    }
+
+    #[test]
+    fn test_set_cookie_lines() {
+        let session = Cookie::new("session", "abc").unwrap()
+            .path("/").domain("example.com").max_age(3600)
+            .secure(true).http_only(true).same_site(SameSite::Strict);
+        let theme = Cookie::new("theme", "dark").unwrap();
+
+        let res = Response::new().set(session).set(theme);
+        assert_eq!(res.headers.to_string(), "Set-Cookie: session=abc; Path=/; Domain=example.com; \
+                                             Max-Age=3600; Secure; HttpOnly; SameSite=Strict\r\n\
+                                             Set-Cookie: theme=dark\r\n");
+    }
+
+    #[test]
+    fn test_cookie_policy() {
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, &[], |req| {
+            let policy = CookiePolicy::default();
+            let cookie = |name| Cookie::new(name, "x").unwrap();
+            assert_eq!(policy.apply(req, cookie("a")).to_string(),
+                       "a=x; Secure; HttpOnly; SameSite=Lax");
+            assert_eq!(policy.apply(req, cookie("a").http_only(false).same_site(SameSite::Strict))
+                           .to_string(),
+                       "a=x; Secure; SameSite=Strict");
+
+            let policy = CookiePolicy { secure: false, http_only: false, same_site: None };
+            assert_eq!(policy.apply(req, cookie("a")).to_string(), "a=x");
+            req.secure = true;
+            assert_eq!(policy.apply(req, cookie("a")).to_string(), "a=x; Secure");
+            assert_eq!(policy.apply(req, cookie("a").secure(false)).to_string(), "a=x");
+        });
+    }
+
+    #[test]
+    fn test_invalid_cookies() {
+        assert!(Cookie::new("", "x").is_err());
+        assert!(Cookie::new("a b", "x").is_err());
+        assert!(Cookie::new("a", "x;y").is_err());
+        assert!(Cookie::new("a", "x\r\nLocation: evil").is_err());
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt;
 
 use modifier::Modifier;
 use {Response};
+use status::{self, Status};
 
 pub use err::Error;
 pub use hyper::Error as HttpError;
@@ -43,6 +44,46 @@ impl IronError {
             response: Response::with(m)
         }
     }
+
+    /// A `400 Bad Request` error, with `message` as its body.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// fn handler(req: &mut Request) -> IronResult<Response> {
+    ///     let id = match req.url.query {
+    ///         Some(ref id) => id.clone(),
+    ///         None => return Err(IronError::bad_request("missing id"))
+    ///     };
+    ///     Ok(Response::with((status::Ok, id)))
+    /// }
+    /// ```
+    pub fn bad_request<M: Into<String>>(message: M) -> IronError {
+        IronError::with_message(status::BadRequest, message.into())
+    }
+
+    /// A `401 Unauthorized` error, with `message` as its body.
+    pub fn unauthorized<M: Into<String>>(message: M) -> IronError {
+        IronError::with_message(status::Unauthorized, message.into())
+    }
+
+    /// A `403 Forbidden` error, with `message` as its body.
+    pub fn forbidden<M: Into<String>>(message: M) -> IronError {
+        IronError::with_message(status::Forbidden, message.into())
+    }
+
+    /// A `500 Internal Server Error` caused by `e`.
+    ///
+    /// The body only says `Internal Server Error`, so details of `e` aren't
+    /// shown to clients, though they are logged.
+    pub fn internal<E: Error>(e: E) -> IronError {
+        IronError::new(e, (status::InternalServerError, "Internal Server Error"))
+    }
+
+    fn with_message(status: Status, message: String) -> IronError {
+        let body = message.clone();
+        IronError::new(StatusError { status: status, message: message }, (status, body))
+    }
 }
 
 impl fmt::Display for IronError {
@@ -61,3 +102,71 @@ impl StdError for IronError {
     }
 }
 
+
+/// The error made by `IronError::bad_request` and the other constructors
+/// which take a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusError {
+    /// The status of the response.
+    pub status: Status,
+
+    /// What went wrong, which is also the body of the response.
+    pub message: String
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}: {}", self.status, self.message)
+    }
+}
+
+impl StdError for StatusError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+/// The error produced when a `Chain` which catches panics catches one.
+///
+/// See `Chain::catch_panics`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicError {
+    /// The message the panic was started with, if it was a string.
+    pub message: Option<String>
+}
+
+impl fmt::Display for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.message {
+            Some(ref message) => write!(f, "Panicked while handling a request: {}", message),
+            None => f.write_str("Panicked while handling a request")
+        }
+    }
+}
+
+impl StdError for PanicError {
+    fn description(&self) -> &str {
+        "Panicked while handling a request"
+    }
+}
+
+/// The error which signals that no resource matches a request, as opposed to
+/// a resource failing to handle it.
+///
+/// Routers should fail with
+/// `IronError::new(NotFound, status::NotFound)` when no route matches, so a
+/// `Chain` with a `not_found` handler can answer instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotFound;
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str("No resource matches the request")
+    }
+}
+
+impl StdError for NotFound {
+    fn description(&self) -> &str {
+        "No resource matches the request"
+    }
+}
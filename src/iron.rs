@@ -1,22 +1,42 @@
 //! Exposes the `Iron` type, the main entrance point of the
 //! `Iron` library.
 
+use std::cell::Cell;
 use std::net::{ToSocketAddrs, SocketAddr};
-use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+#[cfg(feature = "ssl")]
+use std::fs::File;
+#[cfg(feature = "ssl")]
+use std::io;
+#[cfg(any(unix, feature = "ssl"))]
+use std::path::Path;
 #[cfg(feature = "ssl")]
 use std::path::PathBuf;
 
 pub use hyper::server::Listening;
 use hyper::server::Server;
-use hyper::net::Fresh;
+use hyper::net::{Fresh, HttpListener, NetworkListener};
 
 use request::HttpRequest;
-use response::HttpResponse;
+use response::{HttpResponse, WriteBody};
 
 use error::HttpResult;
 
-use {Request, Handler};
-use status;
+#[cfg(unix)]
+use unix::{self, UnixListener};
+
+use shutdown::{ShutdownState, GracefulListener};
+pub use shutdown::ShutdownSignal;
+
+use metrics::CountedBody;
+pub use metrics::{Metrics, RequestMetrics};
+
+use {Request, Response, Handler, Headers, IronError, headers};
+use method::{self, Method};
+use request::{self, Body, RequestUri};
+use status::{self, Status};
 
 /// The primary entrance point to `Iron`, a `struct` to instantiate a new server.
 ///
@@ -31,7 +51,88 @@ pub struct Iron<H> {
     addr: Option<SocketAddr>,
 
     /// Once listening, the protocol used to serve content.
-    protocol: Option<Protocol>
+    protocol: Option<Protocol>,
+
+    /// The number of worker threads used to handle requests.
+    threads: usize,
+
+    /// The timeouts applied to each connection.
+    timeouts: Timeouts,
+
+    /// Tracks in-flight requests for graceful shutdown.
+    shutdown: Arc<ShutdownState>,
+
+    /// What to do with the `Server` header of each response.
+    server_header: ServerHeader,
+
+    /// Renders errors returned by the handler, if set.
+    error_handler: Option<Arc<Fn(&IronError, &mut Request) -> Response + Send + Sync>>,
+
+    /// Changes each response just before it is written, if set.
+    before_write: Option<Arc<Fn(&mut Request, &mut Response) + Send + Sync>>,
+
+    /// Receives metrics events, if set.
+    metrics: Option<Arc<Metrics>>,
+
+    /// The most bytes of headers a request may have.
+    max_header_bytes: usize,
+
+    /// The most header lines a request may have.
+    max_header_count: usize,
+
+    /// The most bytes the request URI may have.
+    max_uri_length: usize,
+
+    /// The most bytes of request body which can be read, if limited.
+    max_body_bytes: Option<u64>,
+
+    /// The most bytes of request body left by the handler which are
+    /// discarded to keep the connection alive.
+    max_drain_bytes: u64,
+
+    /// The most requests handled at once, if limited.
+    max_concurrent_requests: Option<usize>,
+
+    /// The number of requests being handled, counted against
+    /// `max_concurrent_requests`.
+    concurrent_requests: AtomicUsize,
+
+    /// The most requests served on one connection, if limited.
+    max_keep_alive_requests: Option<usize>,
+
+    /// The CA bundle which client certificates must be signed by, if they
+    /// are required.
+    #[cfg(feature = "ssl")]
+    client_ca: Option<PathBuf>
+}
+
+thread_local! {
+    // The number of requests served on the connection this worker thread is
+    // handling. Hyper handles each connection on a single thread from start
+    // to end, so this is reset as each one starts.
+    static CONNECTION_REQUESTS: Cell<usize> = Cell::new(0)
+}
+
+#[derive(Clone, Debug)]
+enum ServerHeader {
+    // Leave responses as the handler made them.
+    Unchanged,
+    // Add this value to responses which don't have a `Server` header.
+    Default(String),
+    // Remove the `Server` header from every response.
+    Hidden
+}
+
+impl ServerHeader {
+    fn apply(&self, headers: &mut Headers) {
+        match *self {
+            ServerHeader::Unchanged => (),
+            ServerHeader::Default(ref value) => if !headers.has::<headers::Server>() {
+                headers.set(headers::Server(value.clone()));
+            },
+            ServerHeader::Hidden => { headers.remove::<headers::Server>(); }
+        }
+    }
 }
 
 /// A settings struct containing a set of timeouts which can be applied to a server.
@@ -90,6 +191,15 @@ impl Protocol {
             Protocol::Https { .. } => "https"
         }
     }
+
+    /// Whether connections made with this protocol are encrypted.
+    pub fn is_secure(&self) -> bool {
+        match *self {
+            Protocol::Http => false,
+            #[cfg(feature = "ssl")]
+            Protocol::Https { .. } => true
+        }
+    }
 }
 
 impl<H: Handler> Iron<H> {
@@ -102,14 +212,19 @@ impl<H: Handler> Iron<H> {
     /// The thread returns a guard that will automatically join with the parent
     /// once it is dropped, blocking until this happens.
     ///
-    /// Defaults to a threadpool of size `8 * num_cpus`.
+    /// Uses a threadpool of the size set with `threads`, which defaults to
+    /// `8 * num_cpus`.
+    ///
+    /// Binding to port `0` picks a free port, which is given by the `socket`
+    /// of the returned `Listening` and used for the `local_addr` of requests.
     ///
     /// ## Panics
     ///
     /// Panics if the provided address does not parse. To avoid this
     /// call `to_socket_addrs` yourself and pass a parsed `SocketAddr`.
     pub fn http<A: ToSocketAddrs>(self, addr: A) -> HttpResult<Listening> {
-        self.listen_with(addr, 8 * ::num_cpus::get(), Protocol::Http, None)
+        let threads = self.threads;
+        self.listen_with(addr, threads, Protocol::Http, None)
     }
 
     /// Kick off the server process using the HTTPS protocol.
@@ -118,10 +233,18 @@ impl<H: Handler> Iron<H> {
     /// This consumes the Iron instance, but does the listening on
     /// another task, so is not blocking.
     ///
+    /// `certificate` and `key` are paths to PEM files. They are loaded before
+    /// the socket is bound, and an `Err` naming the offending file is
+    /// returned if they can't be. Requests served this way have `https`
+    /// URLs.
+    ///
+    /// Requires the `ssl` feature.
+    ///
     /// The thread returns a guard that will automatically join with the parent
     /// once it is dropped, blocking until this happens.
     ///
-    /// Defaults to a threadpool of size `8 * num_cpus`.
+    /// Uses a threadpool of the size set with `threads`, which defaults to
+    /// `8 * num_cpus`.
     ///
     /// ## Panics
     ///
@@ -130,29 +253,104 @@ impl<H: Handler> Iron<H> {
     #[cfg(feature = "ssl")]
     pub fn https<A: ToSocketAddrs>(self, addr: A, certificate: PathBuf, key: PathBuf)
                                    -> HttpResult<Listening> {
-        self.listen_with(addr, 8 * ::num_cpus::get(),
+        let threads = self.threads;
+        self.listen_with(addr, threads,
                          Protocol::Https { certificate: certificate, key: key }, None)
     }
 
+    /// Kick off the server process using the HTTP protocol, with support for
+    /// graceful shutdown.
+    ///
+    /// Behaves like `http`, but also returns a `ShutdownSignal` which can be
+    /// used to stop accepting connections and wait for in-flight requests to
+    /// complete.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the provided address does not parse. To avoid this
+    /// call `to_socket_addrs` yourself and pass a parsed `SocketAddr`.
+    pub fn http_with_shutdown<A: ToSocketAddrs>(mut self, addr: A)
+                                                -> HttpResult<(Listening, ShutdownSignal)> {
+        let sock_addr = addr.to_socket_addrs()
+            .ok().and_then(|mut addrs| addrs.next()).expect("Could not parse socket address.");
+        let threads = self.threads;
+
+        let mut listener = try!(GracefulListener::bind(sock_addr, self.shutdown.clone()));
+        let state = self.shutdown.clone();
+
+        self.addr = Some(try!(listener.local_addr()));
+        self.protocol = Some(Protocol::Http);
+
+        let mut server = Server::new(listener);
+        let timeouts = self.timeouts;
+        server.keep_alive(timeouts.keep_alive);
+        server.set_read_timeout(timeouts.read);
+        server.set_write_timeout(timeouts.write);
+
+        let listening = try!(server.handle_threads(self, threads));
+        let signal = ShutdownSignal::new(state, listening.socket, threads);
+        Ok((listening, signal))
+    }
+
+    /// Kick off the server process using the HTTP protocol on a Unix domain
+    /// socket at `path`.
+    ///
+    /// Behaves like `http`. If a socket file is left at `path` by a server
+    /// which is no longer running, it is replaced; a socket still being
+    /// listened on, or any other kind of file, is left alone and binding
+    /// fails.
+    ///
+    /// The socket is created with the permissions allowed by the process
+    /// umask, and anyone who can write to it can send requests. To limit who
+    /// can connect, put it in a directory only they can reach.
+    ///
+    /// Unix sockets have no IP addresses, so requests have a `remote_addr` and
+    /// `local_addr` of `0.0.0.0:0`, and their `url` takes its port from the
+    /// `Host` header. When serving behind a reverse proxy, pass `0.0.0.0/32`
+    /// as a trusted network to `Request::real_remote_addr`.
+    #[cfg(unix)]
+    pub fn unix<P: AsRef<Path>>(mut self, path: P) -> HttpResult<Listening> {
+        let threads = self.threads;
+        let listener = try!(UnixListener::bind(path.as_ref()));
+
+        self.addr = Some(unix::unspecified_addr());
+        self.protocol = Some(Protocol::Http);
+
+        let mut server = Server::new(listener);
+        let timeouts = self.timeouts;
+        server.keep_alive(timeouts.keep_alive);
+        server.set_read_timeout(timeouts.read);
+        server.set_write_timeout(timeouts.write);
+        server.handle_threads(self, threads)
+    }
+
     /// Kick off the server process with X threads.
     ///
+    /// If `timeouts` is `None`, the timeouts configured on this `Iron` are used.
+    ///
     /// ## Panics
     ///
     /// Panics if the provided address does not parse. To avoid this
     /// call `to_socket_addrs` yourself and pass a parsed `SocketAddr`.
+    ///
+    /// Also panics if `threads` is `0`.
     pub fn listen_with<A: ToSocketAddrs>(mut self, addr: A, threads: usize,
                                          protocol: Protocol,
                                          timeouts: Option<Timeouts>) -> HttpResult<Listening> {
+        assert!(threads != 0, "Iron cannot serve requests with 0 threads.");
+
         let sock_addr = addr.to_socket_addrs()
             .ok().and_then(|mut addrs| addrs.next()).expect("Could not parse socket address.");
 
-        self.addr = Some(sock_addr);
         self.protocol = Some(protocol.clone());
 
         match protocol {
             Protocol::Http => {
-                let mut server = try!(Server::http(sock_addr));
-                let timeouts = timeouts.unwrap_or_default();
+                let mut listener = try!(HttpListener::new(sock_addr));
+                self.addr = Some(try!(listener.local_addr()));
+
+                let mut server = Server::new(listener);
+                let timeouts = timeouts.unwrap_or(self.timeouts);
                 server.keep_alive(timeouts.keep_alive);
                 server.set_read_timeout(timeouts.read);
                 server.set_write_timeout(timeouts.write);
@@ -161,11 +359,22 @@ impl<H: Handler> Iron<H> {
 
             #[cfg(feature = "ssl")]
             Protocol::Https { ref certificate, ref key } => {
-                use hyper::net::Openssl;
+                use hyper::net::HttpsListener;
 
-                let ssl = try!(Openssl::with_cert_and_key(certificate, key));
-                let mut server = try!(Server::https(sock_addr, ssl));
-                let timeouts = timeouts.unwrap_or_default();
+                try!(check_readable("certificate", certificate));
+                try!(check_readable("private key", key));
+                if let Some(ref ca) = self.client_ca {
+                    try!(check_readable("client CA bundle", ca));
+                }
+                let ssl = try!(tls_context(certificate, key, self.client_ca.as_ref()).map_err(|e| {
+                    ::hyper::Error::Ssl(format!("Couldn't load TLS certificate {} with key {}: {}",
+                                                certificate.display(), key.display(), e).into())
+                }));
+                let mut listener = try!(HttpsListener::new(sock_addr, ssl));
+                self.addr = Some(try!(listener.local_addr()));
+
+                let mut server = Server::new(listener);
+                let timeouts = timeouts.unwrap_or(self.timeouts);
                 server.keep_alive(timeouts.keep_alive);
                 server.set_read_timeout(timeouts.read);
                 server.set_write_timeout(timeouts.write);
@@ -179,36 +388,570 @@ impl<H: Handler> Iron<H> {
     /// This will create a new `Iron`, the base unit of the server, using the
     /// passed in `Handler`.
     pub fn new(handler: H) -> Iron<H> {
-        Iron { handler: handler, addr: None, protocol: None }
+        Iron {
+            handler: handler,
+            addr: None,
+            protocol: None,
+            threads: 8 * ::num_cpus::get(),
+            timeouts: Timeouts::default(),
+            shutdown: Arc::new(ShutdownState::new()),
+            server_header: ServerHeader::Unchanged,
+            error_handler: None,
+            before_write: None,
+            metrics: None,
+            max_header_bytes: 64 * 1024,
+            max_header_count: 100,
+            max_uri_length: 8 * 1024,
+            max_body_bytes: None,
+            max_drain_bytes: 64 * 1024,
+            max_concurrent_requests: None,
+            concurrent_requests: AtomicUsize::new(0),
+            max_keep_alive_requests: None,
+            #[cfg(feature = "ssl")]
+            client_ca: None
+        }
+    }
+
+    /// Set the number of worker threads used to handle requests.
+    ///
+    /// Each thread handles one connection at a time. The default is
+    /// `8 * num_cpus`.
+    ///
+    /// ```no_run
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// Iron::new(|_: &mut Request| Ok(Response::with(status::Ok)))
+    ///     .threads(16)
+    ///     .http("localhost:3000").unwrap();
+    /// ```
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `threads` is `0`.
+    pub fn threads(mut self, threads: usize) -> Iron<H> {
+        assert!(threads != 0, "Iron cannot serve requests with 0 threads.");
+        self.threads = threads;
+        self
+    }
+
+    /// Set the timeouts applied to each connection.
+    ///
+    /// The defaults are described on `Timeouts`.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Iron<H> {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Set the timeout for reads on each connection, including reads of
+    /// request headers and bodies.
+    ///
+    /// When a read of a request `Body` times out it fails with an `io::Error`
+    /// of kind `TimedOut`. `None` disables the timeout.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Iron<H> {
+        self.timeouts.read = timeout;
+        self
+    }
+
+    /// Set the timeout for writes on each connection.
+    ///
+    /// `None` disables the timeout.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Iron<H> {
+        self.timeouts.write = timeout;
+        self
+    }
+
+    /// Set how long a kept-alive connection may wait idle for its next
+    /// request before it is closed. The default is 5 seconds.
+    ///
+    /// `None` turns keep-alive off, so every response is sent with
+    /// `Connection: close`. Requests pipelined on one connection are always
+    /// handled one after the other, in order.
+    ///
+    /// Each connection holds one of the `threads` for as long as it is open,
+    /// idle or not, so long timeouts leave fewer threads for new connections.
+    /// Idle connections don't count towards `max_concurrent_requests`, which
+    /// only counts requests being handled. Once a graceful shutdown has
+    /// started, the next response on each connection closes it, but a
+    /// connection which stays idle is only closed by this timeout.
+    pub fn keep_alive(mut self, timeout: Option<Duration>) -> Iron<H> {
+        self.timeouts.keep_alive = timeout;
+        self
+    }
+
+    /// Set the most requests served on one connection. The response to the
+    /// last of them is sent with `Connection: close`, so the client opens a
+    /// new connection for any more. By default there is no limit.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// Iron::new(|_: &mut Request| Ok(Response::with(status::Ok)))
+    ///     .keep_alive(Some(Duration::from_secs(15)))
+    ///     .max_keep_alive_requests(100)
+    ///     .http("localhost:3000").unwrap();
+    /// ```
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `limit` is `0`.
+    pub fn max_keep_alive_requests(mut self, limit: usize) -> Iron<H> {
+        assert!(limit != 0, "Iron cannot serve a limit of 0 requests per connection.");
+        self.max_keep_alive_requests = Some(limit);
+        self
+    }
+
+    /// Require every client connecting to `https` to present a certificate
+    /// signed by one of the CAs in `ca`, a PEM bundle, or stop asking for
+    /// certificates with `None`, the default.
+    ///
+    /// Connections whose certificate is missing or can't be verified fail
+    /// during the TLS handshake, so they never reach the handler. The
+    /// certificate of each request is given by `Request::tls_info`.
+    ///
+    /// Requires the `ssl` feature.
+    ///
+    /// ```no_run
+    /// # use std::path::PathBuf;
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// Iron::new(|req: &mut Request| {
+    ///     let client = req.tls_info().and_then(|tls| tls.peer_common_name);
+    ///     Ok(Response::with((status::Ok, client.unwrap_or_default())))
+    /// }).client_certificates(Some(PathBuf::from("clients-ca.pem")))
+    ///   .https("localhost:3000", PathBuf::from("cert.pem"), PathBuf::from("key.pem")).unwrap();
+    /// ```
+    #[cfg(feature = "ssl")]
+    pub fn client_certificates(mut self, ca: Option<PathBuf>) -> Iron<H> {
+        self.client_ca = ca;
+        self
+    }
+
+    /// Send `Server: <value>` with every response.
+    ///
+    /// The handler wins: responses which already have a `Server` header keep
+    /// it. By default Iron doesn't send a `Server` header at all.
+    ///
+    /// ```no_run
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// Iron::new(|_: &mut Request| Ok(Response::with(status::Ok)))
+    ///     .server_header("example/1.0")
+    ///     .http("localhost:3000").unwrap();
+    /// ```
+    pub fn server_header(mut self, value: &str) -> Iron<H> {
+        self.server_header = ServerHeader::Default(value.to_owned());
+        self
+    }
+
+    /// Never send a `Server` header, removing any set by the handler.
+    ///
+    /// Unlike `server_header`, this configuration wins over the handler, so
+    /// no middleware can reveal the server software by accident.
+    pub fn hide_server_header(mut self) -> Iron<H> {
+        self.server_header = ServerHeader::Hidden;
+        self
+    }
+
+    /// Render the response for errors returned by the handler with
+    /// `error_handler`, instead of sending the error's own `response`.
+    ///
+    /// This lets an application give every error the same kind of body, such
+    /// as a JSON envelope, wherever it was raised. `error_handler` can read
+    /// the error's `response` to keep its status. The responses it renders
+    /// still go through `before_write`.
+    ///
+    /// ```no_run
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// Iron::new(|_: &mut Request| Ok(Response::with(status::Ok)))
+    ///     .on_error(|err: &IronError, _: &mut Request| {
+    ///         let status = err.response.status.unwrap_or(status::InternalServerError);
+    ///         let body = format!("{{\"error\":\"{}\"}}", status.canonical_reason().unwrap_or(""));
+    ///         Response::with((status, body))
+    ///     })
+    ///     .http("localhost:3000").unwrap();
+    /// ```
+    pub fn on_error<F>(mut self, error_handler: F) -> Iron<H>
+    where F: Fn(&IronError, &mut Request) -> Response + Send + Sync + 'static {
+        self.error_handler = Some(Arc::new(error_handler));
+        self
+    }
+
+    /// Call `hook` with every response just before it is written, once the
+    /// handler and all of its `AfterMiddleware` are done with it.
+    ///
+    /// This is the place for headers which must reflect the final response,
+    /// such as how long it took to produce. Responses rendered by `on_error`
+    /// go through `hook` too, after `on_error`, so it sees exactly what will
+    /// be sent. Requests rejected before reaching the handler, such as for
+    /// having too many headers, don't.
+    ///
+    /// Iron's own `Connection` and `Server` headers are set after `hook`, so
+    /// it can't keep open a connection which Iron has to close.
+    ///
+    /// ```no_run
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// Iron::new(|_: &mut Request| Ok(Response::with(status::Ok)))
+    ///     .before_write(|req: &mut Request, res: &mut Response| {
+    ///         let path = req.url.path.join("/");
+    ///         res.headers.set_raw("X-Served-Path", vec![path.into_bytes()]);
+    ///     })
+    ///     .http("localhost:3000").unwrap();
+    /// ```
+    pub fn before_write<F>(mut self, hook: F) -> Iron<H>
+    where F: Fn(&mut Request, &mut Response) + Send + Sync + 'static {
+        self.before_write = Some(Arc::new(hook));
+        self
+    }
+
+    /// Set the most bytes of headers a request may have, counting each
+    /// header line as it was sent. The default is 64 KiB.
+    ///
+    /// Requests with more are answered with `431 Request Header Fields Too
+    /// Large` before the handler is called.
+    pub fn max_header_bytes(mut self, bytes: usize) -> Iron<H> {
+        self.max_header_bytes = bytes;
+        self
+    }
+
+    /// Set the most header lines a request may have. The default is 100,
+    /// which is also the most the HTTP parser accepts; it drops the
+    /// connection of requests with more.
+    ///
+    /// Requests with more are answered with `431 Request Header Fields Too
+    /// Large` before the handler is called.
+    pub fn max_header_count(mut self, count: usize) -> Iron<H> {
+        self.max_header_count = count;
+        self
+    }
+
+    /// Set the most bytes the URI of a request may have, as it was sent in the
+    /// request line. The default is 8 KiB.
+    ///
+    /// Requests with longer URIs are answered with `414 URI Too Long` before
+    /// they are parsed any further or the handler is called.
+    pub fn max_uri_length(mut self, bytes: usize) -> Iron<H> {
+        self.max_uri_length = bytes;
+        self
+    }
+
+    /// Limit every request body to `bytes`. By default bodies are unlimited.
+    ///
+    /// This sets the limit of each `req.body` as with `Body::set_limit`
+    /// before the handler is called, so reading past it fails; handlers which
+    /// accept larger bodies can raise it again. Requests sent with `Expect:
+    /// 100-continue` and a `Content-Length` over `bytes` are answered with
+    /// `417 Expectation Failed` before their body is sent, without asking the
+    /// handler.
+    pub fn max_body_bytes(mut self, bytes: u64) -> Iron<H> {
+        self.max_body_bytes = Some(bytes);
+        self
+    }
+
+    /// Set the most bytes of request body the handler may leave unread while
+    /// the connection is kept alive. The default is 64 KiB.
+    ///
+    /// Unread bytes would be taken as the start of the next request on the
+    /// connection, so once the response is ready they are read and thrown
+    /// away. If more than `bytes` are left, the connection is closed after
+    /// the response instead; `0` closes every connection with any unread
+    /// body.
+    pub fn max_drain_bytes(mut self, bytes: u64) -> Iron<H> {
+        self.max_drain_bytes = bytes;
+        self
+    }
+
+    /// Set the most requests which are handled at once. By default only the
+    /// number of `threads` limits it.
+    ///
+    /// Requests over the limit are answered straight away with `503 Service
+    /// Unavailable` and `Retry-After: 1`, without calling the handler, so a
+    /// spike in load is shed rather than queued. A request stops counting
+    /// towards the limit once its response has been written, even if its
+    /// handler failed or panicked.
+    ///
+    /// ```no_run
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// Iron::new(|_: &mut Request| Ok(Response::with(status::Ok)))
+    ///     .max_concurrent_requests(64)
+    ///     .http("localhost:3000").unwrap();
+    /// ```
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `limit` is `0`.
+    pub fn max_concurrent_requests(mut self, limit: usize) -> Iron<H> {
+        assert!(limit != 0, "Iron cannot serve requests with a limit of 0 at once.");
+        self.max_concurrent_requests = Some(limit);
+        self
+    }
+
+    // Count a request against `max_concurrent_requests` until the returned
+    // guard is dropped, or return `None` if the limit has been reached.
+    fn admit(&self) -> Option<Admitted> {
+        let counter = &self.concurrent_requests;
+        let previous = counter.fetch_add(1, Ordering::SeqCst);
+        let admitted = Admitted(counter);
+
+        match self.max_concurrent_requests {
+            Some(limit) if previous >= limit => None,
+            _ => Some(admitted)
+        }
+    }
+
+    /// Report connection and request events to `metrics`.
+    ///
+    /// Without metrics, no measurements are taken at all.
+    pub fn metrics<M: Metrics>(mut self, metrics: M) -> Iron<H> {
+        self.metrics = Some(Arc::new(metrics));
+        self
     }
 }
 
 impl<H: Handler> ::hyper::server::Handler for Iron<H> {
     fn handle(&self, http_req: HttpRequest, mut http_res: HttpResponse<Fresh>) {
+        let _in_flight = self.shutdown.start_request();
+        let served = CONNECTION_REQUESTS.with(|served| {
+            served.set(served.get() + 1);
+            served.get()
+        });
+
         // Set some defaults in case request handler panics.
         // This should not be necessary anymore once stdlib's catch_panic becomes stable.
         *http_res.status_mut() = status::InternalServerError;
 
+        if !headers_within_limits(&http_req.headers, self.max_header_bytes, self.max_header_count) {
+            error!("Rejecting request with headers over the configured limits");
+            self.server_header.apply(http_res.headers_mut());
+            return reject(http_res, status::RequestHeaderFieldsTooLarge);
+        }
+
+        if uri_len(&http_req.uri) > self.max_uri_length {
+            error!("Rejecting request with a URI over the configured limit");
+            self.server_header.apply(http_res.headers_mut());
+            return reject(http_res, status::UriTooLong);
+        }
+
+        let _admitted = match self.admit() {
+            Some(admitted) => admitted,
+            None => {
+                error!("Rejecting request over the limit of concurrent requests");
+                self.server_header.apply(http_res.headers_mut());
+                http_res.headers_mut().set_raw("Retry-After", vec![b"1".to_vec()]);
+                return reject(http_res, status::ServiceUnavailable);
+            }
+        };
+
+        // Ambiguous framing is refused before looking at the codings, so a
+        // request smuggling a body gets `400` however its codings read.
+        if let Err(err) = request::check_framing(&http_req.headers) {
+            error!("Rejecting request: {}", err);
+            self.server_header.apply(http_res.headers_mut());
+            return reject(http_res, status::BadRequest);
+        }
+
+        if let Err(err) = request::transfer_codings(&http_req.headers) {
+            error!("Rejecting request: {}", err);
+            self.server_header.apply(http_res.headers_mut());
+            return reject(http_res, status::NotImplemented);
+        }
+
         // Create `Request` wrapper.
         match Request::from_http(http_req, self.addr.clone().unwrap(),
                                  self.protocol.as_ref().unwrap()) {
             Ok(mut req) => {
+                if let Some(max_body_bytes) = self.max_body_bytes {
+                    req.body.set_limit(max_body_bytes);
+                }
+
+                let started = Instant::now();
+                let is_head = req.method == method::Head;
+                if let Some(ref metrics) = self.metrics {
+                    metrics.request_started(&req);
+                }
+
                 // Dispatch the request, write the response back to http_res
-                self.handler.handle(&mut req).unwrap_or_else(|e| {
+                let mut res = self.handler.handle(&mut req).unwrap_or_else(|e| {
                     error!("Error handling:\n{:?}\nError was: {:?}", req, e.error);
-                    e.response
-                }).write_back(http_res)
+                    match self.error_handler {
+                        Some(ref error_handler) => error_handler(&e, &mut req),
+                        None => e.response
+                    }
+                });
+
+                // Whatever the handler left of the body must go before the
+                // connection can be read from again.
+                let bytes_read = req.body.bytes_read();
+                let drained = req.body.drain(self.max_drain_bytes);
+
+                if let Some(ref before_write) = self.before_write {
+                    before_write(&mut req, &mut res);
+                }
+
+                if !drained {
+                    res.headers.set(headers::Connection::close());
+                }
+
+                // Let kept-alive connections wind down once shutdown has started.
+                if self.shutdown.is_shutting_down() {
+                    res.headers.set(headers::Connection::close());
+                }
+
+                if self.max_keep_alive_requests.map_or(false, |limit| served >= limit) {
+                    res.headers.set(headers::Connection::close());
+                }
+
+                self.server_header.apply(&mut res.headers);
+                match self.metrics {
+                    Some(ref metrics) => {
+                        write_measured(&**metrics, &mut req, res, http_res, is_head, started,
+                                       bytes_read)
+                    },
+                    None => write_back(res, http_res, is_head, &mut req.body)
+                }
             },
             Err(e) => {
                 error!("Error creating request:\n    {}", e);
-                bad_request(http_res)
+                self.server_header.apply(http_res.headers_mut());
+                reject(http_res, status::BadRequest)
             }
         }
     }
+
+    fn check_continue(&self, (method, uri, headers): (&Method, &RequestUri, &Headers)) -> Status {
+        match (headers.get::<headers::ContentLength>(), self.max_body_bytes) {
+            (Some(&headers::ContentLength(len)), Some(max)) if len > max => {
+                status::ExpectationFailed
+            },
+            _ => self.handler.check_continue(method, uri, headers)
+        }
+    }
+
+    fn on_connection_start(&self) {
+        CONNECTION_REQUESTS.with(|served| served.set(0));
+        if let Some(ref metrics) = self.metrics {
+            metrics.connection_opened();
+        }
+    }
+
+    fn on_connection_end(&self) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.connection_closed();
+        }
+    }
+}
+
+// A request counted against `Iron::max_concurrent_requests`.
+//
+// The count is taken back on drop, so handlers which panic are accounted for.
+struct Admitted<'a>(&'a AtomicUsize);
+
+impl<'a> Drop for Admitted<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Are there at most `max_count` header lines, of at most `max_bytes` in all?
+fn headers_within_limits(headers: &Headers, max_bytes: usize, max_count: usize) -> bool {
+    let mut count = 0;
+    let mut bytes = 0;
+    for header in headers.iter() {
+        let name = header.name();
+        for line in headers.get_raw(name).unwrap_or(&[]) {
+            count += 1;
+            // `Name: value\r\n`
+            bytes += name.len() + 2 + line.len() + 2;
+        }
+    }
+
+    count <= max_count && bytes <= max_bytes
+}
+
+// The length of `uri` as it was sent in the request line.
+fn uri_len(uri: &RequestUri) -> usize {
+    match *uri {
+        RequestUri::AbsolutePath(ref path) => path.len(),
+        RequestUri::AbsoluteUri(ref url) => url.to_string().len(),
+        RequestUri::Authority(ref authority) => authority.len(),
+        RequestUri::Star => 1
+    }
 }
 
-fn bad_request(mut http_res: HttpResponse<Fresh>) {
-    *http_res.status_mut() = status::BadRequest;
+// Responses to `HEAD` requests never have a body, even if the handler gave
+// one, such as one written for `GET`. Others may upgrade the connection
+// `body` is read from.
+fn write_back(res: Response, http_res: HttpResponse<Fresh>, is_head: bool, body: &mut Body) {
+    if is_head {
+        res.write_back_head(http_res)
+    } else {
+        res.write_back_or_upgrade(http_res, body)
+    }
+}
+
+// Write `res` back, then report how it went to `metrics`.
+fn write_measured(metrics: &Metrics, req: &mut Request, mut res: Response,
+                  http_res: HttpResponse<Fresh>, is_head: bool, started: Instant,
+                  bytes_read: u64) {
+    let status = res.status.unwrap_or(status::NotFound);
+    let bytes_written = Arc::new(AtomicUsize::new(0));
+    res.body = res.body.take().map(|body| {
+        Box::new(CountedBody { body: body, bytes: bytes_written.clone() }) as Box<WriteBody>
+    });
+
+    write_back(res, http_res, is_head, &mut req.body);
+
+    metrics.request_finished(req, &RequestMetrics {
+        status: status,
+        duration: started.elapsed(),
+        bytes_read: bytes_read,
+        bytes_written: bytes_written.load(Ordering::Relaxed) as u64
+    });
+}
+
+// Fail early, naming the file, if part of the TLS configuration can't be read.
+#[cfg(feature = "ssl")]
+fn check_readable(what: &str, path: &Path) -> HttpResult<()> {
+    match File::open(path) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let message = format!("Couldn't open TLS {} {}: {}", what, path.display(), e);
+            Err(io::Error::new(e.kind(), message).into())
+        }
+    }
+}
+
+// The TLS configuration `Iron::https` serves with, which verifies client
+// certificates against `client_ca` if it is given.
+#[cfg(feature = "ssl")]
+fn tls_context(certificate: &Path, key: &Path, client_ca: Option<&PathBuf>)
+               -> Result<::hyper::net::Openssl, ::openssl::ssl::error::SslError> {
+    use hyper::net::Openssl;
+    use openssl::ssl::{SslContext, SslMethod, SSL_VERIFY_NONE, SSL_VERIFY_PEER,
+                       SSL_VERIFY_FAIL_IF_NO_PEER_CERT};
+    use openssl::x509::X509FileType;
+
+    let mut ctx = try!(SslContext::new(SslMethod::Sslv23));
+    try!(ctx.set_cipher_list("DEFAULT"));
+    try!(ctx.set_certificate_file(certificate, X509FileType::PEM));
+    try!(ctx.set_private_key_file(key, X509FileType::PEM));
+    match client_ca {
+        Some(ca) => {
+            try!(ctx.set_CA_file(ca));
+            ctx.set_verify(SSL_VERIFY_PEER | SSL_VERIFY_FAIL_IF_NO_PEER_CERT, None);
+        },
+        None => ctx.set_verify(SSL_VERIFY_NONE, None)
+    }
+    Ok(Openssl { context: Arc::new(ctx) })
+}
+
+fn reject(mut http_res: HttpResponse<Fresh>, status: Status) {
+    *http_res.status_mut() = status;
+    // The request's body is left unread, so the connection can't be reused.
+    http_res.headers_mut().set(headers::Connection::close());
 
     // Consume and flush the response.
     // We would like this to work, but can't do anything if it doesn't.
@@ -218,3 +961,592 @@ fn bad_request(mut http_res: HttpResponse<Fresh>) {
     }
 }
 
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use prelude::*;
+    use {status, Headers, Metrics, RequestMetrics};
+
+    use super::{headers_within_limits, Timeouts};
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+               path).unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        res
+    }
+
+    #[test]
+    fn test_threads() {
+        let (started_tx, started) = channel();
+        let (release, release_rx) = channel::<()>();
+        let (started_tx, release_rx) = (Mutex::new(started_tx), Mutex::new(release_rx));
+
+        let handler = move |_: &mut Request| {
+            started_tx.lock().unwrap().send(()).unwrap();
+            release_rx.lock().unwrap().recv().unwrap();
+            Ok(Response::with((status::Ok, "done")))
+        };
+        let iron = Iron::new(handler).threads(3);
+        assert_eq!(iron.threads, 3);
+        let (mut listening, signal) = iron.http_with_shutdown("127.0.0.1:0").unwrap();
+        let addr = listening.socket;
+
+        // Each connection holds a thread, so all three are handled at once.
+        let clients = (0..3).map(|_| thread::spawn(move || get(addr, "/"))).collect::<Vec<_>>();
+        for _ in 0..3 {
+            started.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        for _ in 0..3 {
+            release.send(()).unwrap();
+        }
+        for client in clients {
+            assert!(client.join().unwrap().ends_with("\r\n\r\ndone"));
+        }
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "0 threads")]
+    fn test_zero_threads() {
+        Iron::new(|_: &mut Request| Ok(Response::with(status::Ok))).threads(0);
+    }
+
+    #[test]
+    fn test_timeout_setters() {
+        let iron = Iron::new(|_: &mut Request| Ok(Response::with(status::Ok)))
+            .read_timeout(Some(Duration::from_secs(1)))
+            .write_timeout(Some(Duration::from_secs(2)))
+            .keep_alive(None);
+        assert_eq!(iron.timeouts, Timeouts {
+            keep_alive: None,
+            read: Some(Duration::from_secs(1)),
+            write: Some(Duration::from_secs(2))
+        });
+
+        let iron = iron.timeouts(Timeouts::default()).write_timeout(None);
+        assert_eq!(iron.timeouts, Timeouts { write: None, ..Timeouts::default() });
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let handler = |req: &mut Request| {
+            let mut body = Vec::new();
+            let kind = req.body.read_to_end(&mut body).err().map(|e| e.kind());
+            Ok(Response::with((status::Ok, format!("{:?}", kind))))
+        };
+        let (mut listening, signal) = Iron::new(handler).threads(2)
+            .read_timeout(Some(Duration::from_millis(200)))
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        // Half the body is sent, then nothing.
+        let mut stream = TcpStream::connect(listening.socket).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        write!(stream, "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nhello")
+            .unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.contains("Connection: close\r\n"));
+        assert!(res.ends_with("\r\n\r\nSome(TimedOut)"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_max_concurrent_requests() {
+        let (started_tx, started) = channel();
+        let (release, release_rx) = channel::<()>();
+        let (started_tx, release_rx) = (Mutex::new(started_tx), Mutex::new(release_rx));
+
+        let handler = move |req: &mut Request| {
+            match &*req.url.path[0] {
+                "slow" => {
+                    started_tx.lock().unwrap().send(()).unwrap();
+                    release_rx.lock().unwrap().recv().unwrap();
+                },
+                "panic" => panic!("Handler panicked"),
+                _ => ()
+            }
+            Ok(Response::with((status::Ok, "done")))
+        };
+
+        let (mut listening, signal) = Iron::new(handler).threads(4).max_concurrent_requests(1)
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+        let addr = listening.socket;
+
+        let slow = thread::spawn(move || get(addr, "/slow"));
+        started.recv().unwrap();
+
+        let res = get(addr, "/fast");
+        assert!(res.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(res.contains("Retry-After: 1\r\n"));
+
+        release.send(()).unwrap();
+        assert!(slow.join().unwrap().ends_with("\r\n\r\ndone"));
+
+        // Panicking handlers give their place back too.
+        assert!(get(addr, "/panic").starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(get(addr, "/fast").ends_with("\r\n\r\ndone"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_graceful_shutdown() {
+        let (started_tx, started) = channel();
+        let (release, release_rx) = channel::<()>();
+        let (started_tx, release_rx) = (Mutex::new(started_tx), Mutex::new(release_rx));
+
+        let handler = move |_: &mut Request| {
+            started_tx.lock().unwrap().send(()).unwrap();
+            release_rx.lock().unwrap().recv().unwrap();
+            Ok(Response::with((status::Ok, "done")))
+        };
+        let (mut listening, signal) = Iron::new(handler).threads(2)
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+        let addr = listening.socket;
+
+        // Without `Connection: close`, so only the shutdown ends the connection.
+        let slow = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut res = String::new();
+            stream.read_to_string(&mut res).map(|_| res)
+        });
+        started.recv().unwrap();
+
+        let shutdown = thread::spawn(move || signal.shutdown(Duration::from_secs(5)));
+        thread::sleep(Duration::from_millis(100));
+
+        // The request in flight is still answered, and told to close.
+        release.send(()).unwrap();
+        let res = slow.join().unwrap().unwrap();
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(res.contains("Connection: close\r\n"));
+        assert!(res.ends_with("\r\n\r\ndone"));
+        assert!(shutdown.join().unwrap());
+
+        // The socket closes once the acceptor which served it lets go.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while TcpStream::connect(addr).is_ok() {
+            assert!(Instant::now() < deadline, "still accepting connections");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_max_keep_alive_requests() {
+        let handler = |_: &mut Request| Ok(Response::with((status::Ok, "done")));
+        let (mut listening, signal) = Iron::new(handler).threads(2).max_keep_alive_requests(2)
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        // Pipeline two requests; the connection closes after the second
+        // rather than waiting for another. A third request sent along would
+        // be left unread, and closing with unread data resets the connection.
+        let mut stream = TcpStream::connect(listening.socket).unwrap();
+        for _ in 0..2 {
+            write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        }
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert_eq!(res.matches("HTTP/1.1 200 OK\r\n").count(), 2);
+        assert_eq!(res.matches("Connection: close\r\n").count(), 1);
+
+        // New connections start counting again.
+        assert!(get(listening.socket, "/").ends_with("\r\n\r\ndone"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_before_write() {
+        let mut chain = Chain::new(|req: &mut Request| {
+            if req.url.path[0] == "fail" {
+                return Err(IronError::new(::error::NotFound, status::NotFound));
+            }
+            Ok(Response::with((status::Ok, "done")))
+        });
+        chain.link_after(|_: &mut Request, mut res: Response| {
+            res.headers.set_raw("X-Stage", vec![b"after".to_vec()]);
+            Ok(res)
+        });
+
+        // Iron has to close connections after one request, whatever the hook says.
+        let (mut listening, signal) = Iron::new(chain).threads(2).max_keep_alive_requests(1)
+            .on_error(|_: &IronError, _: &mut Request| {
+                Response::with((status::NotFound, "rendered"))
+            })
+            .before_write(|_: &mut Request, res: &mut Response| {
+                res.headers.set_raw("X-Stage", vec![b"before-write".to_vec()]);
+                res.headers.set(::headers::Connection::keep_alive());
+            })
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        let res = get(listening.socket, "/ok");
+        assert!(res.contains("X-Stage: before-write\r\n"));
+        assert!(res.contains("Connection: close\r\n"));
+
+        let res = get(listening.socket, "/fail");
+        assert!(res.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(res.contains("X-Stage: before-write\r\n"));
+        assert!(res.ends_with("\r\n\r\nrendered"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let handler = |req: &mut Request| {
+            if !req.is_upgrade() {
+                return Ok(Response::with((status::BadRequest, "not an upgrade")));
+            }
+            Ok(Response::upgrade("echo", |mut conn| {
+                let mut buf = [0; 4];
+                conn.read_exact(&mut buf).unwrap();
+                conn.write_all(&buf).unwrap();
+            }))
+        };
+        let (mut listening, signal) = Iron::new(handler).threads(2)
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        // The first bytes of the new protocol are sent along with the
+        // request, so the server may already have read them.
+        let mut stream = TcpStream::connect(listening.socket).unwrap();
+        write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\n\
+                        Upgrade: echo\r\n\r\nping").unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(res.contains("Upgrade: echo\r\n"));
+        assert!(res.ends_with("\r\n\r\nping"));
+
+        assert!(get(listening.socket, "/").ends_with("\r\n\r\nnot an upgrade"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_headers_within_limits() {
+        let mut headers = Headers::new();
+        // `Host: localhost\r\n` is 17 bytes, and each `X-Id: n\r\n` 9.
+        headers.set_raw("Host", vec![b"localhost".to_vec()]);
+        headers.set_raw("X-Id", vec![b"1".to_vec(), b"2".to_vec()]);
+
+        assert!(headers_within_limits(&headers, 35, 3));
+        assert!(!headers_within_limits(&headers, 34, 3));
+        assert!(!headers_within_limits(&headers, 35, 2));
+    }
+
+    #[test]
+    fn test_max_header_count() {
+        let handler = |_: &mut Request| Ok(Response::with((status::Ok, "done")));
+        let (mut listening, signal) = Iron::new(handler).threads(2).max_header_count(2)
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        assert!(get(listening.socket, "/").ends_with("\r\n\r\ndone"));
+
+        let mut stream = TcpStream::connect(listening.socket).unwrap();
+        write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\nX-Id: 1\r\nX-Id: 2\r\n\r\n").unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+        assert!(res.contains("Connection: close\r\n"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_framing_checked_before_transfer_codings() {
+        let handler = |_: &mut Request| Ok(Response::with((status::Ok, "done")));
+        let (mut listening, signal) = Iron::new(handler).threads(2)
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        let mut stream = TcpStream::connect(listening.socket).unwrap();
+        write!(stream, "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\
+                        Transfer-Encoding: compress, chunked\r\n\r\n").unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Metrics for Recorder {
+        fn connection_opened(&self) {
+            self.0.lock().unwrap().push("opened".to_owned());
+        }
+
+        fn connection_closed(&self) {
+            self.0.lock().unwrap().push("closed".to_owned());
+        }
+
+        fn request_started(&self, req: &Request) {
+            self.0.lock().unwrap().push(format!("started {}", req.url.path.join("/")));
+        }
+
+        fn request_finished(&self, _: &Request, metrics: &RequestMetrics) {
+            self.0.lock().unwrap().push(format!("finished {} {} {}", metrics.status,
+                                                metrics.bytes_read, metrics.bytes_written));
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let handler = |req: &mut Request| {
+            let mut body = String::new();
+            req.body.read_to_string(&mut body).unwrap();
+            Ok(Response::with((status::Ok, "done")))
+        };
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (mut listening, signal) = Iron::new(handler).threads(2)
+            .metrics(Recorder(events.clone()))
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        let mut stream = TcpStream::connect(listening.socket).unwrap();
+        write!(stream, "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\
+                        Connection: close\r\n\r\nhello").unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.ends_with("\r\n\r\ndone"));
+
+        // The connection is reported closed once the server is done with it,
+        // which may be after the client has seen it close.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while events.lock().unwrap().len() < 4 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let events = events.lock().unwrap().clone();
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+        assert_eq!(events, ["opened", "started upload", "finished 200 OK 5 4", "closed"]);
+    }
+
+    #[test]
+    fn test_max_body_bytes() {
+        let handler = |req: &mut Request| {
+            let mut body = String::new();
+            Ok(match req.body.read_to_string(&mut body) {
+                Ok(_) => Response::with((status::Ok, body)),
+                Err(_) => Response::with((status::PayloadTooLarge, "too large"))
+            })
+        };
+        let (mut listening, signal) = Iron::new(handler).threads(2).max_body_bytes(5)
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        let request = |len: usize, expect: bool| {
+            let mut stream = TcpStream::connect(listening.socket).unwrap();
+            write!(stream, "PUT / HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\
+                            Connection: close\r\n{}\r\n",
+                   len, if expect { "Expect: 100-continue\r\n" } else { "" }).unwrap();
+            if expect {
+                let mut interim = [0; 25];
+                stream.read_exact(&mut interim).unwrap();
+                if &interim[..] != b"HTTP/1.1 100 Continue\r\n\r\n" {
+                    let mut res = String::from_utf8_lossy(&interim).into_owned();
+                    stream.read_to_string(&mut res).unwrap();
+                    return res;
+                }
+            }
+            stream.write_all("x".repeat(len).as_bytes()).unwrap();
+            let mut res = String::new();
+            stream.read_to_string(&mut res).unwrap();
+            res
+        };
+
+        assert!(request(5, true).ends_with("\r\n\r\nxxxxx"));
+        assert!(request(6, true).starts_with("HTTP/1.1 417 Expectation Failed\r\n"));
+        assert!(request(6, false).ends_with("\r\n\r\ntoo large"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_server_header() {
+        type HandlerFn = fn(&mut Request) -> IronResult<Response>;
+
+        fn handler(req: &mut Request) -> IronResult<Response> {
+            let mut res = Response::with((status::Ok, "done"));
+            if req.url.path[0] == "own" {
+                res.headers.set(::headers::Server("handler/1.0".to_owned()));
+            }
+            Ok(res)
+        }
+
+        // The responses to `/` and `/own` from a server set up by `configure`.
+        fn serve(configure: fn(Iron<HandlerFn>) -> Iron<HandlerFn>) -> (String, String) {
+            let iron = Iron::new(handler as HandlerFn);
+            let (mut listening, signal) = configure(iron).threads(2)
+                .http_with_shutdown("127.0.0.1:0").unwrap();
+            let responses = (get(listening.socket, "/"), get(listening.socket, "/own"));
+            signal.shutdown(Duration::from_secs(1));
+            listening.close().unwrap();
+            responses
+        }
+
+        let (plain, own) = serve(|iron| iron);
+        assert!(!plain.contains("Server:"));
+        assert!(own.contains("Server: handler/1.0\r\n"));
+
+        let (plain, own) = serve(|iron| iron.server_header("example/1.0"));
+        assert!(plain.contains("Server: example/1.0\r\n"));
+        assert!(own.contains("Server: handler/1.0\r\n"));
+        assert!(!own.contains("example/1.0"));
+
+        let (plain, own) = serve(|iron| iron.hide_server_header());
+        assert!(!plain.contains("Server:"));
+        assert!(!own.contains("Server:"));
+    }
+
+    #[test]
+    fn test_max_uri_length() {
+        let handler = |_: &mut Request| Ok(Response::with((status::Ok, "done")));
+        let (mut listening, signal) = Iron::new(handler).threads(2).max_uri_length(16)
+            .http_with_shutdown("127.0.0.1:0").unwrap();
+
+        assert!(get(listening.socket, "/0123456789abcde").ends_with("\r\n\r\ndone"));
+        assert!(get(listening.socket, "/0123456789abcdef")
+                .starts_with("HTTP/1.1 414 URI Too Long\r\n"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+
+    // Write a new self-signed certificate for `common_name`, and its key, to
+    // PEM files in the temporary directory.
+    #[cfg(feature = "ssl")]
+    fn self_signed(common_name: &str) -> (::std::path::PathBuf, ::std::path::PathBuf) {
+        use std::env;
+        use std::fs::File;
+        use std::process;
+
+        use openssl::crypto::hash::Type;
+        use openssl::x509::X509Generator;
+
+        let (cert, key) = X509Generator::new()
+            .set_bitlength(2048)
+            .add_name("CN".to_owned(), common_name.to_owned())
+            .set_sign_hash(Type::SHA256)
+            .generate().unwrap();
+
+        let prefix = format!("iron-test-{}-{}", process::id(), common_name);
+        let cert_path = env::temp_dir().join(format!("{}-cert.pem", prefix));
+        let key_path = env::temp_dir().join(format!("{}-key.pem", prefix));
+        cert.write_pem(&mut File::create(&cert_path).unwrap()).unwrap();
+        key.write_pem(&mut File::create(&key_path).unwrap()).unwrap();
+        (cert_path, key_path)
+    }
+
+    // Send a `GET /` over TLS with `ctx`, which doesn't verify the server's
+    // certificate. Any failure is returned rather than panicking, since a
+    // panic would wait forever on a server which hasn't been closed.
+    #[cfg(feature = "ssl")]
+    fn get_tls(addr: SocketAddr, ctx: &::openssl::ssl::SslContext) -> Option<String> {
+        use openssl::ssl::SslStream;
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut stream = match SslStream::connect(ctx, stream) {
+            Ok(stream) => stream,
+            Err(_) => return None
+        };
+        let mut res = String::new();
+        write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .and_then(|_| stream.read_to_string(&mut res)).ok().map(|_| res)
+    }
+
+    #[cfg(feature = "ssl")]
+    #[test]
+    fn test_https() {
+        use std::fs;
+        use std::path::PathBuf;
+
+        use openssl::ssl::{SslContext, SslMethod};
+
+        let (cert, key) = self_signed("localhost");
+        let handler = |req: &mut Request| Ok(Response::with((status::Ok, req.url.scheme.clone())));
+        let mut listening = Iron::new(handler).threads(2)
+            .https("127.0.0.1:0", cert.clone(), key.clone()).unwrap();
+        let res = get_tls(listening.socket, &SslContext::new(SslMethod::Sslv23).unwrap());
+        listening.close().unwrap();
+
+        // Files which can't be read are named before anything is bound.
+        let missing = PathBuf::from("/nonexistent/iron-key.pem");
+        let err = Iron::new(handler).threads(2)
+            .https("127.0.0.1:0", cert.clone(), missing).err().unwrap();
+
+        for path in &[cert, key] {
+            let _ = fs::remove_file(path);
+        }
+
+        let res = res.unwrap();
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(res.ends_with("\r\n\r\nhttps"));
+        assert!(err.to_string().contains("/nonexistent/iron-key.pem"));
+    }
+
+    #[cfg(feature = "ssl")]
+    #[test]
+    fn test_client_certificates() {
+        use std::fs;
+
+        use openssl::ssl::{SslContext, SslMethod};
+        use openssl::x509::X509FileType;
+
+        let (cert, key) = self_signed("localhost");
+        let (client_cert, client_key) = self_signed("client");
+
+        let handler = |req: &mut Request| {
+            let tls = req.tls_info().unwrap();
+            let body = format!("{} {} {}", req.url.scheme, tls.version,
+                               tls.peer_common_name.unwrap_or_default());
+            Ok(Response::with((status::Ok, body)))
+        };
+        // The client certificate is self-signed, so is its own CA.
+        let mut listening = Iron::new(handler).threads(2)
+            .client_certificates(Some(client_cert.clone()))
+            .https("127.0.0.1:0", cert.clone(), key.clone()).unwrap();
+
+        let mut ctx = SslContext::new(SslMethod::Sslv23).unwrap();
+        ctx.set_certificate_file(&client_cert, X509FileType::PEM).unwrap();
+        ctx.set_private_key_file(&client_key, X509FileType::PEM).unwrap();
+        let verified = get_tls(listening.socket, &ctx);
+        let anonymous = get_tls(listening.socket, &SslContext::new(SslMethod::Sslv23).unwrap());
+
+        listening.close().unwrap();
+        for path in &[cert, key, client_cert, client_key] {
+            let _ = fs::remove_file(path);
+        }
+
+        let verified = verified.unwrap();
+        assert!(verified.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(verified.contains("\r\n\r\nhttps TLSv1"));
+        assert!(verified.ends_with(" client"));
+
+        // Clients without a certificate never reach the handler.
+        assert!(!anonymous.unwrap_or_default().starts_with("HTTP/1.1 200"));
+    }
+}
@@ -1,6 +1,5 @@
 #![doc(html_logo_url = "https://avatars0.githubusercontent.com/u/7853871?s=128", html_favicon_url = "https://avatars0.githubusercontent.com/u/7853871?s=256", html_root_url = "http://ironframework.io/core/iron")]
 #![cfg_attr(test, deny(warnings))]
-#![feature(box_syntax)]
 
 //! The main crate for Iron.
 //!
@@ -69,9 +68,22 @@ extern crate plugin;
 extern crate error as err;
 extern crate url;
 extern crate num_cpus;
+extern crate time;
+extern crate flate2;
+extern crate rustc_serialize as serialize;
+extern crate tempfile;
 extern crate conduit_mime_types as mime_types;
 #[macro_use]
 extern crate lazy_static;
+#[cfg(feature = "ssl")]
+extern crate openssl;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
 
 // Request + Response
 pub use request::{Request, Url};
@@ -79,7 +91,7 @@ pub use response::Response;
 
 // Middleware system
 pub use middleware::{BeforeMiddleware, AfterMiddleware, AroundMiddleware,
-                     Handler, Chain};
+                     BeforeAction, Handler, Chain};
 
 // Server
 pub use iron::*;
@@ -159,6 +171,12 @@ pub mod response;
 // Request utilities
 pub mod request;
 
+// Testing utilities
+pub mod test;
+
+// Cookie utilities
+pub mod cookie;
+
 // Request and Response Modifiers
 pub mod modifiers;
 
@@ -166,3 +184,10 @@ pub mod modifiers;
 mod macros;
 
 mod iron;
+
+mod metrics;
+
+mod shutdown;
+
+#[cfg(unix)]
+mod unix;
@@ -0,0 +1,109 @@
+//! Hooks for collecting server metrics.
+
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use Request;
+use response::{ResponseBody, WriteBody};
+use status::Status;
+
+/// Receives events about the connections and requests handled by a server,
+/// to forward to a metrics system such as Prometheus or statsd.
+///
+/// Register an implementation with `Iron::metrics`. Every method does nothing
+/// by default, so only the events of interest need implementing. Methods are
+/// called from the server's worker threads, so they should be quick.
+///
+/// ```no_run
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use iron::{Metrics, RequestMetrics};
+///
+/// struct ActiveConnections(AtomicUsize);
+///
+/// impl Metrics for ActiveConnections {
+///     fn connection_opened(&self) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn connection_closed(&self) {
+///         self.0.fetch_sub(1, Ordering::Relaxed);
+///     }
+///
+///     fn request_finished(&self, _: &Request, metrics: &RequestMetrics) {
+///         println!("{} in {:?}", metrics.status, metrics.duration);
+///     }
+/// }
+///
+/// Iron::new(|_: &mut Request| Ok(Response::with(status::Ok)))
+///     .metrics(ActiveConnections(AtomicUsize::new(0)))
+///     .http("localhost:3000").unwrap();
+/// ```
+pub trait Metrics: Send + Sync + 'static {
+    /// A connection has been accepted.
+    fn connection_opened(&self) {}
+
+    /// A connection is being closed, after all of its requests.
+    fn connection_closed(&self) {}
+
+    /// A request has been parsed and is about to be handled.
+    fn request_started(&self, _: &Request) {}
+
+    /// The response to a request has been written, or failed to be.
+    ///
+    /// This isn't called if the handler panics.
+    fn request_finished(&self, _: &Request, _: &RequestMetrics) {}
+}
+
+/// Measurements of a request, given to `Metrics::request_finished`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestMetrics {
+    /// The status of the response.
+    pub status: Status,
+
+    /// The time from the start of handling the request until the response
+    /// was written.
+    pub duration: Duration,
+
+    /// The number of bytes of the request body read by the handler.
+    pub bytes_read: u64,
+
+    /// The number of bytes of response body written, excluding headers and
+    /// any chunked encoding.
+    pub bytes_written: u64
+}
+
+// Counts the bytes written by the body it wraps, for `Iron::metrics` and the
+// `Logger`.
+pub struct CountedBody {
+    pub body: Box<WriteBody>,
+    pub bytes: Arc<AtomicUsize>
+}
+
+impl WriteBody for CountedBody {
+    fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()> {
+        let mut counter = Counter { inner: res, bytes: &self.bytes };
+        let written = self.body.write_body(&mut ResponseBody::new(&mut counter));
+        written
+    }
+}
+
+struct Counter<'a, 'b: 'a> {
+    inner: &'a mut ResponseBody<'b>,
+    bytes: &'a AtomicUsize
+}
+
+impl<'a, 'b> Write for Counter<'a, 'b> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = try!(self.inner.write(buf));
+        self.bytes.fetch_add(written, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,123 @@
+//! HTTP Basic authentication.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::Arc;
+
+use {Request, IronResult, IronError, BeforeMiddleware};
+use status;
+
+/// Middleware which only lets requests through if they have `Authorization:
+/// Basic` credentials accepted by a verifier.
+///
+/// Other requests are rejected with a `401 Unauthorized` error asking for
+/// credentials with `WWW-Authenticate`, so browsers prompt for them.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::BasicAuth;
+///
+/// let mut chain = Chain::new(|_: &mut Request| Ok(Response::with((status::Ok, "Secret"))));
+/// chain.link_before(BasicAuth::new("Admin", |username, password| {
+///     username == "admin" && password == "hunter2"
+/// }));
+/// ```
+///
+/// Basic credentials are sent unencrypted, so only use this over HTTPS.
+#[derive(Clone)]
+pub struct BasicAuth {
+    realm: String,
+    verifier: Arc<Fn(&str, &str) -> bool + Send + Sync>
+}
+
+/// The error produced by `BasicAuth` when a request lacks valid credentials.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unauthorized {
+    /// The username the request was sent with, if it had credentials.
+    pub username: Option<String>
+}
+
+impl fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.username {
+            Some(ref username) => write!(f, "Invalid credentials for user {:?}", username),
+            None => f.write_str("Missing credentials")
+        }
+    }
+}
+
+impl StdError for Unauthorized {
+    fn description(&self) -> &str {
+        "Missing or invalid credentials"
+    }
+}
+
+impl BasicAuth {
+    /// Create a `BasicAuth` for `realm`, which browsers show when asking
+    /// for credentials.
+    ///
+    /// `verifier` is called with the username and password of each request,
+    /// and should return whether they are valid.
+    pub fn new<F>(realm: &str, verifier: F) -> BasicAuth
+    where F: Fn(&str, &str) -> bool + Send + Sync + 'static {
+        BasicAuth { realm: realm.to_owned(), verifier: Arc::new(verifier) }
+    }
+
+    fn challenge(&self) -> String {
+        let realm = self.realm.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("Basic realm=\"{}\", charset=\"UTF-8\"", realm)
+    }
+}
+
+impl BeforeMiddleware for BasicAuth {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let username = match req.basic_auth() {
+            Some((ref username, ref password)) if (self.verifier)(username, password) => {
+                return Ok(())
+            },
+            Some((username, _)) => Some(username),
+            None => None
+        };
+
+        let mut err = IronError::new(Unauthorized { username: username }, status::Unauthorized);
+        err.response.headers.set_raw("WWW-Authenticate", vec![self.challenge().into_bytes()]);
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prelude::*;
+    use {method, status, test, Handler, Url};
+
+    use super::{BasicAuth, Unauthorized};
+
+    fn handle(authorization: Option<&str>) -> IronResult<Response> {
+        let mut chain = Chain::new(|_: &mut Request| Ok(Response::with(status::Ok)));
+        chain.link_before(BasicAuth::new("Say \"friend\"", |user, password| {
+            user == "aladdin" && password == "open:sesame"
+        }));
+
+        let headers: Vec<_> = authorization.into_iter()
+            .map(|authorization| ("Authorization", authorization)).collect();
+        test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                   &headers, |req| chain.handle(req))
+    }
+
+    #[test]
+    fn test_basic_auth_middleware() {
+        assert_eq!(handle(Some("Basic YWxhZGRpbjpvcGVuOnNlc2FtZQ==")).unwrap().status,
+                   Some(status::Ok));
+
+        let err = handle(None).unwrap_err();
+        assert_eq!(err.response.status, Some(status::Unauthorized));
+        assert_eq!(err.response.headers.get_raw("WWW-Authenticate").map(|v| v[0].clone()),
+                   Some(b"Basic realm=\"Say \\\"friend\\\"\", charset=\"UTF-8\"".to_vec()));
+
+        // aladdin:wrong
+        let err = handle(Some("Basic YWxhZGRpbjp3cm9uZw==")).unwrap_err();
+        assert_eq!(err.error.downcast::<Unauthorized>(),
+                   Some(&Unauthorized { username: Some("aladdin".to_owned()) }));
+    }
+}
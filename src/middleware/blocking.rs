@@ -0,0 +1,187 @@
+//! Handlers from `FnMut` closures, and running blocking work on a pool of
+//! threads of its own.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use {Request, Response, IronResult, Handler};
+
+/// A `Handler` made from a closure which needs `&mut` access to its state,
+/// such as a counter, which plain `Fn` handlers can't have.
+///
+/// Handlers are called from many threads at once, so the closure is kept
+/// behind a lock and only handles one request at a time. It must be `Send`,
+/// but needn't be `Sync`. Keep it quick, or use shared state with its own
+/// locking and a plain `Fn` closure instead.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::FnMutHandler;
+///
+/// let mut hits = 0;
+/// let handler = FnMutHandler::new(move |_: &mut Request| {
+///     hits += 1;
+///     Ok(Response::with((status::Ok, format!("Hit {} times", hits))))
+/// });
+/// let chain = Chain::new(handler);
+/// ```
+pub struct FnMutHandler<F>(Mutex<F>);
+
+impl<F> FnMutHandler<F> where F: FnMut(&mut Request) -> IronResult<Response> + Send + 'static {
+    /// Wrap `handler` so it can be used as a `Handler`.
+    pub fn new(handler: F) -> FnMutHandler<F> {
+        FnMutHandler(Mutex::new(handler))
+    }
+}
+
+impl<F> Handler for FnMutHandler<F>
+where F: FnMut(&mut Request) -> IronResult<Response> + Send + 'static {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        // A handler which panicked is still usable, as it would be unlocked.
+        let mut handler = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        (*handler)(req)
+    }
+}
+
+type Job = Box<FnMut() + Send>;
+
+/// A fixed number of threads for blocking work, such as database queries,
+/// which handlers hand jobs to and wait for.
+///
+/// The handler's thread still waits for its job, but no more than `threads`
+/// jobs run at once however many requests are being handled, so a slow
+/// database can't take up every server thread and keep cheap requests, such
+/// as health checks, waiting. Jobs queue until a thread is free.
+///
+/// Jobs run on other threads, so they must be `Send` and `'static`: copy
+/// what they need out of the `Request` first, and build the `Response` from
+/// their result. `BlockingPool` is cheap to clone, and the threads stop once
+/// every clone has been dropped.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::BlockingPool;
+///
+/// # fn query_user(id: &str) -> String { id.to_owned() }
+/// let pool = BlockingPool::new(4);
+///
+/// let chain = Chain::new(move |req: &mut Request| {
+///     let id = req.url.path.join("/");
+///     let user = pool.run(move || query_user(&id));
+///     Ok(Response::with((status::Ok, user)))
+/// });
+/// ```
+#[derive(Clone)]
+pub struct BlockingPool {
+    jobs: Arc<Mutex<Sender<Job>>>
+}
+
+impl BlockingPool {
+    /// Start a `BlockingPool` of `threads` threads.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `threads` is `0`.
+    pub fn new(threads: usize) -> BlockingPool {
+        assert!(threads != 0, "A BlockingPool needs at least one thread.");
+
+        let (jobs, receiver) = channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads {
+            let receiver = receiver.clone();
+            thread::spawn(move || work(&receiver));
+        }
+
+        BlockingPool { jobs: Arc::new(Mutex::new(jobs)) }
+    }
+
+    /// Run `job` on one of the pool's threads, and wait for its result.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `job` panics, so the request fails as it would had `job` run
+    /// on the handler's thread. The pool's thread carries on with other jobs.
+    pub fn run<F, R>(&self, job: F) -> R
+    where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+        let (result, received) = channel();
+        let mut job = Some(job);
+        let job: Job = Box::new(move || {
+            if let Some(job) = job.take() {
+                let _ = result.send(panic::catch_unwind(AssertUnwindSafe(job)));
+            }
+        });
+
+        self.jobs.lock().unwrap().send(job).expect("BlockingPool threads have stopped.");
+        match received.recv().expect("BlockingPool threads have stopped.") {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload)
+        }
+    }
+}
+
+// Run jobs until every `BlockingPool` using `receiver` has been dropped.
+fn work(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(mut job) => job(),
+            Err(_) => return
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    use prelude::*;
+    use response::ResponseBody;
+    use {method, status, test, Handler, Url};
+
+    use super::{FnMutHandler, BlockingPool};
+
+    #[test]
+    fn test_fn_mut_handler() {
+        let mut hits = 0;
+        let handler = FnMutHandler::new(move |_: &mut Request| {
+            hits += 1;
+            Ok(Response::with((status::Ok, hits.to_string())))
+        });
+
+        for expected in &["1", "2"] {
+            let url = Url::parse("http://localhost:3000/").unwrap();
+            test::request_with_headers(method::Get, url, &[], |req| {
+                let mut res = handler.handle(req).unwrap();
+                let mut out = vec![];
+                res.body.take().unwrap().write_body(&mut ResponseBody::new(&mut out)).unwrap();
+                assert_eq!(out, expected.as_bytes());
+            });
+        }
+    }
+
+    #[test]
+    fn test_blocking_pool() {
+        let pool = BlockingPool::new(2);
+        assert_eq!(pool.run(|| 1 + 1), 2);
+
+        // Both threads run jobs at once.
+        let barrier = Arc::new(Barrier::new(2));
+        let waiters = (0..2).map(|_| {
+            let (pool, barrier) = (pool.clone(), barrier.clone());
+            thread::spawn(move || pool.run(move || { barrier.wait(); }))
+        }).collect::<Vec<_>>();
+        for waiter in waiters { waiter.join().unwrap() }
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.run(|| panic!("Job panicked"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(pool.run(|| "still running"), "still running");
+    }
+}
@@ -0,0 +1,490 @@
+//! Caching responses to `GET` requests.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use typemap::Key;
+
+use {Request, Response, IronResult, Headers, Set, BeforeMiddleware, AfterMiddleware};
+use middleware::BeforeAction;
+use response::{ResponseBody, WriteBody};
+use headers::{self, CacheDirective};
+use method;
+use status::{self, Status};
+
+/// A response kept by `ResponseCache`, along with what is needed to decide
+/// whether it can be served again.
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    /// The status of the response.
+    pub status: Status,
+
+    /// The headers of the response, as they were when it was stored.
+    pub headers: Headers,
+
+    /// The whole body of the response.
+    pub body: Vec<u8>,
+
+    /// The lower-cased names of the request headers listed in the response's
+    /// `Vary` header, with the values the request had for them, if any.
+    pub vary: Vec<(String, Option<Vec<Vec<u8>>>)>,
+
+    /// When the response was stored.
+    pub stored_at: SystemTime,
+
+    /// How long after `stored_at` the response may be served.
+    pub max_age: Duration
+}
+
+impl CachedResponse {
+    /// How long ago the response was stored.
+    pub fn age(&self) -> Duration {
+        SystemTime::now().duration_since(self.stored_at).unwrap_or(Duration::from_secs(0))
+    }
+
+    /// Whether the response is young enough to be served.
+    pub fn is_fresh(&self) -> bool {
+        self.age() < self.max_age
+    }
+
+    // Did the request which produced this response send the same values for
+    // the headers it varies on as `req`?
+    fn matches(&self, req: &Request) -> bool {
+        self.vary.iter().all(|&(ref name, ref values)| {
+            req.headers.get_raw(name).map(|values| values.to_vec()) == *values
+        })
+    }
+
+    fn to_response(&self) -> Response {
+        let mut res = Response::new();
+        res.status = Some(self.status);
+        res.headers = self.headers.clone();
+        res.headers.remove::<headers::TransferEncoding>();
+        res.headers.set_raw("Age", vec![self.age().as_secs().to_string().into_bytes()]);
+        res.set(self.body.clone())
+    }
+}
+
+/// Where `ResponseCache` keeps responses, such as in memory or in a store
+/// shared between servers.
+///
+/// Keys are made with `Url::cache_key`. Stores may drop responses whenever
+/// they like, such as to make room or once they are no longer fresh.
+pub trait CacheStore: Send + Sync + 'static {
+    /// The response stored under `key`, if any.
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Store `response` under `key`, replacing any response already there.
+    fn put(&self, key: &str, response: CachedResponse);
+
+    /// Remove the response stored under `key`, if any.
+    fn remove(&self, key: &str);
+}
+
+/// A `CacheStore` which keeps up to a fixed number of responses in memory,
+/// dropping the least recently used to make room.
+pub struct MemoryStore {
+    capacity: usize,
+    entries: Mutex<Entries>
+}
+
+struct Entries {
+    responses: HashMap<String, CachedResponse>,
+    // Keys from least to most recently used.
+    order: VecDeque<String>
+}
+
+impl Entries {
+    fn touch(&mut self, key: &str) {
+        if let Some(index) = self.order.iter().position(|k| k == key) {
+            self.order.remove(index);
+        }
+        self.order.push_back(key.to_owned());
+    }
+}
+
+impl MemoryStore {
+    /// Create a `MemoryStore` which holds at most `capacity` responses.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `capacity` is `0`.
+    pub fn new(capacity: usize) -> MemoryStore {
+        assert!(capacity != 0, "A MemoryStore must be able to hold at least one response.");
+        MemoryStore {
+            capacity: capacity,
+            entries: Mutex::new(Entries { responses: HashMap::new(), order: VecDeque::new() })
+        }
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let response = entries.responses.get(key).cloned();
+        if response.is_some() { entries.touch(key) }
+        response
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.responses.insert(key.to_owned(), response);
+        entries.touch(key);
+
+        while entries.responses.len() > self.capacity {
+            match entries.order.pop_front() {
+                Some(oldest) => { entries.responses.remove(&oldest); },
+                None => break
+            }
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.responses.remove(key);
+        if let Some(index) = entries.order.iter().position(|k| k == key) {
+            entries.order.remove(index);
+        }
+    }
+}
+
+/// Middleware which stores responses to `GET` requests, and serves them to
+/// later requests for the same URL without calling the `Handler`.
+///
+/// `ResponseCache` is both a `BeforeMiddleware`, which answers requests from
+/// the cache, and an `AfterMiddleware`, which stores responses as they are
+/// written. Link it as both:
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::headers::{CacheControl, CacheDirective};
+/// use iron::middleware::{ResponseCache, MemoryStore};
+///
+/// let cache = ResponseCache::new(MemoryStore::new(1000));
+///
+/// let mut chain = Chain::new(|_: &mut Request| {
+///     let mut res = Response::with((status::Ok, "Expensive"));
+///     res.headers.set(CacheControl(vec![CacheDirective::MaxAge(60)]));
+///     Ok(res)
+/// });
+/// chain.link((cache.clone(), cache));
+/// ```
+///
+/// Only responses which say how long they stay fresh, with `max-age` or
+/// `s-maxage` in `Cache-Control`, are stored, and only with a status which
+/// RFC 7231 allows to be cached, such as `200 OK` or `404 Not Found`.
+/// Responses with `no-store`, `no-cache` or `private`, with a `Set-Cookie`
+/// header, or with `Vary: *` are never stored, and neither are bodies larger
+/// than the maximum body size.
+///
+/// Requests with an `Authorization` header or `Cache-Control: no-store` skip
+/// the cache altogether. With `no-cache` or a `max-age`, a request may not
+/// be served a stored response, or one older than it allows, but its fresh
+/// response is still stored.
+///
+/// One response is kept per URL, keyed by `Url::cache_key`. If it has a
+/// `Vary` header, it is only served to requests with the same values for the
+/// headers it names. Served responses get an `Age` header.
+#[derive(Clone)]
+pub struct ResponseCache {
+    store: Arc<CacheStore>,
+    max_body_size: usize
+}
+
+impl ResponseCache {
+    /// Create a `ResponseCache` which keeps responses in `store`, with bodies
+    /// of up to 1 MiB.
+    pub fn new<S: CacheStore>(store: S) -> ResponseCache {
+        ResponseCache { store: Arc::new(store), max_body_size: 1024 * 1024 }
+    }
+
+    /// Set the largest body which is stored, in bytes.
+    pub fn max_body_size(mut self, bytes: usize) -> ResponseCache {
+        self.max_body_size = bytes;
+        self
+    }
+}
+
+// Whether a request was answered from the cache, or else the key to store its
+// response under.
+struct CacheLookupKey;
+
+impl Key for CacheLookupKey { type Value = Lookup; }
+
+enum Lookup {
+    Hit,
+    Miss(String)
+}
+
+fn cache_control(headers: &Headers) -> Vec<CacheDirective> {
+    headers.get::<headers::CacheControl>().map_or_else(Vec::new, |cc| cc.0.clone())
+}
+
+// Does the request allow a response of `age` to be served from the cache?
+fn request_allows(directives: &[CacheDirective], age: Duration) -> bool {
+    directives.iter().all(|directive| match *directive {
+        CacheDirective::NoCache => false,
+        CacheDirective::MaxAge(max_age) => age.as_secs() <= max_age as u64,
+        _ => true
+    })
+}
+
+// How long `res` may be stored for, if at all.
+fn freshness(res: &Response) -> Option<Duration> {
+    match res.status {
+        Some(status::Ok) | Some(status::NonAuthoritativeInformation) | Some(status::NoContent) |
+        Some(status::MultipleChoices) | Some(status::MovedPermanently) | Some(status::NotFound) |
+        Some(status::MethodNotAllowed) | Some(status::Gone) | Some(status::UriTooLong) |
+        Some(status::NotImplemented) => (),
+        _ => return None
+    }
+
+    if res.headers.get_raw("Set-Cookie").is_some() { return None }
+    if vary_names(res).iter().any(|name| name == "*") { return None }
+
+    let mut max_age = None;
+    let mut shared_max_age = None;
+    for directive in cache_control(&res.headers) {
+        match directive {
+            CacheDirective::NoStore | CacheDirective::NoCache |
+            CacheDirective::Private => return None,
+            CacheDirective::MaxAge(seconds) => max_age = Some(seconds),
+            CacheDirective::SMaxAge(seconds) => shared_max_age = Some(seconds),
+            _ => ()
+        }
+    }
+
+    match shared_max_age.or(max_age) {
+        Some(0) | None => None,
+        Some(seconds) => Some(Duration::from_secs(seconds as u64))
+    }
+}
+
+// The lower-cased names in the `Vary` header of `res`.
+fn vary_names(res: &Response) -> Vec<String> {
+    res.headers.get_raw("Vary").unwrap_or(&[]).iter()
+        .flat_map(|line| line.split(|&b| b == b','))
+        .map(|name| String::from_utf8_lossy(name).trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+impl BeforeMiddleware for ResponseCache {
+    fn before_action(&self, req: &mut Request) -> IronResult<BeforeAction> {
+        if req.method != method::Get || req.headers.get_raw("Authorization").is_some() {
+            return Ok(BeforeAction::Continue);
+        }
+
+        let directives = cache_control(&req.headers);
+        if directives.contains(&CacheDirective::NoStore) { return Ok(BeforeAction::Continue) }
+
+        let key = req.url.cache_key();
+        if let Some(cached) = self.store.get(&key) {
+            if !cached.is_fresh() {
+                self.store.remove(&key);
+            } else if cached.matches(req) && request_allows(&directives, cached.age()) {
+                req.extensions.insert::<CacheLookupKey>(Lookup::Hit);
+                return Ok(BeforeAction::Respond(cached.to_response()));
+            }
+        }
+
+        req.extensions.insert::<CacheLookupKey>(Lookup::Miss(key));
+        Ok(BeforeAction::Continue)
+    }
+}
+
+impl AfterMiddleware for ResponseCache {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        let key = match req.extensions.remove::<CacheLookupKey>() {
+            Some(Lookup::Miss(key)) => key,
+            Some(Lookup::Hit) | None => return Ok(res)
+        };
+
+        let max_age = match freshness(&res) {
+            Some(max_age) => max_age,
+            None => return Ok(res)
+        };
+
+        let entry = CachedResponse {
+            status: res.status.unwrap(),
+            headers: res.headers.clone(),
+            body: Vec::new(),
+            vary: vary_names(&res).into_iter().map(|name| {
+                let values = req.headers.get_raw(&name).map(|values| values.to_vec());
+                (name, values)
+            }).collect(),
+            stored_at: SystemTime::now(),
+            max_age: max_age
+        };
+
+        // The body is stored once it has been written in full.
+        res.body = match res.body.take() {
+            Some(body) => Some(Box::new(CachingBody {
+                body: body,
+                entry: Some(entry),
+                key: key,
+                store: self.store.clone(),
+                max_size: self.max_body_size
+            })),
+            None => {
+                self.store.put(&key, entry);
+                None
+            }
+        };
+
+        Ok(res)
+    }
+}
+
+// Stores a copy of the body it wraps once it has been written.
+struct CachingBody {
+    body: Box<WriteBody>,
+    entry: Option<CachedResponse>,
+    key: String,
+    store: Arc<CacheStore>,
+    max_size: usize
+}
+
+impl WriteBody for CachingBody {
+    fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()> {
+        let mut copier = Copier { inner: res, copy: Some(Vec::new()), max_size: self.max_size };
+        try!(self.body.write_body(&mut ResponseBody::new(&mut copier)));
+
+        if let (Some(mut entry), Some(copy)) = (self.entry.take(), copier.copy) {
+            entry.body = copy;
+            self.store.put(&self.key, entry);
+        }
+        Ok(())
+    }
+}
+
+// Writes through to `inner`, keeping a copy of what is written until it grows
+// larger than `max_size`.
+struct Copier<'a, 'b: 'a> {
+    inner: &'a mut ResponseBody<'b>,
+    copy: Option<Vec<u8>>,
+    max_size: usize
+}
+
+impl<'a, 'b> Write for Copier<'a, 'b> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = try!(self.inner.write(buf));
+        let fits = self.copy.as_ref().map_or(false, |copy| copy.len() + written <= self.max_size);
+        if fits {
+            self.copy.as_mut().unwrap().extend_from_slice(&buf[..written]);
+        } else {
+            self.copy = None;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime};
+
+    use prelude::*;
+    use headers::{CacheControl, CacheDirective};
+    use response::ResponseBody;
+    use {method, status, test, Handler, Headers, Url};
+
+    use super::{ResponseCache, MemoryStore, CacheStore, CachedResponse};
+
+    fn chain(cache: ResponseCache, calls: Arc<AtomicUsize>) -> Chain {
+        let mut chain = Chain::new(move |req: &mut Request| {
+            let count = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let mut res = Response::with((status::Ok, format!("response {}", count)));
+            res.headers.set(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(60)]));
+            match &*req.url.path[0] {
+                "cookie" => res.headers.set_raw("Set-Cookie", vec![b"a=b".to_vec()]),
+                "no-store" => res.headers.set(CacheControl(vec![CacheDirective::NoStore])),
+                "vary" => res.headers.set_raw("Vary", vec![b"Accept-Language".to_vec()]),
+                _ => ()
+            }
+            Ok(res)
+        });
+        chain.link((cache.clone(), cache));
+        chain
+    }
+
+    // Handle a `GET` of `path`, writing the body out as the server would.
+    fn get(chain: &Chain, path: &str, headers: &[(&str, &str)]) -> (Response, String) {
+        let url = Url::parse(&format!("http://localhost:3000{}", path)).unwrap();
+        test::request_with_headers(method::Get, url, headers, |req| {
+            let mut res = chain.handle(req).unwrap();
+
+            let mut out = vec![];
+            res.body.take().unwrap().write_body(&mut ResponseBody::new(&mut out)).unwrap();
+            (res, String::from_utf8(out).unwrap())
+        })
+    }
+
+    #[test]
+    fn test_response_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let chain = chain(ResponseCache::new(MemoryStore::new(10)), calls.clone());
+
+        assert_eq!(get(&chain, "/a?x=1&y=2", &[]).1, "response 1");
+        let (res, body) = get(&chain, "/a?y=2&x=1", &[]);
+        assert_eq!(body, "response 1");
+        assert_eq!(res.headers.get_raw("Age").unwrap(), &[b"0".to_vec()][..]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Requests may refuse stored responses, and skip the cache.
+        assert_eq!(get(&chain, "/a?x=1&y=2", &[("Cache-Control", "no-cache")]).1, "response 2");
+        assert_eq!(get(&chain, "/a?x=1&y=2", &[]).1, "response 2");
+        assert_eq!(get(&chain, "/a?x=1&y=2", &[("Authorization", "Bearer x")]).1, "response 3");
+
+        assert_eq!(get(&chain, "/vary", &[("Accept-Language", "en")]).1, "response 4");
+        assert_eq!(get(&chain, "/vary", &[("Accept-Language", "en")]).1, "response 4");
+        assert_eq!(get(&chain, "/vary", &[("Accept-Language", "fr")]).1, "response 5");
+    }
+
+    #[test]
+    fn test_uncacheable_responses() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let chain = chain(ResponseCache::new(MemoryStore::new(10)).max_body_size(8), calls.clone());
+
+        get(&chain, "/cookie", &[]);
+        get(&chain, "/cookie", &[]);
+        get(&chain, "/no-store", &[]);
+        get(&chain, "/no-store", &[]);
+        // "response 5" is longer than the maximum body size.
+        get(&chain, "/large", &[]);
+        get(&chain, "/large", &[]);
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new(2);
+        let entry = |n: u8| CachedResponse {
+            status: status::Ok,
+            headers: Headers::new(),
+            body: vec![n],
+            vary: vec![],
+            stored_at: SystemTime::now(),
+            max_age: Duration::from_secs(60)
+        };
+
+        store.put("a", entry(1));
+        store.put("b", entry(2));
+        assert!(store.get("a").is_some());
+        store.put("c", entry(3));
+        assert!(store.get("b").is_none());
+        assert_eq!(store.get("a").unwrap().body, [1]);
+        assert_eq!(store.get("c").unwrap().body, [3]);
+
+        store.remove("a");
+        assert!(store.get("a").is_none());
+    }
+}
@@ -0,0 +1,177 @@
+//! Compression of response bodies.
+
+use std::io;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use {Request, Response, IronResult, AfterMiddleware};
+use mime::Mime;
+use response::{ResponseBody, WriteBody};
+use headers;
+use status;
+
+/// Middleware which compresses response bodies with gzip when the client
+/// accepts it.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::Compress;
+///
+/// let mut chain = Chain::new(|_: &mut Request| Ok(Response::with((status::Ok, "Hello!"))));
+/// chain.link_after(Compress::new().min_size(256));
+/// ```
+///
+/// A response is compressed if the client's `Accept-Encoding` allows gzip,
+/// its `Content-Type` is allowed, and its `Content-Length` is at least the
+/// minimum size. Responses without a `Content-Length`, such as streams, are
+/// assumed to be large enough and are compressed as they are written.
+/// Responses which already have a `Content-Encoding` are left alone.
+///
+/// Compressed responses get `Content-Encoding: gzip`, lose their
+/// `Content-Length` and are sent chunked. All responses considered for
+/// compression get `Vary: Accept-Encoding`, so caches keep the versions
+/// apart.
+#[derive(Clone, Debug)]
+pub struct Compress {
+    min_size: u64,
+    content_types: Vec<String>
+}
+
+impl Compress {
+    /// Create a `Compress` which compresses responses of at least 1024 bytes
+    /// with a textual `Content-Type`: `text/*`, `application/json`,
+    /// `application/javascript`, `application/xml` and `image/svg+xml`.
+    pub fn new() -> Compress {
+        Compress {
+            min_size: 1024,
+            content_types: vec!["text/*".to_owned(), "application/json".to_owned(),
+                                "application/javascript".to_owned(), "application/xml".to_owned(),
+                                "image/svg+xml".to_owned()]
+        }
+    }
+
+    /// Set the smallest `Content-Length` which is worth compressing.
+    pub fn min_size(mut self, bytes: u64) -> Compress {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Set the content types which are compressed, such as `text/html` or
+    /// `text/*`.
+    ///
+    /// Parameters such as `charset` are ignored when matching.
+    pub fn content_types(mut self, content_types: &[&str]) -> Compress {
+        self.content_types = content_types.iter().map(|t| t.to_ascii_lowercase()).collect();
+        self
+    }
+
+    // Is the response of a type and size worth compressing?
+    fn should_compress(&self, res: &Response) -> bool {
+        match res.status {
+            Some(status::NoContent) | Some(status::NotModified) => return false,
+            _ => ()
+        }
+
+        if res.body.is_none() || res.headers.has::<headers::ContentEncoding>() { return false }
+
+        if let Some(&headers::ContentLength(len)) = res.headers.get::<headers::ContentLength>() {
+            if len < self.min_size { return false }
+        }
+
+        // Bodies without a type are sent as text/plain.
+        let content_type = res.headers.get::<headers::ContentType>()
+            .map_or_else(|| "text/plain".to_owned(), |content_type| {
+                let headers::ContentType(Mime(ref top, ref sub, _)) = *content_type;
+                format!("{}/{}", top, sub).to_ascii_lowercase()
+            });
+
+        self.content_types.iter().any(|allowed| {
+            if allowed.ends_with("/*") {
+                content_type.starts_with(&allowed[..allowed.len() - 1])
+            } else {
+                *allowed == content_type
+            }
+        })
+    }
+}
+
+// Gzips the body it wraps as it is written.
+struct GzipBody(Box<WriteBody>);
+
+impl WriteBody for GzipBody {
+    fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()> {
+        let mut encoder = GzEncoder::new(res, Compression::default());
+        let written = self.0.write_body(&mut ResponseBody::new(&mut encoder));
+        try!(written);
+        encoder.finish().map(|_| ())
+    }
+}
+
+impl AfterMiddleware for Compress {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        if !self.should_compress(&res) { return Ok(res) }
+
+        res.vary_on(&["Accept-Encoding"]);
+        if req.accepts_encoding("gzip") {
+            res.headers.set(headers::ContentEncoding(vec![headers::Encoding::Gzip]));
+            res.headers.remove::<headers::ContentLength>();
+            res.body = res.body.take().map(|body| Box::new(GzipBody(body)) as Box<WriteBody>);
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use prelude::*;
+    use mime::Mime;
+    use response::ResponseBody;
+    use {method, status, test, Handler, Url};
+
+    use super::Compress;
+
+    fn handle(compress: Compress, accept: &str, content_type: &str) -> Response {
+        let content_type = content_type.parse::<Mime>().unwrap();
+        let mut chain = Chain::new(move |_: &mut Request| {
+            Ok(Response::with((status::Ok, content_type.clone(), "x".repeat(2000))))
+        });
+        chain.link_after(compress);
+
+        test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                   &[("Accept-Encoding", accept)], |req| chain.handle(req).unwrap())
+    }
+
+    #[test]
+    fn test_compress() {
+        let mut res = handle(Compress::new(), "deflate, gzip;q=0.5", "text/html; charset=utf-8");
+        assert_eq!(test::response_header(&res, "Content-Encoding"), Some("gzip".to_owned()));
+        assert_eq!(test::response_header(&res, "Content-Length"), None);
+        assert_eq!(test::response_header(&res, "Vary"), Some("Accept-Encoding".to_owned()));
+
+        let mut out = vec![];
+        res.body.take().unwrap().write_body(&mut ResponseBody::new(&mut out)).unwrap();
+        let mut body = String::new();
+        GzDecoder::new(&out[..]).read_to_string(&mut body).unwrap();
+        assert_eq!(body, "x".repeat(2000));
+    }
+
+    #[test]
+    fn test_skip_compression() {
+        let res = handle(Compress::new(), "gzip;q=0, *", "text/plain");
+        assert_eq!(test::response_header(&res, "Content-Encoding"), None);
+        assert_eq!(test::response_header(&res, "Vary"), Some("Accept-Encoding".to_owned()));
+
+        let res = handle(Compress::new().min_size(4096), "gzip", "text/plain");
+        assert_eq!(test::response_header(&res, "Content-Encoding"), None);
+
+        let res = handle(Compress::new(), "gzip", "image/png");
+        assert_eq!(test::response_header(&res, "Content-Encoding"), None);
+    }
+}
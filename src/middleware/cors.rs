@@ -0,0 +1,297 @@
+//! Cross-Origin Resource Sharing.
+
+use {Request, Response, IronResult, IronError, BeforeMiddleware, AfterMiddleware};
+use middleware::BeforeAction;
+use method::Method;
+use headers;
+use status;
+
+/// Middleware which allows browsers to make cross-origin requests, following
+/// the [CORS](https://www.w3.org/TR/cors/) protocol.
+///
+/// `Cors` is both a `BeforeMiddleware`, which answers preflight `OPTIONS`
+/// requests without calling the `Handler`, and an `AfterMiddleware`, which
+/// adds `Access-Control-*` headers to the responses of allowed origins. Link
+/// it as both:
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::Cors;
+///
+/// let cors = Cors::new().allow_origin("https://example.com").allow_credentials(true);
+///
+/// let mut chain = Chain::new(|_: &mut Request| Ok(Response::with(status::Ok)));
+/// chain.link((cors.clone(), cors));
+/// ```
+///
+/// The `Origin` of a request is only echoed back if it is allowed, and a
+/// request from any other origin is passed on without CORS headers, so the
+/// browser will refuse to expose the response.
+#[derive(Clone, Debug)]
+pub struct Cors {
+    origins: Origins,
+    methods: Vec<Method>,
+    headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<u32>
+}
+
+#[derive(Clone, Debug)]
+enum Origins {
+    Any,
+    List(Vec<String>)
+}
+
+impl Cors {
+    /// Create a `Cors` which allows no origins, and `GET`, `HEAD` and `POST`
+    /// requests without any extra headers.
+    pub fn new() -> Cors {
+        Cors {
+            origins: Origins::List(vec![]),
+            methods: vec![Method::Get, Method::Head, Method::Post],
+            headers: vec![],
+            credentials: false,
+            max_age: None
+        }
+    }
+
+    /// Allow requests from `origin`, such as `https://example.com`.
+    ///
+    /// Has no effect if any origin is allowed.
+    pub fn allow_origin(mut self, origin: &str) -> Cors {
+        if let Origins::List(ref mut origins) = self.origins {
+            origins.push(origin.to_owned());
+        }
+        self
+    }
+
+    /// Allow requests from any origin.
+    pub fn allow_any_origin(mut self) -> Cors {
+        self.origins = Origins::Any;
+        self
+    }
+
+    /// Set the methods which cross-origin requests may use.
+    pub fn allow_methods(mut self, methods: &[Method]) -> Cors {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    /// Set the request headers which cross-origin requests may send, beyond
+    /// those which browsers always allow.
+    ///
+    /// Header names are compared case-insensitively.
+    pub fn allow_headers(mut self, headers: &[&str]) -> Cors {
+        self.headers = headers.iter().map(|h| h.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// Set whether cross-origin requests may include credentials, such as
+    /// cookies.
+    ///
+    /// When enabled, the request's origin is echoed back even if any origin
+    /// is allowed, since browsers reject `*` for requests with credentials.
+    pub fn allow_credentials(mut self, credentials: bool) -> Cors {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Set how many seconds browsers may cache the result of a preflight
+    /// request for.
+    pub fn max_age(mut self, seconds: Option<u32>) -> Cors {
+        self.max_age = seconds;
+        self
+    }
+
+    // The value of `Access-Control-Allow-Origin` for a request, if its origin
+    // is allowed.
+    fn allowed_origin(&self, req: &Request) -> Option<headers::AccessControlAllowOrigin> {
+        let origin = match req.headers.get_raw("Origin") {
+            Some(values) if values.len() == 1 => match String::from_utf8(values[0].clone()) {
+                Ok(origin) => origin,
+                Err(_) => return None
+            },
+            _ => return None
+        };
+
+        match self.origins {
+            Origins::Any if !self.credentials => Some(headers::AccessControlAllowOrigin::Any),
+            Origins::Any => Some(headers::AccessControlAllowOrigin::Value(origin)),
+            Origins::List(ref origins) if origins.contains(&origin) => {
+                Some(headers::AccessControlAllowOrigin::Value(origin))
+            },
+            Origins::List(_) => None
+        }
+    }
+
+    // Is this a preflight request whose actual request would be allowed?
+    fn allows_preflight(&self, req: &Request) -> bool {
+        let allowed_method = match req.headers.get::<headers::AccessControlRequestMethod>() {
+            Some(&headers::AccessControlRequestMethod(ref method)) => self.methods.contains(method),
+            None => false
+        };
+
+        let requested_headers = req.headers.get_raw("Access-Control-Request-Headers")
+            .unwrap_or(&[]);
+        let allowed_headers = requested_headers.iter()
+            .flat_map(|line| line.split(|&b| b == b','))
+            .map(|name| String::from_utf8_lossy(name).trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .all(|name| self.headers.contains(&name));
+
+        allowed_method && allowed_headers
+    }
+
+    fn add_headers(&self, origin: headers::AccessControlAllowOrigin, res: &mut Response) {
+        res.headers.set(origin);
+        if self.credentials {
+            res.headers.set(headers::AccessControlAllowCredentials);
+        }
+    }
+
+    // Mark a response as depending on the request's origin, if the headers
+    // added do, so caches key on it too. This applies whether or not the
+    // origin was allowed, or a copy without CORS headers could be served to
+    // an allowed origin.
+    fn vary(&self, res: &mut Response) {
+        let echoes_origin = match self.origins {
+            Origins::Any => self.credentials,
+            Origins::List(_) => true
+        };
+        if echoes_origin { res.vary_on(&["Origin"]) }
+    }
+}
+
+fn is_preflight(req: &Request) -> bool {
+    req.method == Method::Options && req.headers.has::<headers::AccessControlRequestMethod>()
+}
+
+impl BeforeMiddleware for Cors {
+    fn before_action(&self, req: &mut Request) -> IronResult<BeforeAction> {
+        if !is_preflight(req) { return Ok(BeforeAction::Continue) }
+
+        let mut res = Response::with(status::NoContent);
+        self.vary(&mut res);
+        if let Some(origin) = self.allowed_origin(req) {
+            if self.allows_preflight(req) {
+                self.add_headers(origin, &mut res);
+                res.headers.set(headers::AccessControlAllowMethods(self.methods.clone()));
+                if !self.headers.is_empty() {
+                    res.headers.set_raw("Access-Control-Allow-Headers",
+                                        vec![self.headers.join(", ").into_bytes()]);
+                }
+                if let Some(max_age) = self.max_age {
+                    res.headers.set(headers::AccessControlMaxAge(max_age));
+                }
+            }
+        }
+
+        Ok(BeforeAction::Respond(res))
+    }
+}
+
+impl AfterMiddleware for Cors {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        // Preflight responses were completed in `before_action`.
+        if !is_preflight(req) {
+            self.vary(&mut res);
+            if let Some(origin) = self.allowed_origin(req) {
+                self.add_headers(origin, &mut res);
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn catch(&self, req: &mut Request, mut err: IronError) -> IronResult<Response> {
+        // Let the browser read error responses too.
+        self.vary(&mut err.response);
+        if let Some(origin) = self.allowed_origin(req) {
+            self.add_headers(origin, &mut err.response);
+        }
+
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prelude::*;
+    use {method, status, test, Handler, Url};
+
+    use super::Cors;
+
+    fn handle(cors: Cors, method: method::Method, headers: &[(&str, &str)]) -> Response {
+        let mut chain = Chain::new(|_: &mut Request| Ok(Response::with((status::Ok, "body"))));
+        chain.link((cors.clone(), cors));
+
+        test::request_with_headers(method, Url::parse("http://localhost:3000/").unwrap(), headers,
+                                   |req| chain.handle(req).unwrap())
+    }
+
+    #[test]
+    fn test_actual_request() {
+        let cors = Cors::new().allow_origin("https://example.com");
+
+        let res = handle(cors.clone(), method::Get, &[("Origin", "https://example.com")]);
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Origin"),
+                   Some("https://example.com".to_owned()));
+        assert_eq!(test::response_header(&res, "Vary"), Some("Origin".to_owned()));
+
+        let res = handle(cors.clone(), method::Get, &[("Origin", "https://evil.com")]);
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Origin"), None);
+        assert_eq!(test::response_header(&res, "Vary"), Some("Origin".to_owned()));
+        assert_eq!(res.status, Some(status::Ok));
+
+        // Responses to requests without an origin can't be cached for those with one.
+        let res = handle(cors, method::Get, &[]);
+        assert_eq!(test::response_header(&res, "Vary"), Some("Origin".to_owned()));
+    }
+
+    #[test]
+    fn test_any_origin() {
+        let cors = Cors::new().allow_any_origin();
+        let res = handle(cors, method::Get, &[("Origin", "https://a.com")]);
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Origin"),
+                   Some("*".to_owned()));
+
+        let cors = Cors::new().allow_any_origin().allow_credentials(true);
+        let res = handle(cors, method::Get, &[("Origin", "https://a.com")]);
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Origin"),
+                   Some("https://a.com".to_owned()));
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Credentials"),
+                   Some("true".to_owned()));
+    }
+
+    #[test]
+    fn test_preflight() {
+        let cors = Cors::new()
+            .allow_origin("https://example.com")
+            .allow_methods(&[method::Get, method::Put])
+            .allow_headers(&["Content-Type"])
+            .max_age(Some(600));
+
+        let res = handle(cors.clone(), method::Options, &[
+            ("Origin", "https://example.com"),
+            ("Access-Control-Request-Method", "PUT"),
+            ("Access-Control-Request-Headers", "content-type")
+        ]);
+        assert_eq!(res.status, Some(status::NoContent));
+        assert!(res.body.is_none());
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Origin"),
+                   Some("https://example.com".to_owned()));
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Methods"),
+                   Some("GET, PUT".to_owned()));
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Headers"),
+                   Some("content-type".to_owned()));
+        assert_eq!(test::response_header(&res, "Access-Control-Max-Age"), Some("600".to_owned()));
+
+        let res = handle(cors, method::Options, &[
+            ("Origin", "https://example.com"),
+            ("Access-Control-Request-Method", "DELETE")
+        ]);
+        assert_eq!(res.status, Some(status::NoContent));
+        assert_eq!(test::response_header(&res, "Access-Control-Allow-Origin"), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Bounding the time spent handling a request.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use typemap::Key;
+
+use {Request, Response, IronResult, IronError, BeforeMiddleware, AfterMiddleware};
+use status::{self, Status};
+
+/// Middleware which gives each request a deadline, and fails requests which
+/// take longer.
+///
+/// As a `BeforeMiddleware`, `Deadline` sets the deadline, which handlers can
+/// read with `Request::deadline`. As an `AfterMiddleware`, it replaces the
+/// response to any request which finished after its deadline with a
+/// `503 Service Unavailable` error wrapping `DeadlineExceeded`. Link it as
+/// both:
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use std::time::{Duration, Instant};
+/// use iron::middleware::Deadline;
+///
+/// let deadline = Deadline::new(Duration::from_secs(10));
+///
+/// let mut chain = Chain::new(|req: &mut Request| {
+///     for _ in 0..100 {
+///         if req.deadline().map_or(false, |deadline| Instant::now() >= deadline) {
+///             break;
+///         }
+///         // Do a step of some slow work.
+///     }
+///     Ok(Response::with(status::Ok))
+/// });
+/// chain.link((deadline, deadline));
+/// ```
+///
+/// Threads can't be interrupted, so the deadline is only advisory: a handler
+/// which doesn't check it runs to completion however long it takes, and only
+/// then is its response replaced. Handlers doing slow work, such as calling
+/// other services, should check the deadline between steps and use the time
+/// remaining to bound their own timeouts.
+///
+/// If a request already has an earlier deadline, such as from an enclosing
+/// `Chain`, it is kept.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    timeout: Duration,
+    status: Status
+}
+
+// The time by which a request should be finished.
+struct DeadlineKey;
+
+impl Key for DeadlineKey { type Value = Instant; }
+
+impl Deadline {
+    /// Create a `Deadline` which allows each request `timeout` to finish.
+    pub fn new(timeout: Duration) -> Deadline {
+        Deadline { timeout: timeout, status: status::ServiceUnavailable }
+    }
+
+    /// Set the status of the error sent when a request misses its deadline,
+    /// such as `504 Gateway Timeout` for a proxy. The default is
+    /// `503 Service Unavailable`.
+    pub fn status(mut self, status: Status) -> Deadline {
+        self.status = status;
+        self
+    }
+}
+
+impl<'a> Request<'a> {
+    /// The time by which the handler should respond, set by the `Deadline`
+    /// middleware, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.extensions.get::<DeadlineKey>().cloned()
+    }
+}
+
+impl BeforeMiddleware for Deadline {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let deadline = Instant::now() + self.timeout;
+        let deadline = req.deadline().map_or(deadline, |earlier| earlier.min(deadline));
+        req.extensions.insert::<DeadlineKey>(deadline);
+        Ok(())
+    }
+}
+
+impl AfterMiddleware for Deadline {
+    fn after(&self, req: &mut Request, res: Response) -> IronResult<Response> {
+        match req.deadline() {
+            Some(deadline) if Instant::now() > deadline => {
+                Err(IronError::new(DeadlineExceeded, self.status))
+            },
+            _ => Ok(res)
+        }
+    }
+}
+
+/// The error given when a request isn't finished by the deadline set by the
+/// `Deadline` middleware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl StdError for DeadlineExceeded {
+    fn description(&self) -> &str {
+        "Request was not finished by its deadline"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use prelude::*;
+    use {method, status, test, Handler, Url};
+
+    use super::{Deadline, DeadlineExceeded};
+
+    fn handle(deadline: Deadline, work: Duration) -> IronResult<Response> {
+        let mut chain = Chain::new(move |req: &mut Request| {
+            assert!(req.deadline().is_some());
+            thread::sleep(work);
+            Ok(Response::with(status::Ok))
+        });
+        chain.link((deadline, deadline));
+
+        test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(), &[],
+                                   |req| chain.handle(req))
+    }
+
+    #[test]
+    fn test_deadline() {
+        let res = handle(Deadline::new(Duration::from_secs(10)), Duration::from_millis(0));
+        assert_eq!(res.unwrap().status, Some(status::Ok));
+
+        let deadline = Deadline::new(Duration::from_millis(1)).status(status::GatewayTimeout);
+        let err = handle(deadline, Duration::from_millis(20)).unwrap_err();
+        assert_eq!(err.response.status, Some(status::GatewayTimeout));
+        assert!(err.error.is::<DeadlineExceeded>());
+    }
+}
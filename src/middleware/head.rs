@@ -0,0 +1,57 @@
+//! Answering `HEAD` requests with `GET` handlers.
+
+use {Request, IronResult, BeforeMiddleware};
+use method;
+
+/// Middleware which handles `HEAD` requests as `GET` requests, so handlers
+/// and routers written for `GET` answer `HEAD` requests too.
+///
+/// Iron never sends a body in response to a `HEAD` request, so the client
+/// gets the status and headers, including `Content-Length`, that a `GET`
+/// would have, without the body.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::HeadAsGet;
+///
+/// let mut chain = Chain::new(|_: &mut Request| Ok(Response::with((status::Ok, "Hello!"))));
+/// chain.link_before(HeadAsGet);
+/// ```
+///
+/// Link it before any middleware which checks the method, such as a router.
+#[derive(Clone, Copy, Debug)]
+pub struct HeadAsGet;
+
+impl BeforeMiddleware for HeadAsGet {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        if req.method == method::Head {
+            req.method = method::Get;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prelude::*;
+    use {method, status, test, Handler, Url};
+
+    use super::HeadAsGet;
+
+    #[test]
+    fn test_head_as_get() {
+        let mut chain = Chain::new(|req: &mut Request| {
+            match req.method {
+                method::Get => Ok(Response::with((status::Ok, "Hello!"))),
+                _ => Ok(Response::with(status::MethodNotAllowed))
+            }
+        });
+        chain.link_before(HeadAsGet);
+
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Head, url, &[], |req| {
+            assert_eq!(chain.handle(req).unwrap().status, Some(status::Ok));
+        });
+    }
+}
@@ -0,0 +1,187 @@
+//! Restricting requests by client address.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+
+use {Request, IronResult, IronError, BeforeMiddleware};
+use request::IpNetwork;
+use status;
+
+/// Middleware which only lets requests through from allowed client
+/// addresses, rejecting others with a `403 Forbidden` error wrapping
+/// `AddressDenied`.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::IpFilter;
+///
+/// let filter = IpFilter::new()
+///     .allow("10.0.0.0/8".parse().unwrap())
+///     .deny("10.13.0.0/16".parse().unwrap());
+///
+/// let mut chain = Chain::new(|_: &mut Request| Ok(Response::with((status::Ok, "Admin"))));
+/// chain.link_before(filter);
+/// ```
+///
+/// The deny list takes precedence: an address in any denied network is
+/// rejected, even if it is also in an allowed one. Otherwise, if any networks
+/// are allowed, only addresses in one of them are let through; with no allowed
+/// networks, every address which isn't denied is.
+///
+/// The client address is the request's `remote_addr`, unless it is one of the
+/// proxies given to `trust_proxy`, in which case it is taken from
+/// `X-Forwarded-For` as described by `Request::real_remote_addr`. IPv6
+/// addresses which are mapped IPv4 addresses, such as `::ffff:10.0.0.1`, are
+/// matched against both IPv4 and IPv6 networks.
+#[derive(Clone, Debug)]
+pub struct IpFilter {
+    allow: Vec<IpNetwork>,
+    deny: Vec<IpNetwork>,
+    trusted: Vec<IpNetwork>
+}
+
+impl IpFilter {
+    /// Create an `IpFilter` which lets every address through, and trusts no
+    /// proxies.
+    pub fn new() -> IpFilter {
+        IpFilter { allow: vec![], deny: vec![], trusted: vec![] }
+    }
+
+    /// Allow addresses in `network`. Once any network is allowed, addresses
+    /// outside the allowed networks are rejected.
+    pub fn allow(mut self, network: IpNetwork) -> IpFilter {
+        self.allow.push(network);
+        self
+    }
+
+    /// Reject addresses in `network`, even if they are allowed.
+    pub fn deny(mut self, network: IpNetwork) -> IpFilter {
+        self.deny.push(network);
+        self
+    }
+
+    /// Trust the proxies in `network` to give the client address in
+    /// `X-Forwarded-For`.
+    pub fn trust_proxy(mut self, network: IpNetwork) -> IpFilter {
+        self.trusted.push(network);
+        self
+    }
+
+    /// Whether requests from `ip` are let through.
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        let matches = |networks: &[IpNetwork]| {
+            networks.iter().any(|network| {
+                network.contains(ip) || unmapped(ip).map_or(false, |ip| network.contains(ip))
+            })
+        };
+
+        !matches(&self.deny) && (self.allow.is_empty() || matches(&self.allow))
+    }
+}
+
+impl BeforeMiddleware for IpFilter {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let ip = req.real_remote_addr(&self.trusted).ip();
+        if self.is_allowed(ip) {
+            Ok(())
+        } else {
+            Err(IronError::new(AddressDenied { ip: ip }, status::Forbidden))
+        }
+    }
+}
+
+// The IPv4 address mapped into `ip`, if it is an IPv4-mapped IPv6 address.
+fn unmapped(ip: IpAddr) -> Option<IpAddr> {
+    match ip {
+        IpAddr::V6(ip) => match ip.segments() {
+            [0, 0, 0, 0, 0, 0xFFFF, high, low] => {
+                Some(IpAddr::V4(Ipv4Addr::new((high >> 8) as u8, high as u8,
+                                              (low >> 8) as u8, low as u8)))
+            },
+            _ => None
+        },
+        IpAddr::V4(_) => None
+    }
+}
+
+/// The error produced by `IpFilter` when a request comes from an address
+/// which isn't allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressDenied {
+    /// The client address of the request.
+    pub ip: IpAddr
+}
+
+impl fmt::Display for AddressDenied {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Requests from {} are not allowed", self.ip)
+    }
+}
+
+impl StdError for AddressDenied {
+    fn description(&self) -> &str {
+        "Requests from this address are not allowed"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use prelude::*;
+    use {method, status, test, Handler, Url};
+
+    use super::{IpFilter, AddressDenied};
+
+    fn filter() -> IpFilter {
+        IpFilter::new()
+            .allow("10.0.0.0/8".parse().unwrap())
+            .allow("fd00::/8".parse().unwrap())
+            .deny("10.13.0.0/16".parse().unwrap())
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let filter = filter();
+        assert!(filter.is_allowed("10.1.2.3".parse().unwrap()));
+        assert!(filter.is_allowed("fd12::1".parse().unwrap()));
+        assert!(filter.is_allowed("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!filter.is_allowed("10.13.2.3".parse().unwrap()));
+        assert!(!filter.is_allowed("::ffff:10.13.2.3".parse().unwrap()));
+        assert!(!filter.is_allowed("192.168.1.1".parse().unwrap()));
+
+        let filter = IpFilter::new().deny("192.168.0.0/16".parse().unwrap());
+        assert!(filter.is_allowed("10.1.2.3".parse().unwrap()));
+        assert!(!filter.is_allowed("192.168.1.1".parse().unwrap()));
+    }
+
+    fn handle(filter: IpFilter, remote_addr: &str, forwarded_for: Option<&str>)
+              -> IronResult<Response> {
+        let mut chain = Chain::new(|_: &mut Request| Ok(Response::with(status::Ok)));
+        chain.link_before(filter);
+
+        let headers: Vec<_> = forwarded_for.into_iter()
+            .map(|forwarded_for| ("X-Forwarded-For", forwarded_for)).collect();
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, &headers, |req| {
+            req.remote_addr = remote_addr.parse::<SocketAddr>().unwrap();
+            chain.handle(req)
+        })
+    }
+
+    #[test]
+    fn test_ip_filter_middleware() {
+        assert_eq!(handle(filter(), "10.1.2.3:1234", None).unwrap().status, Some(status::Ok));
+
+        let err = handle(filter(), "192.168.1.1:1234", Some("10.1.2.3")).unwrap_err();
+        assert_eq!(err.response.status, Some(status::Forbidden));
+        assert_eq!(err.error.downcast::<AddressDenied>(),
+                   Some(&AddressDenied { ip: "192.168.1.1".parse().unwrap() }));
+
+        let proxied = filter().trust_proxy("192.168.0.0/16".parse().unwrap());
+        assert!(handle(proxied.clone(), "192.168.1.1:1234", Some("10.1.2.3")).is_ok());
+        assert!(handle(proxied, "192.168.1.1:1234", Some("10.13.2.3")).is_err());
+    }
+}
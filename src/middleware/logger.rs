@@ -0,0 +1,317 @@
+//! Access logging.
+
+use std::fmt::Write as FmtWrite;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use time;
+use typemap::Key;
+
+use {Request, Response, IronResult, IronError, BeforeMiddleware, AfterMiddleware};
+use method::Method;
+use metrics::CountedBody;
+use request::HttpVersion;
+use response::{ResponseBody, WriteBody};
+use status::Status;
+
+/// Middleware which logs one line for each request, with its method, path,
+/// status, body size and duration.
+///
+/// Lines are emitted through the `log` crate at the `info` level once the
+/// response has been written, so the byte count and duration include the
+/// body even when it is streamed. Responses without a body are logged as
+/// they leave the `Logger`. `Logger` is both a `BeforeMiddleware`,
+/// which notes when the request started, and an `AfterMiddleware`, which
+/// logs its response. Link it as both, before any other middleware so the
+/// duration covers the whole chain:
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::{LogFormat, Logger};
+///
+/// let logger = Logger::new(LogFormat::Common);
+///
+/// let mut chain = Chain::new(|_: &mut Request| Ok(Response::with(status::Ok)));
+/// chain.link((logger.clone(), logger));
+/// ```
+///
+/// Responses to errors are logged too, although an `AfterMiddleware` linked
+/// after the `Logger` could still change them.
+#[derive(Clone)]
+pub struct Logger {
+    formatter: Arc<Fn(&LogRecord) -> String + Send + Sync>
+}
+
+/// The built-in formats for log lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The Common Log Format used by many web servers, such as
+    /// `127.0.0.1 - - [10/Oct/2016:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326`.
+    Common,
+    /// A JSON object, such as `{"remote_addr":"127.0.0.1:5678","method":"GET",
+    /// "path":"/index.html","status":200,"bytes":2326,"duration_ms":1.25}`,
+    /// plus a `request_id` if the request has one.
+    Json
+}
+
+/// What is known about a request once its response has been written.
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    /// The address of the client.
+    pub remote_addr: SocketAddr,
+    /// The request method.
+    pub method: Method,
+    /// The path of the request, including any query.
+    pub path: String,
+    /// The HTTP version of the request.
+    pub version: HttpVersion,
+    /// The response status, if one was set.
+    pub status: Option<Status>,
+    /// The number of bytes in the response body.
+    pub bytes: u64,
+    /// When the request reached the `Logger`.
+    pub started: SystemTime,
+    /// How long it took to handle the request and write the response.
+    pub duration: Duration,
+    /// The ID given to the request by the `RequestId` middleware, if any.
+    pub request_id: Option<String>
+}
+
+impl Logger {
+    /// Create a `Logger` which writes lines in `format`.
+    pub fn new(format: LogFormat) -> Logger {
+        match format {
+            LogFormat::Common => Logger::with_formatter(common_format),
+            LogFormat::Json => Logger::with_formatter(json_format)
+        }
+    }
+
+    /// Create a `Logger` which writes the lines made by `formatter`.
+    pub fn with_formatter<F>(formatter: F) -> Logger
+    where F: Fn(&LogRecord) -> String + Send + Sync + 'static {
+        Logger { formatter: Arc::new(formatter) }
+    }
+
+    // Arrange for `res` to be logged once its body has been written, or log
+    // it now if it has none.
+    fn log_response(&self, req: &mut Request, res: &mut Response) {
+        let (instant, started) = match req.extensions.get::<StartTime>() {
+            Some(&start) => start,
+            None => (Instant::now(), SystemTime::now())
+        };
+
+        let mut record = LogRecord {
+            remote_addr: req.remote_addr,
+            method: req.method.clone(),
+            path: req.raw_uri.to_string(),
+            version: req.version,
+            status: res.status,
+            bytes: 0,
+            started: started,
+            duration: Duration::from_secs(0),
+            request_id: req.request_id().map(|id| id.to_owned())
+        };
+
+        // Bodiless responses are left as they are, since one with a body is
+        // written with a `Content-Type` and chunked encoding.
+        match res.body.take() {
+            Some(body) => {
+                res.body = Some(Box::new(LoggedBody {
+                    body: CountedBody { body: body, bytes: Arc::new(AtomicUsize::new(0)) },
+                    record: record,
+                    instant: instant,
+                    formatter: self.formatter.clone()
+                }));
+            },
+            None => {
+                record.duration = instant.elapsed();
+                info!("{}", (self.formatter)(&record));
+            }
+        }
+    }
+}
+
+// When the request reached the `Logger`.
+struct StartTime;
+
+impl Key for StartTime { type Value = (Instant, SystemTime); }
+
+// Counts the bytes written by the body it wraps, and logs the request when
+// it is dropped, whether or not it was ever written.
+struct LoggedBody {
+    body: CountedBody,
+    record: LogRecord,
+    instant: Instant,
+    formatter: Arc<Fn(&LogRecord) -> String + Send + Sync>
+}
+
+impl WriteBody for LoggedBody {
+    fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()> {
+        let written = self.body.write_body(res);
+        self.record.bytes = self.body.bytes.load(Ordering::Relaxed) as u64;
+        written
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        self.record.duration = self.instant.elapsed();
+        info!("{}", (self.formatter)(&self.record));
+    }
+}
+
+fn common_format(record: &LogRecord) -> String {
+    let since_epoch = record.started.duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+    let started = time::at_utc(time::Timespec::new(since_epoch.as_secs() as i64, 0));
+
+    format!("{} - - [{}] \"{} {} {}\" {} {}",
+            record.remote_addr.ip(),
+            started.strftime("%d/%b/%Y:%H:%M:%S +0000").unwrap(),
+            record.method,
+            record.path,
+            record.version,
+            record.status.map_or_else(|| "-".to_owned(), |status| status.to_u16().to_string()),
+            record.bytes)
+}
+
+fn json_format(record: &LogRecord) -> String {
+    let mut line = String::from("{\"remote_addr\":");
+    push_json_string(&mut line, &record.remote_addr.to_string());
+    line.push_str(",\"method\":");
+    push_json_string(&mut line, &record.method.to_string());
+    line.push_str(",\"path\":");
+    push_json_string(&mut line, &record.path);
+
+    let status = record.status.map_or_else(|| "null".to_owned(),
+                                           |status| status.to_u16().to_string());
+    let millis = record.duration.as_secs() as f64 * 1000.0 +
+                 record.duration.subsec_nanos() as f64 / 1_000_000.0;
+    write!(line, ",\"status\":{},\"bytes\":{},\"duration_ms\":{:.3}",
+           status, record.bytes, millis).unwrap();
+
+    if let Some(ref id) = record.request_id {
+        line.push_str(",\"request_id\":");
+        push_json_string(&mut line, id);
+    }
+    line.push('}');
+    line
+}
+
+fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
+
+impl BeforeMiddleware for Logger {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        req.extensions.insert::<StartTime>((Instant::now(), SystemTime::now()));
+        Ok(())
+    }
+
+    fn catch(&self, req: &mut Request, err: IronError) -> IronResult<()> {
+        req.extensions.insert::<StartTime>((Instant::now(), SystemTime::now()));
+        Err(err)
+    }
+}
+
+impl AfterMiddleware for Logger {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        self.log_response(req, &mut res);
+        Ok(res)
+    }
+
+    fn catch(&self, req: &mut Request, mut err: IronError) -> IronResult<Response> {
+        self.log_response(req, &mut err.response);
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    use metrics::CountedBody;
+    use middleware::AfterMiddleware;
+    use request::HttpVersion;
+    use response::{BodyStream, ResponseBody, WriteBody};
+    use {method, status, test, Response, Url};
+
+    use super::{LogFormat, LogRecord, LoggedBody, Logger, common_format, json_format};
+
+    fn record() -> LogRecord {
+        LogRecord {
+            remote_addr: "127.0.0.1:5678".parse::<SocketAddr>().unwrap(),
+            method: method::Get,
+            path: "/a \"b\"?c=1".to_owned(),
+            version: HttpVersion::Http11,
+            status: Some(status::Ok),
+            bytes: 12,
+            started: UNIX_EPOCH + Duration::from_secs(1476107736),
+            duration: Duration::from_millis(5),
+            request_id: Some("abc".to_owned())
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(common_format(&record()),
+                   "127.0.0.1 - - [10/Oct/2016:13:55:36 +0000] \"GET /a \"b\"?c=1 HTTP/1.1\" \
+                    200 12");
+        assert_eq!(json_format(&record()),
+                   "{\"remote_addr\":\"127.0.0.1:5678\",\"method\":\"GET\",\
+                    \"path\":\"/a \\\"b\\\"?c=1\",\
+                    \"status\":200,\"bytes\":12,\"duration_ms\":5.000,\"request_id\":\"abc\"}");
+    }
+
+    #[test]
+    fn test_counts_streamed_bytes() {
+        let chunks = vec![Ok(b"hello ".to_vec()), Ok(b"world".to_vec())];
+        let mut body = LoggedBody {
+            body: CountedBody {
+                body: Box::new(BodyStream(chunks.into_iter())),
+                bytes: Arc::new(AtomicUsize::new(0))
+            },
+            record: record(),
+            instant: Instant::now(),
+            formatter: Arc::new(common_format)
+        };
+        body.record.bytes = 0;
+
+        let mut out = vec![];
+        body.write_body(&mut ResponseBody::new(&mut out)).unwrap();
+        assert_eq!(out, b"hello world");
+        assert_eq!(body.record.bytes, 11);
+    }
+
+    #[test]
+    fn test_bodiless_response_keeps_no_body() {
+        let logger = Logger::new(LogFormat::Common);
+
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, &[], |req| {
+            let mut res = logger.after(req, Response::with(status::Found)).unwrap();
+            assert!(res.body.is_none());
+
+            let mut out = Vec::new();
+            res.write_to(&mut out).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            assert!(out.contains("Content-Length: 0\r\n"));
+            assert!(!out.contains("Content-Type"));
+        });
+    }
+}
@@ -20,6 +20,14 @@
 //! AfterMiddleware are called with both the Request and Response. After all AfterMiddleware
 //! have been fired, the response is written back to the client.
 //!
+//! A BeforeMiddleware can also answer the request itself, such as with a `304 Not
+//! Modified`, by returning `BeforeAction::Respond`. The remaining BeforeMiddleware
+//! and the Handler are then skipped, and the flow continues at the first AfterMiddleware:
+//!
+//! ```plain
+//! [b] -> [b] -> [a] -> [a] -> [a] -> [a]
+//! ```
+//!
 //! Iron's error handling system is pragmatic and focuses on tracking two pieces
 //! of information for error receivers (other middleware):
 //!
@@ -51,13 +59,61 @@
 //! also do the necessary action.
 //!
 
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
-use {Request, Response, IronResult, IronError};
+
+use {Request, Response, IronResult, IronError, Headers};
+use error::{NotFound, PanicError};
+use method::Method;
+use request::RequestUri;
+use status::{self, Status};
+
+pub use self::basic_auth::{BasicAuth, Unauthorized};
+pub use self::blocking::{FnMutHandler, BlockingPool};
+pub use self::cache::{ResponseCache, CacheStore, CachedResponse, MemoryStore};
+pub use self::compress::Compress;
+pub use self::cors::Cors;
+pub use self::deadline::{Deadline, DeadlineExceeded};
+pub use self::head::HeadAsGet;
+pub use self::ip_filter::{IpFilter, AddressDenied};
+pub use self::logger::{Logger, LogFormat, LogRecord};
+pub use self::rate_limit::{RateLimit, RateLimitStrategy, RateLimitStore, RateLimitDecision,
+                           MemoryRateLimitStore, RateLimited};
+pub use self::request_id::RequestId;
+pub use self::security_headers::SecurityHeaders;
+
+mod basic_auth;
+mod blocking;
+mod cache;
+mod compress;
+mod cors;
+mod deadline;
+mod head;
+mod ip_filter;
+mod logger;
+mod rate_limit;
+mod request_id;
+mod security_headers;
 
 /// `Handler`s are responsible for handling requests by creating Responses from Requests.
 pub trait Handler: Send + Sync + 'static {
     /// Produce a `Response` from a Request, with the possibility of error.
     fn handle(&self, &mut Request) -> IronResult<Response>;
+
+    /// Decide whether to accept the body of a request sent with `Expect:
+    /// 100-continue`, before the client sends it.
+    ///
+    /// This is called with the request line and headers before the request
+    /// is handled. Returning `status::Continue` tells the client to send the
+    /// body, and the request is then handled as usual. Any other status, such
+    /// as `status::ExpectationFailed`, is sent to the client on its own and
+    /// the connection is closed, without calling `handle`.
+    ///
+    /// The default implementation accepts every body.
+    fn check_continue(&self, _: &Method, _: &RequestUri, _: &Headers) -> Status {
+        status::Continue
+    }
 }
 
 /// `BeforeMiddleware` are fired before a `Handler` is called inside of a Chain.
@@ -68,18 +124,72 @@ pub trait Handler: Send + Sync + 'static {
 ///
 /// `BeforeMiddleware` only have access to the Request, if you need to modify or read
 /// a Response, you will need `AfterMiddleware`. Middleware which wishes to send an
-/// early response that is not an error cannot be `BeforeMiddleware`, but should
-/// instead be `AroundMiddleware`.
+/// early response that is not an error, such as a `304 Not Modified`, can do so by
+/// implementing `before_action`.
 pub trait BeforeMiddleware: Send + Sync + 'static {
     /// Do whatever work this middleware should do with a `Request` object.
     fn before(&self, _: &mut Request) -> IronResult<()> { Ok(()) }
 
+    /// Like `before`, but may also end the normal flow early with a response.
+    ///
+    /// Returning `BeforeAction::Respond` skips the remaining `BeforeMiddleware`
+    /// and the `Handler`, and passes the response to the first
+    /// `AfterMiddleware`.
+    ///
+    /// The default implementation calls `before` and continues.
+    fn before_action(&self, req: &mut Request) -> IronResult<BeforeAction> {
+        self.before(req).map(|()| BeforeAction::Continue)
+    }
+
     /// Respond to an error thrown by a previous `BeforeMiddleware`.
     ///
     /// Returning a `Ok` will cause the request to resume the normal flow at the
     /// next `BeforeMiddleware`, or if this was the last `BeforeMiddleware`,
     /// at the `Handler`.
     fn catch(&self, _: &mut Request, err: IronError) -> IronResult<()> { Err(err) }
+
+    /// Decide whether to accept the body of a request sent with `Expect:
+    /// 100-continue`, before the client sends it, such as by checking its
+    /// `Content-Length`.
+    ///
+    /// A `Chain` asks each of its `BeforeMiddleware` in turn, then its
+    /// `Handler`, and rejects the body with the first status other than
+    /// `status::Continue`. See `Handler::check_continue`.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::{headers, status, BeforeMiddleware, Headers};
+    /// # use iron::method::Method;
+    /// # use iron::request::RequestUri;
+    /// # use iron::status::Status;
+    /// struct MaxUpload(u64);
+    ///
+    /// impl BeforeMiddleware for MaxUpload {
+    ///     fn check_continue(&self, _: &Method, _: &RequestUri, headers: &Headers) -> Status {
+    ///         match headers.get::<headers::ContentLength>() {
+    ///             Some(&headers::ContentLength(len)) if len > self.0 => status::ExpectationFailed,
+    ///             _ => status::Continue
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// The default implementation accepts every body.
+    fn check_continue(&self, _: &Method, _: &RequestUri, _: &Headers) -> Status {
+        status::Continue
+    }
+}
+
+/// What a `Chain` should do after a `BeforeMiddleware` has run.
+///
+/// See `BeforeMiddleware::before_action`.
+pub enum BeforeAction {
+    /// Carry on to the next `BeforeMiddleware`, or the `Handler`.
+    Continue,
+
+    /// Skip the rest of the `BeforeMiddleware` and the `Handler`, and pass
+    /// this response to the `AfterMiddleware`.
+    Respond(Response)
 }
 
 /// `AfterMiddleware` are fired after a `Handler` is called inside of a Chain.
@@ -133,7 +243,11 @@ pub struct Chain {
     afters: Vec<Box<AfterMiddleware>>,
 
     // Internal invariant: this is always Some
-    handler: Option<Box<Handler>>
+    handler: Option<Box<Handler>>,
+
+    not_found: Option<Box<Handler>>,
+
+    catch_panics: bool
 }
 
 impl Chain {
@@ -142,7 +256,9 @@ impl Chain {
         Chain {
             befores: vec![],
             afters: vec![],
-            handler: Some(Box::new(handler) as Box<Handler>)
+            handler: Some(Box::new(handler) as Box<Handler>),
+            not_found: None,
+            catch_panics: false
         }
     }
 
@@ -174,6 +290,55 @@ impl Chain {
         self
     }
 
+    /// Set whether panics in the middleware and `Handler` of this `Chain` are
+    /// caught.
+    ///
+    /// When enabled, a panic in any `BeforeMiddleware`, `AfterMiddleware` or
+    /// the `Handler` is logged and turned into an `IronError` wrapping a
+    /// `PanicError`, with a `500 Internal Server Error` response. It then
+    /// enters the error flow from wherever it was raised, just like a returned
+    /// error. Otherwise a panic unwinds the worker thread and the client's
+    /// connection is dropped.
+    ///
+    /// Middleware are called through `AssertUnwindSafe`, since they are given
+    /// `&mut Request`. After a caught panic the `Request` may have been left
+    /// partially modified, so later middleware should not rely on invariants
+    /// which the panicking one was maintaining.
+    ///
+    /// The default is `false`.
+    pub fn catch_panics(&mut self, catch: bool) -> &mut Chain {
+        self.catch_panics = catch;
+        self
+    }
+
+    /// Set a `Handler` to answer requests for which the `Handler` of this
+    /// `Chain` fails with a `NotFound` error, such as when no route matches.
+    ///
+    /// Its response, or error, takes the place of the one from the `Handler`
+    /// and continues through the `AfterMiddleware` as usual. Other errors
+    /// aren't affected, so a missing route can get a custom 404 page while an
+    /// erroring route keeps its own response.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// use iron::error::NotFound;
+    ///
+    /// let mut chain = Chain::new(|req: &mut Request| {
+    ///     match req.url.path[0].as_str() {
+    ///         "hello" => Ok(Response::with((status::Ok, "Hello!"))),
+    ///         _ => Err(IronError::new(NotFound, status::NotFound))
+    ///     }
+    /// });
+    /// chain.not_found(|_: &mut Request| {
+    ///     Ok(Response::with((status::NotFound, "{\"error\":\"Not found\"}")))
+    /// });
+    /// ```
+    pub fn not_found<H: Handler>(&mut self, handler: H) -> &mut Chain {
+        self.not_found = Some(Box::new(handler) as Box<Handler>);
+        self
+    }
+
     /// Apply an `AroundMiddleware` to the `Handler` in this `Chain`.
     ///
     /// Note: This function is being renamed `link_around()`, and will
@@ -184,26 +349,68 @@ impl Chain {
     }
 
     /// Apply an `AroundMiddleware` to the `Handler` in this `Chain`.
+    ///
+    /// Requests sent with `Expect: 100-continue` are still checked with the
+    /// wrapped `Handler::check_continue`, once the handler returned by the
+    /// `AroundMiddleware` has accepted them.
     pub fn link_around<A>(&mut self, around: A) -> &mut Chain
     where A: AroundMiddleware {
-        let mut handler = self.handler.take().unwrap();
-        handler = around.around(handler);
-        self.handler = Some(handler);
+        let inner = Arc::new(self.handler.take().unwrap());
+        let outer = around.around(Box::new(inner.clone()));
+        self.handler = Some(Box::new(Around { outer: outer, inner: inner }));
         self
     }
 }
 
+// A handler wrapped by an `AroundMiddleware`, kept so that its
+// `check_continue` is asked as well as that of the wrapping handler.
+struct Around {
+    outer: Box<Handler>,
+    inner: Arc<Box<Handler>>
+}
+
+impl Handler for Around {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        self.outer.handle(req)
+    }
+
+    fn check_continue(&self, method: &Method, uri: &RequestUri, headers: &Headers) -> Status {
+        match self.outer.check_continue(method, uri, headers) {
+            status::Continue => self.inner.check_continue(method, uri, headers),
+            status => status
+        }
+    }
+}
+
 impl Handler for Chain {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
         // Kick off at befores, which will continue into handler
         // then afters.
         self.continue_from_before(req, 0)
     }
+
+    fn check_continue(&self, method: &Method, uri: &RequestUri, headers: &Headers) -> Status {
+        self.befores.iter()
+            .map(|before| before.check_continue(method, uri, headers))
+            .find(|status| *status != status::Continue)
+            .unwrap_or_else(|| self.handler.as_ref().unwrap().check_continue(method, uri, headers))
+    }
 }
 
 impl Chain {
     ///////////////// Implementation Helpers /////////////////
 
+    // Call into a middleware or the handler, catching panics if enabled.
+    fn guard<T, F>(&self, f: F) -> IronResult<T> where F: FnOnce() -> IronResult<T> {
+        if !self.catch_panics { return f() }
+
+        panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+            let err = PanicError { message: panic_message(&*payload) };
+            error!("Caught panic in middleware chain: {}", err);
+            Err(IronError::new(err, status::InternalServerError))
+        })
+    }
+
     // Enter the error flow from a before middleware, starting
     // at the passed index.
     //
@@ -217,7 +424,7 @@ impl Chain {
         }
 
         for (i, before) in self.befores[index..].iter().enumerate() {
-            err = match before.catch(req, err) {
+            err = match self.guard(|| before.catch(req, err)) {
                 Err(err) => err,
                 Ok(()) => return self.continue_from_before(req, index + i + 1)
             };
@@ -246,7 +453,7 @@ impl Chain {
         if index == self.afters.len() { return Err(err) }
 
         for (i, after) in self.afters[index..].iter().enumerate() {
-            err = match after.catch(req, err) {
+            err = match self.guard(|| after.catch(req, err)) {
                 Err(err) => err,
                 Ok(res) => return self.continue_from_after(req, index + i + 1, res)
             }
@@ -266,8 +473,9 @@ impl Chain {
         }
 
         for (i, before) in self.befores[index..].iter().enumerate() {
-            match before.before(req) {
-                Ok(()) => {},
+            match self.guard(|| before.before_action(req)) {
+                Ok(BeforeAction::Continue) => {},
+                Ok(BeforeAction::Respond(res)) => return self.continue_from_after(req, 0, res),
                 Err(err) => return self.fail_from_before(req, index + i + 1, err)
             }
         }
@@ -279,7 +487,15 @@ impl Chain {
     // Enter the normal flow at the handler.
     fn continue_from_handler(&self, req: &mut Request) -> IronResult<Response> {
         // unwrap is safe because it's always Some
-        match self.handler.as_ref().unwrap().handle(req) {
+        let mut handled = self.guard(|| self.handler.as_ref().unwrap().handle(req));
+
+        if let Some(ref not_found) = self.not_found {
+            if handled.as_ref().err().map_or(false, |err| err.error.is::<NotFound>()) {
+                handled = self.guard(|| not_found.handle(req));
+            }
+        }
+
+        match handled {
             Ok(res) => self.continue_from_after(req, 0, res),
             Err(err) => self.fail_from_handler(req, err)
         }
@@ -295,7 +511,7 @@ impl Chain {
         }
 
         for (i, after) in self.afters[index..].iter().enumerate() {
-            res = match after.after(req, res) {
+            res = match self.guard(|| after.after(req, res)) {
                 Ok(r) => r,
                 Err(err) => return self.fail_from_after(req, index + i + 1, err)
             }
@@ -306,6 +522,14 @@ impl Chain {
     }
 }
 
+// The message of a panic, if it was started with a string.
+fn panic_message(payload: &Any) -> Option<String> {
+    match payload.downcast_ref::<&'static str>() {
+        Some(message) => Some((*message).to_owned()),
+        None => payload.downcast_ref::<String>().cloned()
+    }
+}
+
 impl<F> Handler for F
 where F: Send + Sync + 'static + Fn(&mut Request) -> IronResult<Response> {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
@@ -317,6 +541,20 @@ impl Handler for Box<Handler> {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
         (**self).handle(req)
     }
+
+    fn check_continue(&self, method: &Method, uri: &RequestUri, headers: &Headers) -> Status {
+        (**self).check_continue(method, uri, headers)
+    }
+}
+
+impl<T> Handler for Arc<T> where T: Handler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        (**self).handle(req)
+    }
+
+    fn check_continue(&self, method: &Method, uri: &RequestUri, headers: &Headers) -> Status {
+        (**self).check_continue(method, uri, headers)
+    }
 }
 
 impl<F> BeforeMiddleware for F
@@ -331,9 +569,17 @@ impl BeforeMiddleware for Box<BeforeMiddleware> {
         (**self).before(req)
     }
 
+    fn before_action(&self, req: &mut Request) -> IronResult<BeforeAction> {
+        (**self).before_action(req)
+    }
+
     fn catch(&self, req: &mut Request, err: IronError) -> IronResult<()> {
         (**self).catch(req, err)
     }
+
+    fn check_continue(&self, method: &Method, uri: &RequestUri, headers: &Headers) -> Status {
+        (**self).check_continue(method, uri, headers)
+    }
 }
 
 impl<T> BeforeMiddleware for Arc<T> where T: BeforeMiddleware {
@@ -341,9 +587,17 @@ impl<T> BeforeMiddleware for Arc<T> where T: BeforeMiddleware {
         (**self).before(req)
     }
 
+    fn before_action(&self, req: &mut Request) -> IronResult<BeforeAction> {
+        (**self).before_action(req)
+    }
+
     fn catch(&self, req: &mut Request, err: IronError) -> IronResult<()> {
         (**self).catch(req, err)
     }
+
+    fn check_continue(&self, method: &Method, uri: &RequestUri, headers: &Headers) -> Status {
+        (**self).check_continue(method, uri, headers)
+    }
 }
 
 impl<F> AfterMiddleware for F
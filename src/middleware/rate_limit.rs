@@ -0,0 +1,379 @@
+//! Limiting how many requests each client makes in a period of time.
+
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use typemap::Key;
+
+use {Request, Response, IronResult, IronError, Headers, BeforeMiddleware, AfterMiddleware};
+use request::IpNetwork;
+use status;
+
+/// How `RateLimit` counts requests against its limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitStrategy {
+    /// Allow `limit` requests in each window, starting from a client's first
+    /// request, after which the count starts again. Simple, but a client can
+    /// make up to twice the limit around the end of one window and the start
+    /// of the next.
+    FixedWindow,
+
+    /// Give each client a bucket of `limit` requests, which refills at an
+    /// even rate over the window. Bursts of up to `limit` requests are
+    /// allowed, and the rate is never more than `limit` per window after that.
+    TokenBucket
+}
+
+/// What a `RateLimitStore` decided about one request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    /// Whether the request is let through.
+    pub allowed: bool,
+    /// The most requests allowed per window.
+    pub limit: u64,
+    /// How many more requests the client may make right away.
+    pub remaining: u64,
+    /// How long until the client may make `limit` requests again.
+    pub reset: Duration,
+    /// If the request isn't allowed, how long until the client may make
+    /// another.
+    pub retry_after: Option<Duration>
+}
+
+/// Where `RateLimit` counts the requests made by each client, such as in
+/// memory, or in a store shared between servers so that a client's requests
+/// are counted together whichever server they reach.
+pub trait RateLimitStore: Send + Sync + 'static {
+    /// Count a request by the client `key` against `limit` requests per
+    /// `window`, counted with `strategy`, and decide whether it is allowed.
+    ///
+    /// Requests which aren't allowed shouldn't count against the limit, so a
+    /// client which keeps retrying isn't locked out for longer.
+    fn hit(&self, key: &str, limit: u64, window: Duration, strategy: RateLimitStrategy)
+           -> RateLimitDecision;
+}
+
+/// A `RateLimitStore` which counts requests in memory, for one server.
+///
+/// Clients which haven't made a request for a whole window are forgotten
+/// from time to time, so memory use follows the number of recent clients.
+pub struct MemoryRateLimitStore {
+    entries: Mutex<Entries>
+}
+
+struct Entries {
+    clients: HashMap<String, Entry>,
+    // Once there are this many clients, forget those no longer limited.
+    prune_at: usize
+}
+
+struct Entry {
+    // When the window started, or when the bucket was last refilled.
+    updated: Instant,
+    // Requests made in the window, or tokens left in the bucket.
+    count: f64,
+    window: Duration
+}
+
+impl MemoryRateLimitStore {
+    /// Create an empty `MemoryRateLimitStore`.
+    pub fn new() -> MemoryRateLimitStore {
+        MemoryRateLimitStore {
+            entries: Mutex::new(Entries { clients: HashMap::new(), prune_at: 1024 })
+        }
+    }
+}
+
+impl RateLimitStore for MemoryRateLimitStore {
+    fn hit(&self, key: &str, limit: u64, window: Duration, strategy: RateLimitStrategy)
+           -> RateLimitDecision {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.clients.len() >= entries.prune_at {
+            entries.clients.retain(|_, entry| now.duration_since(entry.updated) < entry.window);
+            entries.prune_at = (entries.clients.len() * 2).max(1024);
+        }
+
+        let fresh = match strategy {
+            RateLimitStrategy::FixedWindow => 0.0,
+            RateLimitStrategy::TokenBucket => limit as f64
+        };
+        let entry = entries.clients.entry(key.to_owned())
+            .or_insert(Entry { updated: now, count: fresh, window: window });
+        entry.window = window;
+
+        let elapsed = now.duration_since(entry.updated);
+        let limit_f = limit as f64;
+        match strategy {
+            RateLimitStrategy::FixedWindow => {
+                if elapsed >= window {
+                    entry.updated = now;
+                    entry.count = 0.0;
+                }
+                let reset = window - now.duration_since(entry.updated);
+
+                let allowed = entry.count < limit_f;
+                if allowed { entry.count += 1.0 }
+                RateLimitDecision {
+                    allowed: allowed,
+                    limit: limit,
+                    remaining: (limit_f - entry.count) as u64,
+                    reset: reset,
+                    retry_after: if allowed { None } else { Some(reset) }
+                }
+            },
+            RateLimitStrategy::TokenBucket => {
+                // Seconds for one token to be refilled.
+                let per_token = duration_secs(window) / limit_f;
+                entry.count = (entry.count + duration_secs(elapsed) / per_token).min(limit_f);
+                entry.updated = now;
+
+                let allowed = entry.count >= 1.0;
+                if allowed { entry.count -= 1.0 }
+                RateLimitDecision {
+                    allowed: allowed,
+                    limit: limit,
+                    remaining: entry.count as u64,
+                    reset: secs_duration((limit_f - entry.count) * per_token),
+                    retry_after: if allowed {
+                        None
+                    } else {
+                        Some(secs_duration((1.0 - entry.count) * per_token))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn duration_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+fn secs_duration(secs: f64) -> Duration {
+    let secs = secs.max(0.0);
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+/// Middleware which limits how many requests each client makes in a window
+/// of time, rejecting the rest with a `429 Too Many Requests` error wrapping
+/// `RateLimited`.
+///
+/// Link it to the chains of the routes to limit. As a `BeforeMiddleware`,
+/// `RateLimit` counts the request and rejects it if the client is over its
+/// limit. As an `AfterMiddleware`, it tells clients where they stand by
+/// adding `X-RateLimit-Limit`, `X-RateLimit-Remaining` and
+/// `X-RateLimit-Reset`, the seconds until the full limit is available again,
+/// to every response. Link it as both:
+///
+/// ```
+/// # use std::time::Duration;
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::{RateLimit, MemoryRateLimitStore, RateLimitStrategy};
+///
+/// let limit = RateLimit::new(MemoryRateLimitStore::new(), 5, Duration::from_secs(60))
+///     .strategy(RateLimitStrategy::TokenBucket)
+///     .key_prefix("login");
+///
+/// let mut chain = Chain::new(|_: &mut Request| Ok(Response::with((status::Ok, "Logged in"))));
+/// chain.link((limit.clone(), limit));
+/// ```
+///
+/// Rejected requests also get a `Retry-After` header, and don't count
+/// against the limit. Clients are told apart by their address, which is the
+/// request's `remote_addr` unless it is one of the proxies given to
+/// `trust_proxy`, as described by `Request::real_remote_addr`.
+///
+/// Chains limited separately can share a store, as long as each has its own
+/// `key_prefix`.
+#[derive(Clone)]
+pub struct RateLimit {
+    store: Arc<RateLimitStore>,
+    limit: u64,
+    window: Duration,
+    strategy: RateLimitStrategy,
+    prefix: String,
+    trusted: Vec<IpNetwork>
+}
+
+impl RateLimit {
+    /// Create a `RateLimit` which lets each client make `limit` requests per
+    /// `window`, counted in `store` with `RateLimitStrategy::FixedWindow`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `limit` is `0` or `window` is empty.
+    pub fn new<S: RateLimitStore>(store: S, limit: u64, window: Duration) -> RateLimit {
+        assert!(limit != 0, "A RateLimit must allow at least one request.");
+        assert!(window != Duration::from_secs(0), "A RateLimit needs a window of some length.");
+        RateLimit {
+            store: Arc::new(store),
+            limit: limit,
+            window: window,
+            strategy: RateLimitStrategy::FixedWindow,
+            prefix: "rate-limit".to_owned(),
+            trusted: vec![]
+        }
+    }
+
+    /// Set how requests are counted.
+    pub fn strategy(mut self, strategy: RateLimitStrategy) -> RateLimit {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Set what the keys of clients in the store start with, to keep them
+    /// apart from those of other `RateLimit`s sharing the store.
+    pub fn key_prefix(mut self, prefix: &str) -> RateLimit {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Trust the proxies in `network` to give the client address in
+    /// `X-Forwarded-For`.
+    pub fn trust_proxy(mut self, network: IpNetwork) -> RateLimit {
+        self.trusted.push(network);
+        self
+    }
+}
+
+// The decision made for a request, to describe in its response.
+struct DecisionKey;
+
+impl Key for DecisionKey { type Value = RateLimitDecision; }
+
+// Round up to whole seconds, so clients never retry too early.
+fn whole_secs(duration: Duration) -> u64 {
+    duration.as_secs() + if duration.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+fn add_headers(decision: &RateLimitDecision, headers: &mut Headers) {
+    let mut set = |name: &str, value: u64| {
+        headers.set_raw(name.to_owned(), vec![value.to_string().into_bytes()]);
+    };
+
+    set("X-RateLimit-Limit", decision.limit);
+    set("X-RateLimit-Remaining", decision.remaining);
+    set("X-RateLimit-Reset", whole_secs(decision.reset));
+    if let Some(retry_after) = decision.retry_after {
+        set("Retry-After", whole_secs(retry_after));
+    }
+}
+
+impl BeforeMiddleware for RateLimit {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let key = format!("{}:{}", self.prefix, req.real_remote_addr(&self.trusted).ip());
+        let decision = self.store.hit(&key, self.limit, self.window, self.strategy);
+        req.extensions.insert::<DecisionKey>(decision);
+
+        match decision.retry_after {
+            Some(retry_after) if !decision.allowed => {
+                let mut err = IronError::new(RateLimited { retry_after: retry_after },
+                                             status::TooManyRequests);
+                add_headers(&decision, &mut err.response.headers);
+                Err(err)
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+impl AfterMiddleware for RateLimit {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        if let Some(decision) = req.extensions.get::<DecisionKey>() {
+            add_headers(decision, &mut res.headers);
+        }
+        Ok(res)
+    }
+
+    fn catch(&self, req: &mut Request, mut err: IronError) -> IronResult<Response> {
+        if let Some(decision) = req.extensions.get::<DecisionKey>() {
+            add_headers(decision, &mut err.response.headers);
+        }
+        Err(err)
+    }
+}
+
+/// The error produced by `RateLimit` when a client has made too many
+/// requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimited {
+    /// How long until the client may make another request.
+    pub retry_after: Duration
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Too many requests; retry in {} seconds", whole_secs(self.retry_after))
+    }
+}
+
+impl StdError for RateLimited {
+    fn description(&self) -> &str {
+        "Too many requests"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use prelude::*;
+    use {method, status, test, Handler, Url};
+
+    use super::{RateLimit, RateLimitStore, RateLimitStrategy, MemoryRateLimitStore, RateLimited};
+
+    #[test]
+    fn test_rate_limit() {
+        let limit = RateLimit::new(MemoryRateLimitStore::new(), 2, Duration::from_secs(60));
+        let mut chain = Chain::new(|_: &mut Request| Ok(Response::with((status::Ok, "done"))));
+        chain.link((limit.clone(), limit));
+
+        let handle = || {
+            test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                       &[], |req| chain.handle(req))
+        };
+
+        for remaining in &["1", "0"] {
+            let res = handle().unwrap();
+            assert_eq!(test::response_header(&res, "X-RateLimit-Limit").unwrap(), "2");
+            assert_eq!(test::response_header(&res, "X-RateLimit-Remaining").unwrap(), *remaining);
+            assert_eq!(test::response_header(&res, "X-RateLimit-Reset").unwrap(), "60");
+            assert_eq!(test::response_header(&res, "Retry-After"), None);
+        }
+
+        let err = handle().unwrap_err();
+        assert_eq!(err.response.status, Some(status::TooManyRequests));
+        assert_eq!(test::response_header(&err.response, "X-RateLimit-Remaining").unwrap(), "0");
+        assert_eq!(test::response_header(&err.response, "Retry-After").unwrap(), "60");
+        assert!(err.error.downcast::<RateLimited>().is_some());
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let store = MemoryRateLimitStore::new();
+        let (window, bucket) = (Duration::from_millis(200), RateLimitStrategy::TokenBucket);
+
+        assert!(store.hit("a", 2, window, bucket).allowed);
+        assert!(store.hit("a", 2, window, bucket).allowed);
+        let rejected = store.hit("a", 2, window, bucket);
+        assert!(!rejected.allowed);
+        assert!(rejected.retry_after.unwrap() <= Duration::from_millis(100));
+
+        // Other clients have buckets of their own.
+        assert!(store.hit("b", 2, window, bucket).allowed);
+
+        // One token is back after half the window.
+        thread::sleep(Duration::from_millis(110));
+        let decision = store.hit("a", 2, window, bucket);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert!(!store.hit("a", 2, window, bucket).allowed);
+    }
+}
@@ -0,0 +1,153 @@
+//! Identifying requests across logs.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use typemap::Key;
+
+use {Request, Response, IronResult, IronError, BeforeMiddleware, AfterMiddleware};
+
+/// Middleware which gives each request an ID, for correlating the log lines
+/// and services involved in handling it.
+///
+/// The ID is taken from the request's `X-Request-Id` header, so an ID
+/// assigned by a proxy or calling service is kept, or generated if there is
+/// none. It can be read with `Request::request_id` and is sent back in the
+/// `X-Request-Id` header of the response.
+///
+/// `RequestId` is both a `BeforeMiddleware` and an `AfterMiddleware`. Link it
+/// as both; clones share the counter used to generate IDs:
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::RequestId;
+///
+/// let request_id = RequestId::new();
+///
+/// let mut chain = Chain::new(|req: &mut Request| {
+///     Ok(Response::with((status::Ok, format!("Request {}", req.request_id().unwrap()))))
+/// });
+/// chain.link((request_id.clone(), request_id));
+/// ```
+///
+/// Generated IDs are a random prefix chosen when the `RequestId` is created,
+/// followed by a counter, so they are cheap to make and unique across
+/// restarts. Incoming IDs are only kept if they are at most 200 printable
+/// ASCII characters.
+#[derive(Clone, Debug)]
+pub struct RequestId {
+    prefix: u64,
+    counter: Arc<AtomicUsize>
+}
+
+// The ID of a request.
+struct RequestIdKey;
+
+impl Key for RequestIdKey { type Value = String; }
+
+impl RequestId {
+    /// Create a `RequestId` with a new random prefix.
+    pub fn new() -> RequestId {
+        RequestId {
+            prefix: RandomState::new().build_hasher().finish(),
+            counter: Arc::new(AtomicUsize::new(0))
+        }
+    }
+
+    fn generate(&self) -> String {
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        format!("{:016x}-{:08x}", self.prefix, count)
+    }
+}
+
+fn is_valid_id(id: &[u8]) -> bool {
+    !id.is_empty() && id.len() <= 200 && id.iter().all(|&b| b > 0x20 && b < 0x7F)
+}
+
+impl<'a> Request<'a> {
+    /// The ID given to this request by the `RequestId` middleware, if any.
+    pub fn request_id(&self) -> Option<&str> {
+        self.extensions.get::<RequestIdKey>().map(|id| &**id)
+    }
+}
+
+impl BeforeMiddleware for RequestId {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let incoming = match req.headers.get_raw("X-Request-Id") {
+            Some(lines) if lines.len() == 1 && is_valid_id(&lines[0]) => {
+                Some(String::from_utf8(lines[0].clone()).unwrap())
+            },
+            _ => None
+        };
+
+        let id = incoming.unwrap_or_else(|| self.generate());
+        req.extensions.insert::<RequestIdKey>(id);
+        Ok(())
+    }
+
+    fn catch(&self, req: &mut Request, err: IronError) -> IronResult<()> {
+        try!(self.before(req));
+        Err(err)
+    }
+}
+
+fn echo_id(req: &Request, res: &mut Response) {
+    if let Some(id) = req.request_id() {
+        res.headers.set_raw("X-Request-Id", vec![id.as_bytes().to_vec()]);
+    }
+}
+
+impl AfterMiddleware for RequestId {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        echo_id(req, &mut res);
+        Ok(res)
+    }
+
+    fn catch(&self, req: &mut Request, mut err: IronError) -> IronResult<Response> {
+        echo_id(req, &mut err.response);
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prelude::*;
+    use {method, status, test, Handler, Url};
+
+    use super::RequestId;
+
+    fn handle(request_id: &RequestId, incoming: Option<&str>) -> (String, String) {
+        let mut chain = Chain::new(|req: &mut Request| {
+            Ok(Response::with((status::Ok, req.request_id().unwrap().to_owned())))
+        });
+        chain.link((request_id.clone(), request_id.clone()));
+
+        let headers: Vec<_> = incoming.into_iter()
+            .map(|incoming| ("X-Request-Id", incoming)).collect();
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, &headers, |req| {
+            let res = chain.handle(req).unwrap();
+            let echoed = test::response_header(&res, "X-Request-Id").unwrap();
+            (req.request_id().unwrap().to_owned(), echoed)
+        })
+    }
+
+    #[test]
+    fn test_request_id() {
+        let request_id = RequestId::new();
+
+        assert_eq!(handle(&request_id, Some("abc-123")),
+                   ("abc-123".to_owned(), "abc-123".to_owned()));
+
+        let (first, echoed) = handle(&request_id, None);
+        assert_eq!(first, echoed);
+        assert!(first.ends_with("-00000000"));
+
+        let (second, _) = handle(&request_id, Some("bad id\r\n"));
+        assert!(second.ends_with("-00000001"));
+        assert_eq!(first[..16], second[..16]);
+    }
+}
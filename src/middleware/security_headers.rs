@@ -0,0 +1,171 @@
+//! Setting common security headers on every response.
+
+use {Request, Response, IronResult, IronError, AfterMiddleware};
+
+/// Middleware which adds headers asking browsers to enable protections
+/// against common attacks to every response, including error responses.
+///
+/// By default these are:
+///
+/// * `Strict-Transport-Security: max-age=31536000; includeSubDomains`, only
+///   on requests made over HTTPS, as browsers ignore it otherwise.
+/// * `X-Content-Type-Options: nosniff`
+/// * `X-Frame-Options: SAMEORIGIN`
+///
+/// A `Content-Security-Policy` depends too much on the application to have a
+/// default, so it is only sent once set with `content_security_policy`. Each
+/// header can be changed or disabled, and none of them replaces a header the
+/// handler has already set, so single responses can differ.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::middleware::SecurityHeaders;
+///
+/// let headers = SecurityHeaders::new()
+///     .content_security_policy(Some("default-src 'self'"))
+///     .frame_options(Some("DENY"));
+///
+/// let mut chain = Chain::new(|_: &mut Request| Ok(Response::with((status::Ok, "Hello"))));
+/// chain.link_after(headers);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    hsts: Option<String>,
+    nosniff: bool,
+    frame_options: Option<String>,
+    csp: Option<String>,
+    trust_forwarded: bool
+}
+
+impl SecurityHeaders {
+    /// Create a `SecurityHeaders` which sends the default headers.
+    pub fn new() -> SecurityHeaders {
+        SecurityHeaders {
+            hsts: Some("max-age=31536000; includeSubDomains".to_owned()),
+            nosniff: true,
+            frame_options: Some("SAMEORIGIN".to_owned()),
+            csp: None,
+            trust_forwarded: false
+        }
+    }
+
+    /// Set the value of `Strict-Transport-Security`, such as
+    /// `max-age=63072000; includeSubDomains; preload`, or disable it with
+    /// `None`.
+    pub fn hsts(mut self, value: Option<&str>) -> SecurityHeaders {
+        self.hsts = value.map(header_value);
+        self
+    }
+
+    /// Set whether to send `X-Content-Type-Options: nosniff`.
+    pub fn nosniff(mut self, nosniff: bool) -> SecurityHeaders {
+        self.nosniff = nosniff;
+        self
+    }
+
+    /// Set the value of `X-Frame-Options`, such as `DENY`, or disable it with
+    /// `None`.
+    pub fn frame_options(mut self, value: Option<&str>) -> SecurityHeaders {
+        self.frame_options = value.map(header_value);
+        self
+    }
+
+    /// Set the value of `Content-Security-Policy`, or disable it with `None`.
+    pub fn content_security_policy(mut self, value: Option<&str>) -> SecurityHeaders {
+        self.csp = value.map(header_value);
+        self
+    }
+
+    /// Set whether to trust `X-Forwarded-Proto` to tell whether the request
+    /// was made over HTTPS, as described by `Request::scheme`. Only set it when
+    /// every request arrives through a proxy which sets the header.
+    pub fn trust_forwarded(mut self, trust: bool) -> SecurityHeaders {
+        self.trust_forwarded = trust;
+        self
+    }
+
+    fn add_headers(&self, req: &Request, res: &mut Response) {
+        let mut set = |name: &str, value: &str| {
+            if res.headers.get_raw(name).is_none() {
+                res.headers.set_raw(name.to_owned(), vec![value.as_bytes().to_vec()]);
+            }
+        };
+
+        if let Some(ref hsts) = self.hsts {
+            if req.scheme(self.trust_forwarded) == "https" {
+                set("Strict-Transport-Security", hsts);
+            }
+        }
+        if self.nosniff { set("X-Content-Type-Options", "nosniff") }
+        if let Some(ref frame_options) = self.frame_options {
+            set("X-Frame-Options", frame_options)
+        }
+        if let Some(ref csp) = self.csp { set("Content-Security-Policy", csp) }
+    }
+}
+
+// Check that `value` can be sent as a header value.
+fn header_value(value: &str) -> String {
+    assert!(!value.contains(|c: char| c.is_control()),
+            "Header value {:?} contains control characters", value);
+    value.to_owned()
+}
+
+impl AfterMiddleware for SecurityHeaders {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        self.add_headers(req, &mut res);
+        Ok(res)
+    }
+
+    fn catch(&self, req: &mut Request, mut err: IronError) -> IronResult<Response> {
+        self.add_headers(req, &mut err.response);
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prelude::*;
+    use {method, status, test, Handler, Url};
+
+    use super::SecurityHeaders;
+
+    fn handle(headers: SecurityHeaders, url: &str, forwarded_proto: Option<&str>) -> Response {
+        let mut chain = Chain::new(|_: &mut Request| {
+            let mut res = Response::with((status::Ok, "body"));
+            res.headers.set_raw("X-Frame-Options", vec![b"DENY".to_vec()]);
+            Ok(res)
+        });
+        chain.link_after(headers);
+
+        let raw: Vec<_> = forwarded_proto.into_iter()
+            .map(|proto| ("X-Forwarded-Proto", proto)).collect();
+        test::request_with_headers(method::Get, Url::parse(url).unwrap(), &raw,
+                                   |req| chain.handle(req).unwrap())
+    }
+
+    #[test]
+    fn test_security_headers() {
+        let res = handle(SecurityHeaders::new(), "https://localhost:3000/", None);
+        assert_eq!(test::response_header(&res, "Strict-Transport-Security").unwrap(),
+                   "max-age=31536000; includeSubDomains");
+        assert_eq!(test::response_header(&res, "X-Content-Type-Options").unwrap(), "nosniff");
+        assert_eq!(test::response_header(&res, "X-Frame-Options").unwrap(), "DENY");
+        assert_eq!(test::response_header(&res, "Content-Security-Policy"), None);
+
+        let res = handle(SecurityHeaders::new(), "http://localhost:3000/", Some("https"));
+        assert_eq!(test::response_header(&res, "Strict-Transport-Security"), None);
+
+        let headers = SecurityHeaders::new()
+            .trust_forwarded(true)
+            .hsts(Some("max-age=60"))
+            .nosniff(false)
+            .content_security_policy(Some("default-src 'self'"));
+        let res = handle(headers, "http://localhost:3000/", Some("https"));
+        assert_eq!(test::response_header(&res, "Strict-Transport-Security").unwrap(), "max-age=60");
+        assert_eq!(test::response_header(&res, "X-Content-Type-Options"), None);
+        assert_eq!(test::response_header(&res, "Content-Security-Policy").unwrap(),
+                   "default-src 'self'");
+    }
+}
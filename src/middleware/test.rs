@@ -1,4 +1,4 @@
-use std::net::ToSocketAddrs;
+use std::io::{self, Read};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
@@ -6,8 +6,11 @@ use std::sync::Arc;
 use self::Kind::{Fine, Prob};
 
 use prelude::*;
-use {method, headers};
-use {AfterMiddleware, BeforeMiddleware, Handler, TypeMap, Url};
+use method;
+use status;
+use error::{NotFound, PanicError};
+use request::{Body, RequestUri};
+use {AfterMiddleware, BeforeMiddleware, BeforeAction, Handler, Headers, Url, headers};
 
 #[test] fn test_chain_normal() {
     test_chain(
@@ -64,6 +67,144 @@ use {AfterMiddleware, BeforeMiddleware, Handler, TypeMap, Url};
     );
 }
 
+#[test] fn test_chain_catch_panics() {
+    let caught = sharedbool(false);
+    let after_caught = caught.clone();
+
+    let mut chain = Chain::new(|_: &mut Request| -> IronResult<Response> { panic!("oh no") });
+    chain.catch_panics(true);
+    chain.link_after(move |_: &mut Request, res: Response| Ok(res));
+    chain.link_after(CatchPanic(after_caught));
+
+    let mut reader = io::empty();
+    let res = chain.handle(&mut request(&mut reader)).unwrap();
+    assert_eq!(res.status, Some(status::InternalServerError));
+    assert!(caught.load(Relaxed));
+}
+
+#[test] fn test_chain_before_respond() {
+    struct NotModified;
+
+    impl BeforeMiddleware for NotModified {
+        fn before_action(&self, _: &mut Request) -> IronResult<BeforeAction> {
+            Ok(BeforeAction::Respond(Response::with(status::NotModified)))
+        }
+    }
+
+    let mut chain = Chain::new(|_: &mut Request| -> IronResult<Response> {
+        panic!("The handler should have been skipped")
+    });
+    chain.link_before(NotModified);
+    chain.link_before(|_: &mut Request| -> IronResult<()> {
+        panic!("Later before middleware should have been skipped")
+    });
+    chain.link_after(|_: &mut Request, mut res: Response| {
+        res.headers.set_raw("X-After", vec![b"yes".to_vec()]);
+        Ok(res)
+    });
+
+    let mut reader = io::empty();
+    let res = chain.handle(&mut request(&mut reader)).unwrap();
+    assert_eq!(res.status, Some(status::NotModified));
+    assert!(res.headers.get_raw("X-After").is_some());
+}
+
+#[test] fn test_chain_check_continue() {
+    struct MaxUpload(u64);
+
+    impl BeforeMiddleware for MaxUpload {
+        fn check_continue(&self, _: &method::Method, _: &RequestUri, headers: &Headers)
+                          -> status::Status {
+            match headers.get::<headers::ContentLength>() {
+                Some(&headers::ContentLength(len)) if len > self.0 => status::ExpectationFailed,
+                _ => status::Continue
+            }
+        }
+    }
+
+    let mut chain = Chain::new(|_: &mut Request| Ok(Response::new()));
+    chain.link_before(MaxUpload(10));
+
+    let uri = RequestUri::AbsolutePath("/".to_owned());
+    let mut headers = Headers::new();
+    headers.set(headers::ContentLength(10));
+    assert_eq!(chain.check_continue(&method::Put, &uri, &headers), status::Continue);
+    headers.set(headers::ContentLength(11));
+    assert_eq!(chain.check_continue(&method::Put, &uri, &headers), status::ExpectationFailed);
+}
+
+#[test] fn test_link_around_check_continue() {
+    struct MaxUpload(u64);
+
+    impl Handler for MaxUpload {
+        fn handle(&self, _: &mut Request) -> IronResult<Response> {
+            Ok(Response::with(status::Ok))
+        }
+
+        fn check_continue(&self, _: &method::Method, _: &RequestUri, headers: &Headers)
+                          -> status::Status {
+            match headers.get::<headers::ContentLength>() {
+                Some(&headers::ContentLength(len)) if len > self.0 => status::ExpectationFailed,
+                _ => status::Continue
+            }
+        }
+    }
+
+    // The wrapping handler only knows about `handle`.
+    let mut chain = Chain::new(MaxUpload(10));
+    chain.link_around(|handler: Box<Handler>| -> Box<Handler> {
+        Box::new(move |req: &mut Request| handler.handle(req))
+    });
+
+    let uri = RequestUri::AbsolutePath("/".to_owned());
+    let mut headers = Headers::new();
+    headers.set(headers::ContentLength(10));
+    assert_eq!(chain.check_continue(&method::Put, &uri, &headers), status::Continue);
+    headers.set(headers::ContentLength(11));
+    assert_eq!(chain.check_continue(&method::Put, &uri, &headers), status::ExpectationFailed);
+
+    let mut reader = io::empty();
+    let mut req = request(&mut reader);
+    assert_eq!(chain.handle(&mut req).unwrap().status, Some(status::Ok));
+}
+
+#[test] fn test_chain_not_found() {
+    let mut chain = Chain::new(|req: &mut Request| -> IronResult<Response> {
+        if req.url.path[0] == "missing" {
+            Err(IronError::new(NotFound, status::NotFound))
+        } else {
+            Err(IronError::new(io::Error::new(io::ErrorKind::Other, "broken"), status::BadGateway))
+        }
+    });
+    chain.not_found(|_: &mut Request| Ok(Response::with((status::NotFound, "Custom"))));
+    chain.link_after(|_: &mut Request, mut res: Response| {
+        res.headers.set_raw("X-After", vec![b"yes".to_vec()]);
+        Ok(res)
+    });
+
+    let mut reader = io::empty();
+    let mut req = request(&mut reader);
+    req.url = Url::parse("http://localhost:3000/missing").unwrap();
+    let res = chain.handle(&mut req).unwrap();
+    assert_eq!(res.status, Some(status::NotFound));
+    assert!(res.headers.get_raw("X-After").is_some());
+
+    let mut reader = io::empty();
+    let err = chain.handle(&mut request(&mut reader)).unwrap_err();
+    assert_eq!(err.response.status, Some(status::BadGateway));
+}
+
+struct CatchPanic(Arc<AtomicBool>);
+
+impl AfterMiddleware for CatchPanic {
+    fn catch(&self, _: &mut Request, err: IronError) -> IronResult<Response> {
+        let message = err.error.downcast::<PanicError>().and_then(|e| e.message.clone());
+        assert_eq!(message, Some("oh no".to_owned()));
+        self.0.store(true, Relaxed);
+        Ok(err.response)
+    }
+}
+
 // Used to indicate the action taken by a middleware or handler.
 #[derive(Debug, PartialEq)]
 enum Kind {
@@ -134,16 +275,9 @@ impl AfterMiddleware for Middleware {
 }
 
 // Stub request
-fn request<'a, 'b>() -> Request<'a, 'b> {
-    Request {
-        url: Url::parse("http://www.rust-lang.org").unwrap(),
-        remote_addr: "localhost:3000".to_socket_addrs().unwrap().next().unwrap(),
-        local_addr: "localhost:3000".to_socket_addrs().unwrap().next().unwrap(),
-        headers: headers::Headers::new(),
-        body: unsafe { ::std::mem::uninitialized() }, // FIXME(reem): Ugh
-        method: method::Get,
-        extensions: TypeMap::new()
-    }
+fn request<'a>(reader: &'a mut Read) -> Request<'a> {
+    ::test::request(method::Get, Url::parse("http://www.rust-lang.org").unwrap(), None,
+                    Body::from_reader(reader, Some(0), false))
 }
 
 // Stub response
@@ -216,7 +350,9 @@ fn to_chain(counters: &ChainLike<Twice<Arc<AtomicBool>>>,
     Chain {
         befores: befores,
         handler: Some(Box::new(handler) as Box<Handler>),
-        afters: afters
+        afters: afters,
+        not_found: None,
+        catch_panics: false
     }
 }
 
@@ -240,7 +376,8 @@ fn test_chain(chain: ChainLike<Kind>, expected: ChainLike<Kind>) {
     let chain = to_chain(&actual, chain);
 
     // Run the chain
-    let _ = chain.handle(&mut request());
+    let mut reader = io::empty();
+    let _ = chain.handle(&mut request(&mut reader));
 
     // Get all the results
     let outbefores = actual.0.into_iter()
@@ -46,18 +46,20 @@
 //! For more information about the modifier system, see
 //! [rust-modifier](https://github.com/reem/rust-modifier).
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
 use modifier::Modifier;
 
 use hyper::mime::Mime;
+use time;
 
-use {status, headers, Request, Response, Set, Url};
+use {status, headers, IronError, IronResult, Request, Response, Set, Url};
 
 use mime_types;
-use response::{WriteBody, BodyReader};
+use response::{WriteBody, BodyReader, BodyStream};
 
 lazy_static! {
     static ref MIME_TYPES: mime_types::Types = mime_types::Types::new().unwrap();
@@ -84,6 +86,19 @@ impl <R: io::Read + Send + 'static> Modifier<Response> for BodyReader<R> {
     }
 }
 
+impl<I> Modifier<Response> for BodyStream<I>
+        where I: Iterator<Item = io::Result<Vec<u8>>> + Send + 'static {
+    /// Stream the body using chunked transfer encoding.
+    ///
+    /// Any `Content-Length` is removed. If the iterator yields an error, the
+    /// response is left unfinished and the connection is closed, since that
+    /// is the only way to abort a response part way through.
+    fn modify(self, res: &mut Response) {
+        res.headers.remove::<headers::ContentLength>();
+        res.body = Some(Box::new(self));
+    }
+}
+
 impl Modifier<Response> for String {
     #[inline]
     fn modify(self, res: &mut Response) {
@@ -140,6 +155,62 @@ impl<'a> Modifier<Response> for &'a Path {
     }
 }
 
+// Defined here rather than in `response` to share `MIME_TYPES`.
+impl Response {
+    /// Construct a `200 OK` response which streams the file at `path`.
+    ///
+    /// `Content-Length` is set from the file's size, `Content-Type` is
+    /// guessed from its extension and `Last-Modified` is set from its
+    /// modification time, for use with `Request::is_fresh`.
+    ///
+    /// If `path` is a symlink it isn't followed, since it could lead outside
+    /// the directory being served, and fails with `403 Forbidden`. Missing files and
+    /// directories fail with `404 Not Found`. The error is an `io::Error`.
+    ///
+    /// ```no_run
+    /// # use iron::prelude::*;
+    /// fn handler(_: &mut Request) -> IronResult<Response> {
+    ///     Response::from_file("static/index.html")
+    /// }
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> IronResult<Response> {
+        let path = path.as_ref();
+        let file = try!(open_regular_file(path).map_err(|err| {
+            let status = match err.kind() {
+                io::ErrorKind::NotFound => status::NotFound,
+                io::ErrorKind::PermissionDenied => status::Forbidden,
+                _ => status::InternalServerError
+            };
+            IronError::new(err, status)
+        }));
+
+        let modified = file.metadata().and_then(|metadata| metadata.modified()).ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+
+        let mut res = Response::with((status::Ok, file));
+        let _ = MIME_TYPES.mime_for_path(path).parse().map(|mime: Mime| res.set_mut(mime));
+        if let Some(modified) = modified {
+            let tm = time::at_utc(time::Timespec::new(modified.as_secs() as i64, 0));
+            res.headers.set(headers::LastModified(headers::HttpDate(tm)));
+        }
+
+        Ok(res)
+    }
+}
+
+fn open_regular_file(path: &Path) -> io::Result<File> {
+    if try!(fs::symlink_metadata(path)).file_type().is_symlink() {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Not following a symlink"));
+    }
+
+    let file = try!(File::open(path));
+    if !try!(file.metadata()).is_file() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "Not a file"));
+    }
+
+    Ok(file)
+}
+
 impl Modifier<Response> for PathBuf {
     /// Set the body to the contents of the File at this path.
     ///
@@ -195,3 +266,92 @@ impl Modifier<Response> for RedirectRaw {
         res.headers.set(headers::Location(path));
     }
 }
+
+/// A modifier for choosing whether the connection is kept alive after the
+/// response is sent.
+///
+/// `KeepAlive(false)` sends `Connection: close`, and the server closes the
+/// connection once the response has been written. `KeepAlive(true)` removes
+/// any `Connection` header set earlier, restoring the default: HTTP/1.1
+/// connections are kept alive unless the client asked otherwise.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::modifiers::KeepAlive;
+///
+/// let res = Response::with((status::Ok, KeepAlive(false)));
+/// ```
+pub struct KeepAlive(pub bool);
+
+impl Modifier<Response> for KeepAlive {
+    fn modify(self, res: &mut Response) {
+        if self.0 {
+            res.headers.remove::<headers::Connection>();
+        } else {
+            res.headers.set(headers::Connection::close());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    use prelude::*;
+    use {headers, status, test};
+
+    use super::KeepAlive;
+
+    // Read one response, which ends with `done`, leaving the connection open.
+    fn read_response(stream: &mut TcpStream) -> String {
+        let mut res = Vec::new();
+        let mut buf = [0; 1024];
+        while !res.ends_with(b"done") {
+            let read = stream.read(&mut buf).unwrap();
+            assert!(read > 0, "connection closed early");
+            res.extend_from_slice(&buf[..read]);
+        }
+        String::from_utf8(res).unwrap()
+    }
+
+    #[test]
+    fn test_keep_alive_headers() {
+        let res = Response::with((status::Ok, KeepAlive(false)));
+        assert_eq!(test::response_header(&res, "Connection"), Some("close".to_owned()));
+
+        let mut res = Response::with(status::Ok);
+        res.headers.set(headers::Connection::close());
+        let res = res.set(KeepAlive(true));
+        assert!(!res.headers.has::<headers::Connection>());
+    }
+
+    #[test]
+    fn test_keep_alive_connections() {
+        let (mut listening, signal) = Iron::new(|req: &mut Request| {
+            let keep_alive = req.url.path[0] != "close";
+            Ok(Response::with((status::Ok, KeepAlive(keep_alive), "done")))
+        }).threads(2).http_with_shutdown("127.0.0.1:0").unwrap();
+
+        let mut stream = TcpStream::connect(listening.socket).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        // The same connection serves another request after `KeepAlive(true)`,
+        for _ in 0..2 {
+            write!(stream, "GET /open HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            assert!(!read_response(&mut stream).contains("Connection: close\r\n"));
+        }
+
+        // but is closed after `KeepAlive(false)`.
+        write!(stream, "GET /close HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.contains("Connection: close\r\n"));
+        assert!(res.ends_with("\r\n\r\ndone"));
+
+        signal.shutdown(Duration::from_secs(1));
+        listening.close().unwrap();
+    }
+}
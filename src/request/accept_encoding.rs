@@ -0,0 +1,118 @@
+//! Content coding negotiation using the `Accept-Encoding` header.
+
+use super::Request;
+
+impl<'a> Request<'a> {
+    /// The content codings listed in the client's `Accept-Encoding` header,
+    /// with their qualities, in the order given.
+    ///
+    /// Codings are lowercased, and `x-gzip` and `x-compress` are given as
+    /// `gzip` and `compress`. A missing or malformed quality counts as `1.0`.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use iron::{method, test, Headers, Url};
+    /// # use iron::request::Body;
+    /// # let mut headers = Headers::new();
+    /// # headers.set_raw("Accept-Encoding", vec![b"gzip;q=0.8, br".to_vec()]);
+    /// # let mut reader = io::empty();
+    /// # let req = test::request(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+    /// #                         Some(headers), Body::from_reader(&mut reader, Some(0), false));
+    /// // Accept-Encoding: gzip;q=0.8, br
+    /// assert_eq!(req.accepted_encodings(),
+    ///            vec![("gzip".to_owned(), 0.8), ("br".to_owned(), 1.0)]);
+    /// ```
+    pub fn accepted_encodings(&self) -> Vec<(String, f32)> {
+        let lines = match self.headers.get_raw("Accept-Encoding") {
+            Some(lines) => lines,
+            None => return Vec::new()
+        };
+
+        lines.iter()
+            .flat_map(|line| line.split(|&b| b == b','))
+            .filter_map(|entry| parse_coding(&String::from_utf8_lossy(entry)))
+            .collect()
+    }
+
+    /// Whether the client accepts responses with the content coding `enc`,
+    /// such as `"gzip"`.
+    ///
+    /// A coding is accepted if it is listed with a nonzero quality, or, if it
+    /// isn't listed, if `*` is. The `identity` coding, meaning no
+    /// compression, is accepted unless it is refused explicitly with
+    /// `identity;q=0` or through `*;q=0`.
+    ///
+    /// Clients that send no `Accept-Encoding` are taken to accept only
+    /// `identity`, since many of them can't decompress responses.
+    pub fn accepts_encoding(&self, enc: &str) -> bool {
+        let enc = enc.to_ascii_lowercase();
+        let enc = canonical(&enc);
+        let accepted = self.accepted_encodings();
+
+        let quality = accepted.iter()
+            .find(|&&(ref coding, _)| *coding == enc)
+            .or_else(|| accepted.iter().find(|&&(ref coding, _)| coding == "*"))
+            .map(|&(_, quality)| quality);
+
+        match quality {
+            Some(quality) => quality > 0.0,
+            None => enc == "identity"
+        }
+    }
+}
+
+// Parse one entry of an `Accept-Encoding` header, such as `gzip;q=0.5`.
+fn parse_coding(entry: &str) -> Option<(String, f32)> {
+    let mut parts = entry.split(';').map(|part| part.trim());
+
+    let coding = parts.next().unwrap().to_ascii_lowercase();
+    if coding.is_empty() { return None }
+
+    let quality = parts
+        .find(|part| part.starts_with("q=") || part.starts_with("Q="))
+        .and_then(|q| q[2..].parse::<f32>().ok())
+        .and_then(|q| if 0.0 <= q && q <= 1.0 { Some(q) } else { None })
+        .unwrap_or(1.0);
+
+    Some((canonical(&coding).to_owned(), quality))
+}
+
+// The registered name of a content coding, mapping the deprecated `x-` aliases.
+fn canonical(coding: &str) -> &str {
+    match coding {
+        "x-gzip" => "gzip",
+        "x-compress" => "compress",
+        coding => coding
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {method, test, Url};
+
+    fn accepts(accept: Option<&str>, enc: &str) -> bool {
+        let headers: Vec<_> = accept.into_iter().map(|value| ("Accept-Encoding", value)).collect();
+        test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                   &headers, |req| req.accepts_encoding(enc))
+    }
+
+    #[test]
+    fn test_accepts_encoding() {
+        assert!(accepts(Some("deflate, GZIP;q=0.5"), "gzip"));
+        assert!(accepts(Some("x-gzip"), "gzip"));
+        assert!(accepts(Some("br, *;q=0.1"), "gzip"));
+        assert!(accepts(Some("gzip;q=nonsense"), "gzip"));
+        assert!(!accepts(Some("gzip;q=0, *"), "gzip"));
+        assert!(!accepts(Some("br"), "gzip"));
+        assert!(!accepts(None, "gzip"));
+    }
+
+    #[test]
+    fn test_accepts_identity() {
+        assert!(accepts(None, "identity"));
+        assert!(accepts(Some("gzip"), "identity"));
+        assert!(accepts(Some("*;q=0, identity"), "identity"));
+        assert!(!accepts(Some("gzip, identity;q=0"), "identity"));
+        assert!(!accepts(Some("gzip, *;q=0"), "identity"));
+    }
+}
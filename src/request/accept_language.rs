@@ -0,0 +1,143 @@
+//! Language negotiation using the `Accept-Language` header.
+
+use super::Request;
+use super::negotiate::parse_quality;
+
+impl<'a> Request<'a> {
+    /// Choose the `available` language the client prefers, according to its
+    /// `Accept-Language` header.
+    ///
+    /// Language ranges match tags they are a prefix of, so `en` matches
+    /// `en-US` as well as `en`, and `*` matches anything. Each available
+    /// language gets the quality (`q=`) of the most specific range matching
+    /// it, and a quality of `0` means "not acceptable". Failing that, a range
+    /// for a more specific language matches a more general available one,
+    /// so `en-GB` can be served `en`, but only if nothing matches directly at
+    /// the same quality. Tags are compared without regard to case, and ties
+    /// go to whichever language comes first in `available`.
+    ///
+    /// Returns `None` if none of the available languages are acceptable, so
+    /// the caller can fall back to a default. A request without a usable
+    /// `Accept-Language` header gets the first available language.
+    ///
+    /// The response then depends on `Accept-Language`, so should be marked
+    /// with `Response::vary_on(&["Accept-Language"])`.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use iron::{method, test, Headers, Url};
+    /// # use iron::request::Body;
+    /// # let mut headers = Headers::new();
+    /// # headers.set_raw("Accept-Language", vec![b"fr-CH, fr;q=0.9, en;q=0.8".to_vec()]);
+    /// # let mut reader = io::empty();
+    /// # let req = test::request(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+    /// #                         Some(headers), Body::from_reader(&mut reader, Some(0), false));
+    /// // Accept-Language: fr-CH, fr;q=0.9, en;q=0.8
+    /// assert_eq!(req.preferred_language(&["en-US", "fr-FR"]), Some("fr-FR".to_owned()));
+    /// assert_eq!(req.preferred_language(&["de", "en-US"]), Some("en-US".to_owned()));
+    /// assert_eq!(req.preferred_language(&["de"]), None);
+    /// ```
+    pub fn preferred_language(&self, available: &[&str]) -> Option<String> {
+        let ranges = self.headers.get_raw("Accept-Language").map(|lines| {
+            lines.iter()
+                .filter_map(|line| ::std::str::from_utf8(line).ok())
+                .flat_map(|line| line.split(','))
+                .filter_map(parse_language_range)
+                .collect::<Vec<_>>()
+        }).unwrap_or_else(Vec::new);
+
+        if ranges.is_empty() { return available.first().map(|&tag| tag.to_owned()) }
+
+        let mut best: Option<(&str, (u16, bool))> = None;
+        for &tag in available {
+            let rank = match rank_of(&tag.to_ascii_lowercase(), &ranges) {
+                Some(rank) if rank.0 > 0 => rank,
+                _ => continue
+            };
+
+            if best.map_or(true, |(_, best_rank)| rank > best_rank) {
+                best = Some((tag, rank));
+            }
+        }
+
+        best.map(|(tag, _)| tag.to_owned())
+    }
+}
+
+// A lowercased language range from an `Accept-Language` header, with its
+// quality in thousandths.
+type LanguageRange = (String, u16);
+
+// Parse one entry of an `Accept-Language` header, such as `en-GB;q=0.8`.
+fn parse_language_range(entry: &str) -> Option<LanguageRange> {
+    let mut parts = entry.split(';').map(|part| part.trim());
+
+    let range = parts.next().unwrap().to_ascii_lowercase();
+    let valid = range == "*" || range.split('-').all(|subtag| {
+        !subtag.is_empty() && subtag.len() <= 8 && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+    if !valid { return None }
+
+    let quality = match parts.find(|part| part.starts_with("q=") || part.starts_with("Q=")) {
+        Some(q) => match parse_quality(&q[2..]) {
+            Some(quality) => quality,
+            None => return None
+        },
+        None => 1000
+    };
+
+    Some((range, quality))
+}
+
+// The quality with which `tag` is accepted, and whether a range matched it
+// directly rather than through a more specific language.
+fn rank_of(tag: &str, ranges: &[LanguageRange]) -> Option<(u16, bool)> {
+    let direct = ranges.iter()
+        .filter(|&&(ref range, _)| range == "*" || is_prefix(range, tag))
+        .max_by_key(|&&(ref range, _)| if range == "*" { 0 } else { range.len() + 1 })
+        .map(|&(_, quality)| (quality, true));
+
+    // A direct match, even one refusing the tag, decides.
+    if direct.is_some() { return direct }
+
+    ranges.iter()
+        .filter(|&&(ref range, _)| is_prefix(tag, range))
+        .max_by_key(|&&(_, quality)| quality)
+        .map(|&(_, quality)| (quality, false))
+}
+
+// Whether the language `prefix` covers `tag`, such as `en` for `en-US`.
+fn is_prefix(prefix: &str, tag: &str) -> bool {
+    tag == prefix || (tag.starts_with(prefix) && tag[prefix.len()..].starts_with('-'))
+}
+
+#[cfg(test)]
+mod test {
+    use {method, test, Url};
+
+    fn preferred(accept: Option<&str>, available: &[&str]) -> Option<String> {
+        let headers: Vec<_> = accept.into_iter().map(|value| ("Accept-Language", value)).collect();
+        test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                   &headers, |req| req.preferred_language(available))
+    }
+
+    #[test]
+    fn test_preferred_language() {
+        assert_eq!(preferred(Some("en;q=0.5, de"), &["en-US", "de-AT"]), Some("de-AT".to_owned()));
+        assert_eq!(preferred(Some("EN-us"), &["en-GB", "en-US"]), Some("en-US".to_owned()));
+        assert_eq!(preferred(Some("en-GB"), &["de", "en"]), Some("en".to_owned()));
+        assert_eq!(preferred(Some("en-GB, en-US"), &["en", "en-US"]), Some("en-US".to_owned()));
+        assert_eq!(preferred(Some("en-GB, en-US;q=0.9"), &["en", "en-US"]), Some("en".to_owned()));
+        assert_eq!(preferred(Some("*, de;q=0"), &["de", "fr"]), Some("fr".to_owned()));
+        assert_eq!(preferred(Some("ja"), &["en", "fr"]), None);
+        assert_eq!(preferred(Some("eng"), &["en"]), None);
+    }
+
+    #[test]
+    fn test_missing_or_malformed_accept_language() {
+        assert_eq!(preferred(None, &["en", "fr"]), Some("en".to_owned()));
+        assert_eq!(preferred(Some("en_US;q=x"), &["fr"]), Some("fr".to_owned()));
+        assert_eq!(preferred(Some("en;q=1.0000, de;q=0.5"), &["en", "de"]), Some("de".to_owned()));
+        assert_eq!(preferred(None, &[]), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Reading credentials from the `Authorization` header.
+
+use serialize::base64::FromBase64;
+
+use super::Request;
+
+impl<'a> Request<'a> {
+    /// The username and password sent with `Authorization: Basic`, if any.
+    ///
+    /// The credentials are split at the first colon, so passwords may contain
+    /// colons but usernames can't. Returns `None` if the header is missing,
+    /// uses another scheme, or isn't valid base64 encoded UTF-8 with a colon.
+    pub fn basic_auth(&self) -> Option<(String, String)> {
+        let credentials = match self.authorization() {
+            Some((scheme, credentials)) if scheme.eq_ignore_ascii_case("basic") => credentials,
+            _ => return None
+        };
+
+        let decoded = match credentials.from_base64().ok().and_then(|d| String::from_utf8(d).ok()) {
+            Some(decoded) => decoded,
+            None => return None
+        };
+
+        let mut parts = decoded.splitn(2, ':');
+        let username = parts.next().unwrap().to_owned();
+        parts.next().map(|password| (username, password.to_owned()))
+    }
+
+    /// The token sent with `Authorization: Bearer`, if any.
+    ///
+    /// The token is returned as sent, without surrounding whitespace; checking
+    /// it is up to the application.
+    pub fn bearer_token(&self) -> Option<&str> {
+        match self.authorization() {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") && !token.is_empty() => {
+                Some(token)
+            },
+            _ => None
+        }
+    }
+
+    // The scheme and credentials of a single `Authorization` header.
+    fn authorization(&self) -> Option<(&str, &str)> {
+        let header = match self.headers.get_raw("Authorization") {
+            Some(lines) if lines.len() == 1 => match ::std::str::from_utf8(&lines[0]) {
+                Ok(header) => header.trim(),
+                Err(_) => return None
+            },
+            _ => return None
+        };
+
+        let mut parts = header.splitn(2, ' ');
+        let scheme = parts.next().unwrap();
+        parts.next().map(|credentials| (scheme, credentials.trim()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use request::Request;
+    use {method, test, Url};
+
+    fn with_authorization<F, T>(header: &str, f: F) -> T where F: FnOnce(&Request) -> T {
+        test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                   &[("Authorization", header)], |req| f(req))
+    }
+
+    fn basic_auth(header: &str) -> Option<(String, String)> {
+        with_authorization(header, |req| req.basic_auth())
+    }
+
+    fn bearer_token(header: &str) -> Option<String> {
+        with_authorization(header, |req| req.bearer_token().map(|t| t.to_owned()))
+    }
+
+    #[test]
+    fn test_basic_auth() {
+        // aladdin:open:sesame
+        assert_eq!(basic_auth("Basic YWxhZGRpbjpvcGVuOnNlc2FtZQ=="),
+                   Some(("aladdin".to_owned(), "open:sesame".to_owned())));
+        // aladdin:
+        assert_eq!(basic_auth("basic YWxhZGRpbjo="), Some(("aladdin".to_owned(), "".to_owned())));
+        // aladdin
+        assert_eq!(basic_auth("Basic YWxhZGRpbg=="), None);
+        assert_eq!(basic_auth("Basic !!!"), None);
+        assert_eq!(basic_auth("Bearer YWxhZGRpbjpvcGVuOnNlc2FtZQ=="), None);
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc.def.ghi"), Some("abc.def.ghi".to_owned()));
+        assert_eq!(bearer_token("  bearer   abc.def.ghi  "), Some("abc.def.ghi".to_owned()));
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("Basic YWxhZGRpbjpvcGVuOnNlc2FtZQ=="), None);
+    }
+}
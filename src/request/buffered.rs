@@ -0,0 +1,86 @@
+//! Buffering request bodies so they can be read more than once.
+
+use std::io::{self, Read};
+
+use typemap::Key;
+
+use super::{Request, LimitExceeded};
+
+// Holds a body read by `Request::buffer_body`.
+struct BufferedBody;
+
+impl Key for BufferedBody { type Value = Vec<u8>; }
+
+impl<'a> Request<'a> {
+    /// Read the whole body into memory, so it can be read more than once.
+    ///
+    /// The body is stored in `extensions`, and later calls return it without
+    /// reading again. Once buffered, `get_body_bytes`, `get_body_string` and
+    /// the body parsers which use them read from the buffer, whatever state
+    /// `body` itself is in.
+    ///
+    /// Fails with an `io::Error` of kind `ErrorKind::Other` wrapping a
+    /// `LimitExceeded` if the body is longer than `max` bytes. Any limit set
+    /// on `body` still applies too. If buffering fails, the bytes read so far
+    /// are put back, so `body` can still be read in full.
+    pub fn buffer_body(&mut self, max: u64) -> io::Result<&[u8]> {
+        if !self.extensions.contains::<BufferedBody>() {
+            let mut bytes = Vec::new();
+            let read = (&mut self.body).take(max.saturating_add(1)).read_to_end(&mut bytes);
+
+            if read.is_err() || bytes.len() as u64 > max {
+                self.body.unread(bytes);
+                try!(read);
+                return Err(io::Error::new(io::ErrorKind::Other, LimitExceeded { limit: max }));
+            }
+
+            self.extensions.insert::<BufferedBody>(bytes);
+        }
+
+        Ok(self.buffered_body().unwrap())
+    }
+
+    /// The body read by `buffer_body`, if it has been buffered.
+    pub fn buffered_body(&self) -> Option<&[u8]> {
+        self.extensions.get::<BufferedBody>().map(|bytes| &**bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use request::{Body, LimitExceeded};
+    use {method, test, Headers, Url};
+
+    #[test]
+    fn test_buffer_body_twice() {
+        let mut headers = Headers::new();
+        headers.set_raw("Content-Type", vec![b"application/x-www-form-urlencoded".to_vec()]);
+
+        let mut reader = "a=1&b=2".as_bytes();
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    Some(headers), Body::from_reader(&mut reader, Some(7), false));
+
+        assert_eq!(req.buffer_body(7).unwrap(), b"a=1&b=2");
+        assert_eq!(req.buffer_body(7).unwrap(), b"a=1&b=2");
+        assert_eq!(req.form_body().unwrap().get("b"), Some("2"));
+        assert_eq!(req.get_body_string().unwrap(), "a=1&b=2");
+    }
+
+    #[test]
+    fn test_buffer_body_too_large() {
+        let mut reader = "hello world".as_bytes();
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    None, Body::from_reader(&mut reader, Some(11), false));
+
+        let err = req.buffer_body(5).unwrap_err();
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()),
+                   Some(&LimitExceeded { limit: 5 }));
+        assert!(req.buffered_body().is_none());
+
+        let mut body = String::new();
+        req.body.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "hello world");
+    }
+}
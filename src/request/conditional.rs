@@ -0,0 +1,193 @@
+//! Evaluating conditional `GET`s against a resource's validators.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use headers::{EntityTag, HttpDate, IfModifiedSince, IfNoneMatch};
+use method;
+
+use super::Request;
+
+impl<'a> Request<'a> {
+    /// Whether the client's cached copy of the resource is still fresh, so a
+    /// `304 Not Modified` without a body can be sent instead.
+    ///
+    /// `etag` is the resource's current `ETag`, as it would be sent in the
+    /// header, such as `"v1"` or `W/"v1"`, and `last_modified` is when it last
+    /// changed.
+    ///
+    /// Following RFC 7232, `If-None-Match` is used if it is present: `*`
+    /// matches any existing resource, and tags are compared weakly. Otherwise
+    /// `If-Modified-Since` is compared with `last_modified`, to the second.
+    /// Only `GET` and `HEAD` requests can be fresh.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// fn handler(req: &mut Request) -> IronResult<Response> {
+    ///     let etag = "\"v1\"";
+    ///     if req.is_fresh(Some(etag), None) {
+    ///         return Ok(Response::with(status::NotModified));
+    ///     }
+    ///
+    ///     let mut res = Response::with((status::Ok, "Version 1"));
+    ///     res.headers.set_raw("ETag", vec![etag.as_bytes().to_vec()]);
+    ///     Ok(res)
+    /// }
+    /// ```
+    pub fn is_fresh(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        match self.method {
+            method::Get | method::Head => (),
+            _ => return false
+        }
+
+        if self.headers.get_raw("If-None-Match").is_some() {
+            let etag = etag.and_then(|etag| etag.trim().parse::<EntityTag>().ok());
+            return match (self.headers.get::<IfNoneMatch>(), etag) {
+                (Some(&IfNoneMatch::Any), Some(_)) => true,
+                (Some(&IfNoneMatch::Items(ref tags)), Some(ref etag)) => {
+                    tags.iter().any(|tag| tag.weak_eq(etag))
+                },
+                _ => false
+            };
+        }
+
+        match (self.headers.get::<IfModifiedSince>(), last_modified) {
+            (Some(&IfModifiedSince(HttpDate(since))), Some(modified)) => {
+                unix_seconds(modified) <= since.to_timespec().sec
+            },
+            _ => false
+        }
+    }
+
+    /// Whether a range request may be answered with only the parts asked for,
+    /// according to its `If-Range` header.
+    ///
+    /// `If-Range` lets a client resume a download only if the resource hasn't
+    /// changed; if it has, the whole resource should be sent with `200 OK`
+    /// instead of the ranges from `range`. This is `true` when there is no
+    /// `If-Range` header.
+    ///
+    /// Following RFC 7233, an entity tag in `If-Range` must strongly match
+    /// `etag`, so weak tags on either side never match. A date must be exactly
+    /// `last_modified`, to the second. An invalid `If-Range` never matches.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// fn download(req: &mut Request) -> IronResult<Response> {
+    ///     let data = b"0123456789";
+    ///     let etag = "\"v1\"";
+    ///
+    ///     if req.if_range_matches(Some(etag), None) {
+    ///         if let Some(Ok(ranges)) = req.range(data.len() as u64) {
+    ///             if ranges.len() == 1 {
+    ///                 let (first, last) = ranges[0];
+    ///                 // Send a `206 Partial Content` with a `Content-Range`...
+    /// #               let _ = (first, last);
+    ///             }
+    ///         }
+    ///     }
+    ///
+    ///     Ok(Response::with((status::Ok, &data[..])))
+    /// }
+    /// ```
+    pub fn if_range_matches(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        let value = match self.headers.get_raw("If-Range") {
+            Some(lines) if lines.len() == 1 => String::from_utf8_lossy(&lines[0]).into_owned(),
+            Some(_) => return false,
+            None => return true
+        };
+        let value = value.trim();
+
+        // An entity tag always starts with a quote or `W/`, which no date does.
+        if value.starts_with('"') || value.starts_with("W/") {
+            let etag = etag.and_then(|etag| etag.trim().parse::<EntityTag>().ok());
+            return match (value.parse::<EntityTag>(), etag) {
+                (Ok(tag), Some(etag)) => tag.strong_eq(&etag),
+                _ => false
+            };
+        }
+
+        match (value.parse::<HttpDate>(), last_modified) {
+            (Ok(HttpDate(date)), Some(modified)) => {
+                unix_seconds(modified) == date.to_timespec().sec
+            },
+            _ => false
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_secs() as i64,
+        Err(before) => {
+            let before = before.duration();
+            -(before.as_secs() as i64) - if before.subsec_nanos() > 0 { 1 } else { 0 }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use method::Method;
+    use {method, test, Url};
+
+    fn is_fresh(method: Method, headers: &[(&str, &str)], etag: Option<&str>,
+                last_modified: Option<SystemTime>) -> bool {
+        test::request_with_headers(method, Url::parse("http://localhost:3000/").unwrap(), headers,
+                                   |req| req.is_fresh(etag, last_modified))
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let inm = |value| vec![("If-None-Match", value)];
+        assert!(is_fresh(method::Get, &inm("\"a\", W/\"b\""), Some("\"b\""), None));
+        assert!(is_fresh(method::Head, &inm("\"a\""), Some("W/\"a\""), None));
+        assert!(!is_fresh(method::Get, &inm("\"a\""), Some("\"b\""), None));
+        assert!(is_fresh(method::Get, &inm("*"), Some("\"a\""), None));
+        assert!(!is_fresh(method::Get, &inm("*"), None, None));
+        assert!(!is_fresh(method::Post, &inm("\"a\""), Some("\"a\""), None));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        let since = UNIX_EPOCH + Duration::from_secs(784111777);
+        let ims = vec![("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")];
+
+        assert!(is_fresh(method::Get, &ims, None, Some(since + Duration::from_millis(500))));
+        assert!(!is_fresh(method::Get, &ims, None, Some(since + Duration::from_secs(1))));
+        assert!(!is_fresh(method::Get, &[], None, Some(since)));
+
+        // If-None-Match takes precedence.
+        let both = vec![ims[0], ("If-None-Match", "\"a\"")];
+        assert!(!is_fresh(method::Get, &both, Some("\"b\""), Some(since)));
+    }
+
+    fn if_range_matches(if_range: Option<&str>, etag: Option<&str>,
+                        last_modified: Option<SystemTime>) -> bool {
+        let headers: Vec<_> = if_range.into_iter().map(|if_range| ("If-Range", if_range)).collect();
+        test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                   &headers, |req| req.if_range_matches(etag, last_modified))
+    }
+
+    #[test]
+    fn test_if_range_matches() {
+        let modified = UNIX_EPOCH + Duration::from_secs(784111777);
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+        assert!(if_range_matches(None, None, None));
+        assert!(if_range_matches(Some("\"a\""), Some("\"a\""), None));
+        assert!(!if_range_matches(Some("\"a\""), Some("\"b\""), None));
+        assert!(!if_range_matches(Some("W/\"a\""), Some("W/\"a\""), None));
+        assert!(!if_range_matches(Some("\"a\""), Some("W/\"a\""), None));
+        assert!(!if_range_matches(Some("\"a\""), None, Some(modified)));
+
+        assert!(if_range_matches(Some(date), None, Some(modified + Duration::from_millis(500))));
+        assert!(!if_range_matches(Some(date), None, Some(modified + Duration::from_secs(1))));
+        assert!(!if_range_matches(Some(date), Some("\"a\""), None));
+        assert!(!if_range_matches(Some("yesterday"), None, Some(modified)));
+    }
+}
@@ -0,0 +1,81 @@
+//! Inspecting the `Content-Type` of requests.
+
+use mime::{Mime, TopLevel, SubLevel};
+
+use super::Request;
+use headers;
+
+impl<'a> Request<'a> {
+    /// The parsed `Content-Type` of the request body, if it has one.
+    ///
+    /// Parameters such as `charset` are kept in the returned `Mime`.
+    pub fn content_type(&self) -> Option<Mime> {
+        self.headers.get::<headers::ContentType>().map(|content_type| content_type.0.clone())
+    }
+
+    /// Is the body JSON?
+    ///
+    /// This accepts `application/json` as well as structured syntax types
+    /// such as `application/ld+json`.
+    pub fn is_json(&self) -> bool {
+        self.content_type().map_or(false, |mime| is_json(&mime))
+    }
+
+    /// Is the body an `application/x-www-form-urlencoded` form?
+    pub fn is_form(&self) -> bool {
+        self.content_type().map_or(false, |mime| is_form(&mime))
+    }
+}
+
+fn is_json(mime: &Mime) -> bool {
+    match *mime {
+        Mime(TopLevel::Application, SubLevel::Json, _) => true,
+        Mime(TopLevel::Application, SubLevel::Ext(ref sub), _) => sub.ends_with("+json"),
+        _ => false
+    }
+}
+
+fn is_form(mime: &Mime) -> bool {
+    match *mime {
+        Mime(TopLevel::Application, SubLevel::WwwFormUrlEncoded, _) => true,
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mime::{Attr, Value};
+
+    use {method, test, Url};
+
+    fn check<F: FnOnce(&::Request)>(content_type: Option<&str>, f: F) {
+        let headers: Vec<_> = content_type.into_iter()
+            .map(|value| ("Content-Type", value)).collect();
+        test::request_with_headers(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                   &headers, |req| f(req))
+    }
+
+    #[test]
+    fn test_content_type_keeps_charset() {
+        check(Some("text/plain; charset=utf-8"), |req| {
+            let mime = req.content_type().unwrap();
+            assert_eq!(mime.get_param(Attr::Charset), Some(&Value::Utf8));
+        });
+
+        check(None, |req| assert_eq!(req.content_type(), None));
+    }
+
+    #[test]
+    fn test_is_json() {
+        check(Some("application/json; charset=utf-8"), |req| assert!(req.is_json()));
+        check(Some("application/ld+json"), |req| assert!(req.is_json()));
+        check(Some("text/json"), |req| assert!(!req.is_json()));
+        check(None, |req| assert!(!req.is_json()));
+    }
+
+    #[test]
+    fn test_is_form() {
+        check(Some("application/x-www-form-urlencoded"), |req| assert!(req.is_form()));
+        check(Some("multipart/form-data; boundary=x"), |req| assert!(!req.is_form()));
+    }
+}
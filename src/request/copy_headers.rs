@@ -0,0 +1,85 @@
+//! Copying headers off a request, such as onto a proxied one.
+
+use Headers;
+
+use super::Request;
+
+// Headers which only describe one connection, so are never forwarded.
+const HOP_BY_HOP: &'static [&'static str] = &[
+    "Connection", "Keep-Alive", "Proxy-Connection", "TE", "Trailer", "Transfer-Encoding", "Upgrade"
+];
+
+impl<'a> Request<'a> {
+    /// Copy the headers named in `names` into a new `Headers`, such as for a
+    /// request made on the client's behalf. Multiple values of a header are
+    /// all kept, in order, and names are compared without regard to case.
+    ///
+    /// With `skip_hop_by_hop`, headers which only apply to the connection
+    /// to Iron are left out even if they are named: `Connection`,
+    /// `Keep-Alive`, `Proxy-Connection`, `TE`, `Trailer`,
+    /// `Transfer-Encoding`, `Upgrade`, and any listed in `Connection`.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use iron::{method, test, Headers, Url};
+    /// # use iron::request::Body;
+    /// # let mut headers = Headers::new();
+    /// # headers.set_raw("Accept", vec![b"text/html".to_vec(), b"text/plain".to_vec()]);
+    /// # headers.set_raw("Connection", vec![b"close".to_vec()]);
+    /// # let mut reader = io::empty();
+    /// # let req = test::request(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+    /// #                         Some(headers), Body::from_reader(&mut reader, Some(0), false));
+    /// let copied = req.copy_headers(&["accept", "connection", "cookie"], true);
+    /// assert_eq!(copied.get_raw("Accept").unwrap(),
+    ///            &[b"text/html".to_vec(), b"text/plain".to_vec()][..]);
+    /// assert_eq!(copied.len(), 1);
+    /// ```
+    pub fn copy_headers(&self, names: &[&str], skip_hop_by_hop: bool) -> Headers {
+        let connection_options = self.headers.get_raw("Connection").unwrap_or(&[]).iter()
+            .flat_map(|line| line.split(|&b| b == b','))
+            .map(|option| String::from_utf8_lossy(option).trim().to_owned())
+            .collect::<Vec<_>>();
+
+        let is_hop_by_hop = |name: &str| {
+            HOP_BY_HOP.iter().any(|hop| hop.eq_ignore_ascii_case(name)) ||
+                connection_options.iter().any(|option| option.eq_ignore_ascii_case(name))
+        };
+
+        let mut headers = Headers::new();
+        for &name in names {
+            if skip_hop_by_hop && is_hop_by_hop(name) { continue }
+            if let Some(values) = self.headers.get_raw(name) {
+                headers.set_raw(name.to_owned(), values.to_vec());
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {method, test, Url};
+
+    #[test]
+    fn test_copy_headers() {
+        let headers = [
+            ("Connection", "keep-alive, X-Secret"),
+            ("X-Secret", "hunter2"),
+            ("Transfer-Encoding", "chunked"),
+            ("X-Request-Id", "a"),
+            ("X-Request-Id", "b")
+        ];
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, &headers, |req| {
+            let names = ["Connection", "x-secret", "Transfer-Encoding", "X-Request-Id", "Missing"];
+            let copied = req.copy_headers(&names, true);
+            assert_eq!(copied.len(), 1);
+            assert_eq!(copied.get_raw("X-Request-Id").unwrap(),
+                       &[b"a".to_vec(), b"b".to_vec()][..]);
+
+            let copied = req.copy_headers(&names, false);
+            assert_eq!(copied.len(), 4);
+            assert_eq!(copied.get_raw("X-Secret").unwrap(), &[b"hunter2".to_vec()][..]);
+        });
+    }
+}
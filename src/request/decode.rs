@@ -0,0 +1,161 @@
+//! Decompressing request bodies sent with a `Content-Encoding`.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{self, Read};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+
+use super::{Request, LimitExceeded};
+
+/// The error wrapped by the `io::Error` returned by `Request::decoded_body`
+/// when the request has a `Content-Encoding` Iron can't decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedEncoding {
+    /// The encoding which can't be decoded, such as `br`.
+    pub encoding: String
+}
+
+impl fmt::Display for UnsupportedEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unsupported Content-Encoding: {}", self.encoding)
+    }
+}
+
+impl StdError for UnsupportedEncoding {
+    fn description(&self) -> &str {
+        "Unsupported Content-Encoding"
+    }
+}
+
+impl<'a> Request<'a> {
+    /// A reader of the body with any `Content-Encoding` undone.
+    ///
+    /// `gzip` (or `x-gzip`) and `deflate` bodies are decompressed as they are
+    /// read, and `identity` bodies or those without a `Content-Encoding` are
+    /// passed through. Several encodings are undone in reverse order.
+    ///
+    /// If any encoding is unknown, the body is left unread and an `io::Error`
+    /// of kind `InvalidInput` wrapping an `UnsupportedEncoding` is returned.
+    ///
+    /// A limit set on `body` applies to the decompressed bytes too, so a
+    /// small compressed body can't expand without bound. Reading more fails
+    /// with an `io::Error` of kind `ErrorKind::Other` wrapping a
+    /// `LimitExceeded`.
+    pub fn decoded_body<'r>(&'r mut self) -> io::Result<Box<Read + 'r>> {
+        let mut encodings = Vec::new();
+        for line in self.headers.get_raw("Content-Encoding").unwrap_or(&[]) {
+            for encoding in String::from_utf8_lossy(line).split(',') {
+                let encoding = encoding.trim().to_ascii_lowercase();
+                match &*encoding {
+                    "" | "identity" => (),
+                    "gzip" | "x-gzip" | "deflate" => encodings.push(encoding),
+                    _ => {
+                        let err = UnsupportedEncoding { encoding: encoding };
+                        return Err(io::Error::new(io::ErrorKind::InvalidInput, err));
+                    }
+                }
+            }
+        }
+
+        let limit = self.body.limit();
+        let mut reader: Box<Read + 'r> = Box::new(&mut self.body);
+        if encodings.is_empty() { return Ok(reader) }
+
+        for encoding in encodings.iter().rev() {
+            reader = match &**encoding {
+                "deflate" => Box::new(ZlibDecoder::new(reader)),
+                _ => Box::new(GzDecoder::new(reader))
+            };
+        }
+
+        Ok(match limit {
+            Some(limit) => Box::new(Limited { reader: reader, limit: limit, read: 0 }),
+            None => reader
+        })
+    }
+}
+
+// Caps the number of bytes read from `reader`, like `Body`'s own limit.
+struct Limited<R> {
+    reader: R,
+    limit: u64,
+    read: u64
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() { return Ok(0) }
+
+        if self.read >= self.limit {
+            // Tell a body ending exactly at the limit apart from a longer one.
+            return match try!(self.reader.read(&mut [0])) {
+                0 => Ok(0),
+                _ => Err(io::Error::new(io::ErrorKind::Other, LimitExceeded { limit: self.limit }))
+            };
+        }
+
+        let remaining = (self.limit - self.read).min(buf.len() as u64) as usize;
+        let read = try!(self.reader.read(&mut buf[..remaining]));
+        self.read += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read, Write};
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use request::{Body, LimitExceeded};
+    use {method, test, Headers, Url};
+
+    use super::UnsupportedEncoding;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decode(encoding: &str, body: &[u8], limit: Option<u64>) -> io::Result<Vec<u8>> {
+        let mut headers = Headers::new();
+        headers.set_raw("Content-Encoding", vec![encoding.as_bytes().to_vec()]);
+
+        let mut reader = body;
+        let mut body = Body::from_reader(&mut reader, Some(body.len() as u64), false);
+        if let Some(limit) = limit { body.set_limit(limit) }
+
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    Some(headers), body);
+        let mut decoded = Vec::new();
+        try!(try!(req.decoded_body()).read_to_end(&mut decoded));
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_decode_gzip() {
+        assert_eq!(decode("gzip", &gzip(b"hello"), None).unwrap(), b"hello");
+        assert_eq!(decode("identity", b"hello", None).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_decompressed_size_is_limited() {
+        let bomb = gzip(&[0; 100000]);
+        assert!(bomb.len() < 1000);
+
+        let err = decode("gzip", &bomb, Some(1000)).unwrap_err();
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()),
+                   Some(&LimitExceeded { limit: 1000 }));
+    }
+
+    #[test]
+    fn test_unsupported_encoding() {
+        let err = decode("br", b"hello", None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<UnsupportedEncoding>()),
+                   Some(&UnsupportedEncoding { encoding: "br".to_owned() }));
+    }
+}
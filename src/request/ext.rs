@@ -0,0 +1,80 @@
+//! Storing values in `extensions` by their type alone, or in a store of
+//! their own for each middleware.
+
+use std::any::Any;
+use std::marker::PhantomData;
+
+use typemap::{Key, TypeMap};
+
+use super::Request;
+
+// The `extensions` key for a value stored with `Request::set_ext`.
+struct Ext<T>(PhantomData<T>);
+
+impl<T: Any> Key for Ext<T> { type Value = T; }
+
+// The `extensions` key for the store returned by `Request::scoped::<M>`.
+struct Scoped<M>(PhantomData<M>);
+
+impl<M: Any> Key for Scoped<M> { type Value = TypeMap; }
+
+impl<'a> Request<'a> {
+    /// Store `value` in `extensions`, keyed by its type, replacing any value
+    /// of the same type.
+    ///
+    /// Unlike using `extensions` directly, this doesn't need a `typemap::Key`.
+    /// Since the type is the key, wrap values in a type of your own, such as
+    /// `struct UserId(u64)`, so they don't clash with other middleware's.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use iron::{method, test, Url};
+    /// # use iron::request::Body;
+    /// # let mut reader = io::empty();
+    /// # let mut req = test::request(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+    /// #                             None, Body::from_reader(&mut reader, Some(0), false));
+    /// struct UserId(u64);
+    ///
+    /// req.set_ext(UserId(7));
+    /// assert_eq!(req.get_ext::<UserId>().map(|id| id.0), Some(7));
+    /// ```
+    pub fn set_ext<T: 'static + Send>(&mut self, value: T) {
+        self.extensions.insert::<Ext<T>>(value);
+    }
+
+    /// The value of type `T` stored with `set_ext`, if any.
+    pub fn get_ext<T: 'static>(&self) -> Option<&T> {
+        self.extensions.get::<Ext<T>>()
+    }
+
+    /// A `TypeMap` of this request kept apart for the middleware `M`.
+    ///
+    /// Each type `M` gets its own store, created empty the first time it is
+    /// asked for, so middleware can keep values for the rest of the request
+    /// without clashing with other middleware using the same keys or value
+    /// types. `M` is usually the middleware's own type, or a private marker
+    /// type. Use `extensions` for values meant to be shared.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use iron::{method, test, Url};
+    /// # use iron::request::Body;
+    /// # use iron::typemap::Key;
+    /// # let mut reader = io::empty();
+    /// # let mut req = test::request(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+    /// #                             None, Body::from_reader(&mut reader, Some(0), false));
+    /// struct Timer;
+    /// struct Logger;
+    ///
+    /// struct Started;
+    /// impl Key for Started { type Value = u64; }
+    ///
+    /// req.scoped::<Timer>().insert::<Started>(10);
+    /// req.scoped::<Logger>().insert::<Started>(20);
+    /// assert_eq!(req.scoped::<Timer>().get::<Started>(), Some(&10));
+    /// assert_eq!(req.extensions.get::<Started>(), None);
+    /// ```
+    pub fn scoped<M: Any>(&mut self) -> &mut TypeMap {
+        self.extensions.entry::<Scoped<M>>().or_insert_with(TypeMap::new)
+    }
+}
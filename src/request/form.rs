@@ -0,0 +1,104 @@
+//! Parsing of `application/x-www-form-urlencoded` request bodies.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use mime::Mime;
+
+use super::{Request, QueryMap};
+
+/// Decoded form fields from an `application/x-www-form-urlencoded` body.
+///
+/// This is the same multimap used for query strings, so repeated fields are
+/// kept in the order they were sent.
+pub type FormMap = QueryMap;
+
+/// The error wrapped by the `io::Error` returned by `Request::form_body` when
+/// the request does not have a form `Content-Type`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnexpectedContentType {
+    /// The `Content-Type` the request was sent with, if any.
+    pub found: Option<Mime>
+}
+
+impl fmt::Display for UnexpectedContentType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.found {
+            Some(ref mime) => {
+                write!(f, "Expected a form body, but the request has Content-Type {}", mime)
+            },
+            None => f.write_str("Expected a form body, but the request has no Content-Type")
+        }
+    }
+}
+
+impl StdError for UnexpectedContentType {
+    fn description(&self) -> &str {
+        "Request body is not application/x-www-form-urlencoded"
+    }
+}
+
+impl<'a> Request<'a> {
+    /// Read and parse an `application/x-www-form-urlencoded` body.
+    ///
+    /// If the request has a different `Content-Type`, the body is left unread
+    /// and an `io::Error` of kind `InvalidInput` wrapping an
+    /// `UnexpectedContentType` is returned. Any limit set on the body is
+    /// respected.
+    pub fn form_body(&mut self) -> io::Result<FormMap> {
+        if !self.is_form() {
+            let err = UnexpectedContentType { found: self.content_type() };
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, err));
+        }
+
+        let bytes = try!(self.get_body_bytes());
+        Ok(FormMap::parse_bytes(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use request::Body;
+    use {method, headers, test, Headers, Url};
+
+    use super::{UnexpectedContentType, FormMap};
+
+    fn form_body(content_type: Option<&str>, body: &str) -> io::Result<FormMap> {
+        let mut headers = Headers::new();
+        if let Some(value) = content_type {
+            headers.set_raw("Content-Type", vec![value.as_bytes().to_vec()]);
+        }
+
+        let mut reader = body.as_bytes();
+        let len = Some(body.len() as u64);
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    Some(headers), Body::from_reader(&mut reader, len, false));
+        req.form_body()
+    }
+
+    #[test]
+    fn test_form_body() {
+        let form = form_body(Some("application/x-www-form-urlencoded; charset=utf-8"),
+                             "name=Jane+Doe&pet=cat&pet=dog%21").unwrap();
+        assert_eq!(form.get("name"), Some("Jane Doe"));
+        assert_eq!(form.get_all("pet"), &["cat".to_owned(), "dog!".to_owned()]);
+    }
+
+    #[test]
+    fn test_unexpected_content_type() {
+        let err = form_body(Some("application/json"), "{}").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let found = err.get_ref().and_then(|e| e.downcast_ref::<UnexpectedContentType>());
+        assert_eq!(found, Some(&UnexpectedContentType {
+            found: Some(headers::ContentType::json().0)
+        }));
+
+        let err = form_body(None, "a=1").unwrap_err();
+        let found = err.get_ref().and_then(|e| e.downcast_ref::<UnexpectedContentType>());
+        assert_eq!(found, Some(&UnexpectedContentType { found: None }));
+    }
+}
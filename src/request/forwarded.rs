@@ -0,0 +1,655 @@
+//! Helpers for requests which arrive through proxies.
+
+use std::borrow::Cow;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use url::Host;
+
+use super::Request;
+
+/// A range of IP addresses, written in CIDR notation such as `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8
+}
+
+impl IpNetwork {
+    /// Create a network from an address and a prefix length in bits.
+    ///
+    /// Fails if the prefix is longer than the address.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<IpNetwork, String> {
+        if prefix > max_prefix(addr) {
+            return Err(format!("Invalid prefix length for {}: {}", addr, prefix));
+        }
+
+        Ok(IpNetwork { addr: addr, prefix: prefix })
+    }
+
+    /// Does this network contain the given address?
+    ///
+    /// IPv4 networks never contain IPv6 addresses and vice versa.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let (network, ip) = match (self.addr, ip) {
+            (IpAddr::V4(n), IpAddr::V4(i)) => (n.octets().to_vec(), i.octets().to_vec()),
+            (IpAddr::V6(n), IpAddr::V6(i)) => (n.octets().to_vec(), i.octets().to_vec()),
+            _ => return false
+        };
+
+        let whole = (self.prefix / 8) as usize;
+        let rest = self.prefix % 8;
+
+        if network[..whole] != ip[..whole] { return false }
+        if rest == 0 { return true }
+
+        let mask = !0u8 << (8 - rest);
+        network[whole] & mask == ip[whole] & mask
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    /// Parse a network such as `192.168.0.0/16` or `::1/128`.
+    ///
+    /// A bare address is treated as a network containing only that address.
+    fn from_str(s: &str) -> Result<IpNetwork, String> {
+        let mut parts = s.splitn(2, '/');
+        let addr = match parts.next().unwrap().parse::<IpAddr>() {
+            Ok(addr) => addr,
+            Err(_) => return Err(format!("Invalid network address: `{}`", s))
+        };
+
+        let prefix = match parts.next() {
+            Some(prefix) => match prefix.parse::<u8>() {
+                Ok(prefix) => prefix,
+                Err(_) => return Err(format!("Invalid prefix length: `{}`", s))
+            },
+            None => max_prefix(addr)
+        };
+
+        IpNetwork::new(addr, prefix)
+    }
+}
+
+fn max_prefix(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128
+    }
+}
+
+/// One element of a `Forwarded` header, as defined by RFC 7239, holding
+/// what one proxy knew of the request it forwarded.
+///
+/// Values are given unquoted. Nodes, in `by` and `for`, are kept as sent:
+/// an address such as `192.0.2.60` or `[2001:db8::1]:8080`, `unknown`, or an
+/// obfuscated identifier such as `_hidden`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ForwardedElement {
+    /// The `by` parameter: where the proxy received the request.
+    pub by: Option<String>,
+
+    /// The `for` parameter: the client which sent the request to the proxy.
+    /// Named `for_` since `for` is a keyword.
+    pub for_: Option<String>,
+
+    /// The `host` parameter: the `Host` the proxy was asked for.
+    pub host: Option<String>,
+
+    /// The `proto` parameter: the scheme the proxy was asked with, such as
+    /// `https`.
+    pub proto: Option<String>,
+
+    /// Any other parameters, in order, with their names lowercased.
+    pub extensions: Vec<(String, String)>
+}
+
+impl ForwardedElement {
+    /// The address in `for`, if it is one rather than `unknown` or an
+    /// obfuscated identifier. The port is `0` if none is given.
+    pub fn for_addr(&self) -> Option<SocketAddr> {
+        self.for_.as_ref().and_then(|node| parse_forwarded_addr(node))
+    }
+}
+
+impl<'a> Request<'a> {
+    /// The elements of the `Forwarded` header, ordered from the proxy
+    /// nearest the client to the one nearest Iron.
+    ///
+    /// Parameter names are compared without regard to case, and only the
+    /// first of a parameter repeated in one element is kept. Parameters which
+    /// can't be parsed are skipped, as are elements left with none, and lines
+    /// which aren't valid UTF-8.
+    ///
+    /// Any client can send this header, so only believe elements added by
+    /// proxies you trust.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use iron::{method, test, Headers, Url};
+    /// # use iron::request::Body;
+    /// # let mut headers = Headers::new();
+    /// # headers.set_raw("Forwarded",
+    /// #                 vec![br#"for="[2001:db8::1]:4711";proto=https, for=_gateway"#.to_vec()]);
+    /// # let mut reader = io::empty();
+    /// # let req = test::request(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+    /// #                         Some(headers), Body::from_reader(&mut reader, Some(0), false));
+    /// // Forwarded: for="[2001:db8::1]:4711";proto=https, for=_gateway
+    /// let forwarded = req.forwarded();
+    /// assert_eq!(forwarded[0].for_addr(), Some("[2001:db8::1]:4711".parse().unwrap()));
+    /// assert_eq!(forwarded[0].proto, Some("https".to_owned()));
+    /// assert_eq!(forwarded[1].for_, Some("_gateway".to_owned()));
+    /// assert_eq!(forwarded[1].for_addr(), None);
+    /// ```
+    pub fn forwarded(&self) -> Vec<ForwardedElement> {
+        self.forwarded_pairs().into_iter().map(|pairs| {
+            let mut element = ForwardedElement::default();
+            for (name, value) in pairs {
+                let value = value.into_owned();
+                let field = match &*name.to_ascii_lowercase() {
+                    "by" => &mut element.by,
+                    "for" => &mut element.for_,
+                    "host" => &mut element.host,
+                    "proto" => &mut element.proto,
+                    name => {
+                        if !element.extensions.iter().any(|&(ref seen, _)| seen == name) {
+                            element.extensions.push((name.to_owned(), value));
+                        }
+                        continue;
+                    }
+                };
+                if field.is_none() { *field = Some(value) }
+            }
+            element
+        }).collect()
+    }
+
+    // The parameters of each element of the `Forwarded` header, borrowed
+    // from the header where they have no escapes.
+    fn forwarded_pairs(&self) -> Vec<Vec<(&str, Cow<str>)>> {
+        self.headers.get_raw("Forwarded").unwrap_or(&[]).iter()
+            .filter_map(|line| ::std::str::from_utf8(line).ok())
+            .flat_map(parse_forwarded)
+            .collect()
+    }
+
+    // The first value of the parameter `name` in the last element of the
+    // `Forwarded` header, which was added by the proxy nearest Iron.
+    fn last_forwarded(&self, name: &str) -> Option<Cow<str>> {
+        self.forwarded_pairs().into_iter().last().and_then(|pairs| {
+            pairs.into_iter().find(|&(ref param, _)| param.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+        })
+    }
+
+    /// The address of the client, taking `X-Forwarded-For` into account.
+    ///
+    /// `X-Forwarded-For` is only consulted when the request was received from
+    /// one of the `trusted` proxy networks; otherwise a client could claim any
+    /// address by sending the header itself. The header is walked from the
+    /// nearest proxy backwards, skipping trusted addresses, and the first
+    /// untrusted address is returned. Tokens which are not IP addresses are
+    /// ignored.
+    ///
+    /// If there is no `X-Forwarded-For`, the `for` parameters of the
+    /// standard `Forwarded` header are walked the same way. The legacy header
+    /// wins so that a client can't get around a proxy which only manages
+    /// `X-Forwarded-For` by sending `Forwarded` itself.
+    ///
+    /// Falls back to `remote_addr` if neither header has usable entries.
+    /// Forwarded addresses rarely carry a port; in that case the returned
+    /// port is `0`.
+    pub fn real_remote_addr(&self, trusted: &[IpNetwork]) -> SocketAddr {
+        let entries = match self.headers.get_raw("X-Forwarded-For") {
+            Some(values) => values.iter()
+                .filter_map(|line| ::std::str::from_utf8(line).ok())
+                .flat_map(|line| line.split(','))
+                .map(Cow::Borrowed)
+                .collect::<Vec<_>>(),
+            None => self.forwarded_pairs().into_iter()
+                .filter_map(|pairs| {
+                    pairs.into_iter().find(|&(ref name, _)| name.eq_ignore_ascii_case("for"))
+                        .map(|(_, node)| node)
+                })
+                .collect()
+        };
+
+        let entries = entries.iter().map(|entry| &**entry).collect::<Vec<_>>();
+        forwarded_client(self.remote_addr, &entries, trusted)
+    }
+
+    /// The scheme the client used, such as `https`.
+    ///
+    /// If `trust_forwarded` is set, the `X-Forwarded-Proto` header is
+    /// consulted first, so the scheme used to reach a TLS-terminating proxy
+    /// is reported rather than the one the proxy used to reach Iron. Only set
+    /// it when every request arrives through such a proxy, or clients could
+    /// claim any scheme by sending the header themselves.
+    ///
+    /// If several proxies appended to the header, the last entry, set by the
+    /// proxy nearest Iron, is used; earlier ones may have been sent by the
+    /// client. Without `X-Forwarded-Proto`, the `proto` of the last element
+    /// of `Forwarded` is used instead, for the same reason. Values other
+    /// than `http` and `https` are ignored.
+    pub fn scheme(&self, trust_forwarded: bool) -> &str {
+        if trust_forwarded {
+            let forwarded = match self.headers.get_raw("X-Forwarded-Proto") {
+                Some(_) => self.last_entry("X-Forwarded-Proto").map(Cow::Borrowed),
+                None => self.last_forwarded("proto")
+            };
+
+            match forwarded {
+                Some(ref proto) if proto.eq_ignore_ascii_case("https") => return "https",
+                Some(ref proto) if proto.eq_ignore_ascii_case("http") => return "http",
+                _ => ()
+            }
+        }
+
+        &self.url.scheme
+    }
+
+    // The last entry of a comma-separated header, added by the proxy nearest
+    // Iron.
+    fn last_entry(&self, name: &str) -> Option<&str> {
+        self.headers.get_raw(name)
+            .and_then(|lines| lines.last())
+            .and_then(|line| ::std::str::from_utf8(line).ok())
+            .and_then(|line| line.rsplit(',').next())
+            .map(|entry| entry.trim())
+    }
+
+    /// The host the client asked for, such as `example.com`, for choosing
+    /// between virtual hosts.
+    ///
+    /// If `trust_forwarded` is set, the last entry of the `X-Forwarded-Host`
+    /// header, added by the proxy nearest Iron, is used, so the host requested
+    /// from a proxy is reported rather than the one the proxy requested from
+    /// Iron, or without that header the `host` of the last element of
+    /// `Forwarded`. As with `scheme`, only set it when every request arrives
+    /// through such a proxy. Otherwise, or if those headers are missing or
+    /// malformed, the `Host` header is used, then the host of `url`.
+    ///
+    /// The host is given as sent, without any port. IPv6 addresses keep their
+    /// brackets, like `[::1]`. Returns `None` only if no host is known, or the
+    /// only one is an IPv6 address in `url`.
+    pub fn host(&self, trust_forwarded: bool) -> Option<&str> {
+        match self.authority(trust_forwarded) {
+            Some((host, _)) => Some(host),
+            None => match self.url.host {
+                Host::Domain(ref domain) => Some(domain),
+                _ => None
+            }
+        }
+    }
+
+    /// The port the client asked for, from the same header as `host`.
+    ///
+    /// If that header has no port, the default port of `scheme` is given
+    /// instead, such as `443` for `https`. Without a usable header, this is
+    /// the port of `url`.
+    pub fn port(&self, trust_forwarded: bool) -> Option<u16> {
+        match self.authority(trust_forwarded) {
+            Some((_, Some(port))) => Some(port),
+            Some((_, None)) if self.scheme(trust_forwarded) == "https" => Some(443),
+            Some((_, None)) => Some(80),
+            None => Some(self.url.port)
+        }
+    }
+
+    // The host and port from the first usable of `X-Forwarded-Host` or
+    // `Forwarded`, if they are trusted, and `Host`.
+    fn authority(&self, trust_forwarded: bool) -> Option<(&str, Option<u16>)> {
+        let header = |name| self.last_entry(name).and_then(parse_authority);
+
+        let forwarded = match (trust_forwarded, self.headers.get_raw("X-Forwarded-Host")) {
+            (false, _) => None,
+            (true, Some(_)) => header("X-Forwarded-Host"),
+            // Hosts never need escapes, so those with any are ignored.
+            (true, None) => match self.last_forwarded("host") {
+                Some(Cow::Borrowed(host)) => parse_authority(host),
+                _ => None
+            }
+        };
+        forwarded.or_else(|| header("Host"))
+    }
+}
+
+// Parse a host and optional port, such as `example.com`, `example.com:8080`
+// or `[::1]:8080`.
+fn parse_authority(authority: &str) -> Option<(&str, Option<u16>)> {
+    let authority = authority.trim();
+
+    let (host, port) = if authority.starts_with('[') {
+        let end = match authority.find(']') {
+            Some(end) => end + 1,
+            None => return None
+        };
+
+        match &authority[end..] {
+            "" => (&authority[..end], None),
+            rest if rest.starts_with(':') => (&authority[..end], Some(&rest[1..])),
+            _ => return None
+        }
+    } else {
+        let mut parts = authority.splitn(2, ':');
+        (parts.next().unwrap(), parts.next())
+    };
+
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '/' || c == '@') {
+        return None;
+    }
+
+    // An empty port, as in `example.com:`, means the default one.
+    match port {
+        None | Some("") => Some((host, None)),
+        Some(port) => port.parse::<u16>().ok().map(|port| (host, Some(port)))
+    }
+}
+
+// Parse a line of a `Forwarded` header into the `name=value` pairs of each of
+// its elements. The grammar is `element *( "," element )`, where an element is
+// `[ pair ] *( ";" [ pair ] )` and a value is a token or a quoted string.
+fn parse_forwarded(line: &str) -> Vec<Vec<(&str, Cow<str>)>> {
+    let bytes = line.as_bytes();
+    let mut elements = vec![Vec::new()];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b' ' | b'\t' | b';' => i += 1,
+            b',' => {
+                elements.push(Vec::new());
+                i += 1;
+            },
+            _ => {
+                let (pair, next) = parse_forwarded_pair(line, i);
+                if let Some(pair) = pair { elements.last_mut().unwrap().push(pair) }
+                i = next;
+            }
+        }
+    }
+
+    elements.retain(|pairs| !pairs.is_empty());
+    elements
+}
+
+// Parse the pair starting at `start`, if it is valid, returning it along with
+// where parsing should go on. Invalid pairs are skipped up to the next `;` or
+// `,`.
+fn parse_forwarded_pair(line: &str, start: usize) -> (Option<(&str, Cow<str>)>, usize) {
+    let bytes = line.as_bytes();
+    let token_end = |from: usize| from + bytes[from..].iter().take_while(|&&b| is_tchar(b)).count();
+    let ends_value = |at: usize| at == bytes.len() || b" \t;,".contains(&bytes[at]);
+    let skip = || start + bytes[start..].iter().take_while(|&&b| b != b';' && b != b',').count();
+
+    let name_end = token_end(start);
+    if name_end == start || bytes.get(name_end) != Some(&b'=') { return (None, skip()) }
+    let name = &line[start..name_end];
+    let value_start = name_end + 1;
+
+    if bytes.get(value_start) != Some(&b'"') {
+        let value_end = token_end(value_start);
+        if value_end == value_start || !ends_value(value_end) { return (None, skip()) }
+        return (Some((name, Cow::Borrowed(&line[value_start..value_end]))), value_end);
+    }
+
+    // A quoted string, in which `\` escapes the next character.
+    let mut escaped = Vec::new();
+    let mut has_escapes = false;
+    let mut i = value_start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' if ends_value(i + 1) => {
+                let value = if has_escapes {
+                    Cow::Owned(String::from_utf8(escaped).unwrap())
+                } else {
+                    Cow::Borrowed(&line[value_start + 1..i])
+                };
+                return (Some((name, value)), i + 1);
+            },
+            b'"' => return (None, skip()),
+            b'\\' if i + 1 < bytes.len() => {
+                has_escapes = true;
+                escaped.push(bytes[i + 1]);
+                i += 2;
+            },
+            b => {
+                escaped.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    // The quoted string is never closed.
+    (None, bytes.len())
+}
+
+// Is `b` allowed in a token, as defined by RFC 7230?
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// Walk a list of forwarded addresses, ordered from client to nearest proxy,
+// back from the peer which made the actual connection.
+fn forwarded_client(remote_addr: SocketAddr, entries: &[&str], trusted: &[IpNetwork])
+                    -> SocketAddr {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|network| network.contains(ip));
+
+    if !is_trusted(remote_addr.ip()) { return remote_addr }
+
+    let mut client = remote_addr;
+    for addr in entries.iter().rev().filter_map(|entry| parse_forwarded_addr(entry)) {
+        client = addr;
+        if !is_trusted(addr.ip()) { break }
+    }
+
+    client
+}
+
+// Parse an address such as `1.2.3.4`, `1.2.3.4:80`, `::1` or `[::1]:80`.
+fn parse_forwarded_addr(entry: &str) -> Option<SocketAddr> {
+    let entry = entry.trim();
+
+    if let Ok(ip) = entry.parse::<IpAddr>() {
+        return Some(SocketAddr::new(ip, 0));
+    }
+
+    if let Ok(addr) = entry.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+
+    // A bracketed IPv6 address without a port.
+    if entry.starts_with('[') && entry.ends_with(']') {
+        return entry[1..entry.len() - 1].parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 0));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use {method, test, Url};
+
+    use super::{IpNetwork, ForwardedElement, forwarded_client, parse_authority, parse_forwarded};
+
+    fn addr(s: &str) -> SocketAddr { s.parse().unwrap() }
+
+    fn networks(s: &[&str]) -> Vec<IpNetwork> {
+        s.iter().map(|n| n.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_network_contains() {
+        let network: IpNetwork = "10.1.0.0/16".parse().unwrap();
+        assert!(network.contains("10.1.200.3".parse().unwrap()));
+        assert!(!network.contains("10.2.0.1".parse().unwrap()));
+        assert!(!network.contains("::1".parse().unwrap()));
+
+        let network: IpNetwork = "fe80::/10".parse().unwrap();
+        assert!(network.contains("fe80::1".parse().unwrap()));
+        assert!(network.contains("febf::1".parse().unwrap()));
+        assert!(!network.contains("fec0::1".parse().unwrap()));
+
+        let network: IpNetwork = "127.0.0.1".parse().unwrap();
+        assert!(network.contains("127.0.0.1".parse().unwrap()));
+        assert!(!network.contains("127.0.0.2".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_network_invalid() {
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("example.com/8".parse::<IpNetwork>().is_err());
+        assert!("10.0.0.0/x".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_header() {
+        let trusted = networks(&["10.0.0.0/8"]);
+        assert_eq!(forwarded_client(addr("1.2.3.4:5000"), &["5.6.7.8"], &trusted),
+                   addr("1.2.3.4:5000"));
+    }
+
+    #[test]
+    fn test_skips_trusted_proxies() {
+        let trusted = networks(&["10.0.0.0/8", "::1"]);
+        let entries = ["9.9.9.9", " 5.6.7.8", "10.0.0.2", "[::1]:8080"];
+        assert_eq!(forwarded_client(addr("10.0.0.1:5000"), &entries, &trusted),
+                   addr("5.6.7.8:0"));
+    }
+
+    #[test]
+    fn test_ignores_invalid_entries() {
+        let trusted = networks(&["10.0.0.0/8"]);
+        let entries = ["2001:db8::1", "unknown", "10.0.0.3"];
+        assert_eq!(forwarded_client(addr("10.0.0.1:5000"), &entries, &trusted),
+                   addr("[2001:db8::1]:0"));
+
+        assert_eq!(forwarded_client(addr("10.0.0.1:5000"), &["garbage"], &trusted),
+                   addr("10.0.0.1:5000"));
+    }
+
+    #[test]
+    fn test_forwarded_scheme() {
+        let scheme = |proto: &str, trust: bool| {
+            test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                       &[("X-Forwarded-Proto", proto)],
+                                       |req| req.scheme(trust).to_owned())
+        };
+
+        assert_eq!(scheme("https", true), "https");
+        assert_eq!(scheme("http, HTTPS", true), "https");
+        assert_eq!(scheme("https, http", true), "http");
+        assert_eq!(scheme("https", false), "http");
+        assert_eq!(scheme("gopher", true), "http");
+    }
+
+    #[test]
+    fn test_parse_authority() {
+        assert_eq!(parse_authority("example.com"), Some(("example.com", None)));
+        assert_eq!(parse_authority(" example.com:8080"), Some(("example.com", Some(8080))));
+        assert_eq!(parse_authority("example.com:"), Some(("example.com", None)));
+        assert_eq!(parse_authority("[::1]"), Some(("[::1]", None)));
+        assert_eq!(parse_authority("[::1]:8080"), Some(("[::1]", Some(8080))));
+        assert_eq!(parse_authority("::1"), None);
+        assert_eq!(parse_authority("[::1]8080"), None);
+        assert_eq!(parse_authority("example.com:http"), None);
+        assert_eq!(parse_authority(""), None);
+    }
+
+    #[test]
+    fn test_forwarded_host_and_port() {
+        let headers = [
+            ("Host", "internal:3000"),
+            ("X-Forwarded-Host", "evil.com, example.com"),
+            ("X-Forwarded-Proto", "https")
+        ];
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, &headers, |req| {
+            assert_eq!((req.host(true), req.port(true)), (Some("example.com"), Some(443)));
+            assert_eq!((req.host(false), req.port(false)), (Some("internal"), Some(3000)));
+        });
+    }
+
+    #[test]
+    fn test_parse_forwarded() {
+        let pairs = |line| parse_forwarded(line).into_iter()
+            .map(|pairs| pairs.into_iter().map(|(n, v)| format!("{}={}", n, v)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(pairs("for=192.0.2.60;proto=http;by=203.0.113.43"),
+                   [["for=192.0.2.60", "proto=http", "by=203.0.113.43"]]);
+        assert_eq!(pairs(r#"For="[2001:db8:cafe::17]:4711", for=unknown ;; ,for=_hidden"#),
+                   [vec!["For=[2001:db8:cafe::17]:4711"], vec!["for=unknown"],
+                    vec!["for=_hidden"]]);
+        assert_eq!(pairs(r#"host="a;b,c";ext="q\"uo\\te""#), [[r#"host=a;b,c"#, r#"ext=q"uo\te"#]]);
+
+        // Invalid pairs are skipped, but their neighbours are kept.
+        assert_eq!(pairs("for=1.2.3.4:80;proto=https, =x, by=a"),
+                   [vec!["proto=https"], vec!["by=a"]]);
+        assert_eq!(pairs(r#"for="1.2.3.4"x;proto=https"#), [["proto=https"]]);
+        assert_eq!(pairs(r#"proto=https;for="unclosed, by=a"#), [["proto=https"]]);
+        assert!(pairs("").is_empty());
+    }
+
+    fn forwarded_request(headers: &[(&str, &str)], remote_addr: &str)
+                         -> (Vec<ForwardedElement>, SocketAddr, String, Option<String>) {
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, headers, |req| {
+            req.remote_addr = addr(remote_addr);
+
+            let trusted = networks(&["10.0.0.0/8"]);
+            (req.forwarded(), req.real_remote_addr(&trusted), req.scheme(true).to_owned(),
+             req.host(true).map(String::from))
+        })
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let (elements, client, scheme, host) = forwarded_request(&[
+            ("Forwarded", concat!(r#"for=6.6.6.6;proto=http;host=evil.com, "#,
+                                  r#"for="5.6.7.8";proto=https;host=example.com;"#,
+                                  r#"secret=1;PROTO=http"#))
+        ], "10.0.0.1:5000");
+
+        assert_eq!(elements, vec![
+            ForwardedElement {
+                for_: Some("6.6.6.6".to_owned()),
+                proto: Some("http".to_owned()),
+                host: Some("evil.com".to_owned()),
+                ..ForwardedElement::default()
+            },
+            ForwardedElement {
+                for_: Some("5.6.7.8".to_owned()),
+                proto: Some("https".to_owned()),
+                host: Some("example.com".to_owned()),
+                extensions: vec![("secret".to_owned(), "1".to_owned())],
+                ..ForwardedElement::default()
+            }
+        ]);
+        assert_eq!(client, addr("5.6.7.8:0"));
+        assert_eq!(scheme, "https");
+        assert_eq!(host, Some("example.com".to_owned()));
+    }
+
+    #[test]
+    fn test_legacy_headers_win_over_forwarded() {
+        let (_, client, scheme, host) = forwarded_request(&[
+            ("Forwarded", "for=6.6.6.6;proto=https;host=evil.com"),
+            ("X-Forwarded-For", "5.6.7.8"),
+            ("X-Forwarded-Proto", "http"),
+            ("X-Forwarded-Host", "example.com")
+        ], "10.0.0.1:5000");
+
+        assert_eq!(client, addr("5.6.7.8:0"));
+        assert_eq!(scheme, "http");
+        assert_eq!(host, Some("example.com".to_owned()));
+
+        // Peers which aren't trusted can't claim another address either way.
+        let (_, client, _, _) = forwarded_request(&[("Forwarded", "for=6.6.6.6")], "1.2.3.4:5000");
+        assert_eq!(client, addr("1.2.3.4:5000"));
+    }
+}
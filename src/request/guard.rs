@@ -0,0 +1,160 @@
+//! Checking preconditions of requests, failing with standard errors.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use mime::Mime;
+
+use {IronResult, IronError};
+use middleware::Unauthorized;
+use status;
+
+use super::{Request, LimitExceeded};
+
+/// The error returned by `Request::require_json` and `Request::require_form`
+/// when the request body has another `Content-Type`, with a `415 Unsupported
+/// Media Type` response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedMediaType {
+    /// The kind of body which was expected, such as `JSON`.
+    pub expected: &'static str,
+    /// The `Content-Type` the request was sent with, if any.
+    pub found: Option<Mime>
+}
+
+impl fmt::Display for UnsupportedMediaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.found {
+            Some(ref mime) => {
+                write!(f, "Expected a {} body, but the request has Content-Type {}",
+                       self.expected, mime)
+            },
+            None => {
+                write!(f, "Expected a {} body, but the request has no Content-Type", self.expected)
+            }
+        }
+    }
+}
+
+impl StdError for UnsupportedMediaType {
+    fn description(&self) -> &str {
+        "Unsupported Content-Type"
+    }
+}
+
+impl<'a> Request<'a> {
+    /// Fail with `err` unless `cond` holds, for returning early from a handler
+    /// with `try!`.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// # use iron::error::NotFound;
+    /// fn handler(req: &mut Request) -> IronResult<Response> {
+    ///     try!(req.require_auth());
+    ///     let found = req.url.path.len() == 1;
+    ///     try!(req.require(found, IronError::new(NotFound, status::NotFound)));
+    ///     Ok(Response::with((status::Ok, "Found")))
+    /// }
+    /// ```
+    pub fn require<E: Into<IronError>>(&self, cond: bool, err: E) -> IronResult<()> {
+        if cond { Ok(()) } else { Err(err.into()) }
+    }
+
+    /// Fail with `415 Unsupported Media Type` unless the body is JSON, as
+    /// checked by `is_json`.
+    pub fn require_json(&self) -> IronResult<()> {
+        let json = self.is_json();
+        self.require_media_type(json, "JSON")
+    }
+
+    /// Fail with `415 Unsupported Media Type` unless the body is a form, as
+    /// checked by `is_form`.
+    pub fn require_form(&self) -> IronResult<()> {
+        let form = self.is_form();
+        self.require_media_type(form, "form")
+    }
+
+    /// Fail with `401 Unauthorized` wrapping an `Unauthorized` unless the
+    /// request has `Basic` credentials or a `Bearer` token.
+    ///
+    /// This only checks that credentials were sent; checking them is still up
+    /// to the handler.
+    pub fn require_auth(&self) -> IronResult<()> {
+        let present = self.basic_auth().is_some() || self.bearer_token().is_some();
+        self.require(present, IronError::new(Unauthorized { username: None }, status::Unauthorized))
+    }
+
+    /// Fail with `413 Payload Too Large` wrapping a `LimitExceeded` if the
+    /// request declares a body of more than `max_bytes`.
+    ///
+    /// Bodies whose length isn't known up front, such as chunked ones, get a
+    /// limit of `max_bytes` set on them instead, so reading them fails once
+    /// they grow too large.
+    pub fn require_body_within(&mut self, max_bytes: u64) -> IronResult<()> {
+        let within = self.content_length().map_or(true, |len| len <= max_bytes);
+        try!(self.require(within, IronError::new(LimitExceeded { limit: max_bytes },
+                                                 status::PayloadTooLarge)));
+
+        let limit = self.body.limit().map_or(max_bytes, |limit| limit.min(max_bytes));
+        self.body.set_limit(limit);
+        Ok(())
+    }
+
+    fn require_media_type(&self, cond: bool, expected: &'static str) -> IronResult<()> {
+        if cond { return Ok(()) }
+        let err = UnsupportedMediaType { expected: expected, found: self.content_type() };
+        Err(IronError::new(err, status::UnsupportedMediaType))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use request::LimitExceeded;
+    use middleware::Unauthorized;
+    use {method, status, test, Url};
+
+    use super::UnsupportedMediaType;
+
+    fn with_headers<F: FnOnce(&mut ::Request)>(headers: &[(&str, &str)], f: F) {
+        test::request_with_headers(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                   headers, |req| f(req))
+    }
+
+    #[test]
+    fn test_require_json() {
+        with_headers(&[("Content-Type", "application/json")], |req| {
+            assert!(req.require_json().is_ok());
+
+            let err = req.require_form().unwrap_err();
+            assert_eq!(err.response.status, Some(status::UnsupportedMediaType));
+            assert_eq!(err.error.downcast::<UnsupportedMediaType>().unwrap().expected, "form");
+        });
+    }
+
+    #[test]
+    fn test_require_auth() {
+        with_headers(&[("Authorization", "Bearer abc")], |req| assert!(req.require_auth().is_ok()));
+        with_headers(&[], |req| {
+            let err = req.require_auth().unwrap_err();
+            assert_eq!(err.response.status, Some(status::Unauthorized));
+            assert_eq!(err.error.downcast::<Unauthorized>(),
+                       Some(&Unauthorized { username: None }));
+        });
+    }
+
+    #[test]
+    fn test_require_body_within() {
+        with_headers(&[("Content-Length", "10")], |req| {
+            assert!(req.require_body_within(10).is_ok());
+            let err = req.require_body_within(9).unwrap_err();
+            assert_eq!(err.response.status, Some(status::PayloadTooLarge));
+            assert_eq!(err.error.downcast::<LimitExceeded>(), Some(&LimitExceeded { limit: 9 }));
+        });
+        with_headers(&[], |req| {
+            req.body.set_limit(5);
+            assert!(req.require_body_within(100).is_ok());
+            assert_eq!(req.body.limit(), Some(5));
+        });
+    }
+}
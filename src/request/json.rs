@@ -0,0 +1,153 @@
+//! Deserializing JSON request bodies, behind the `serde` feature.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use mime::Mime;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use super::{Request, LimitExceeded};
+
+/// The ways in which `Request::json_body` can fail.
+#[derive(Debug)]
+pub enum JsonError {
+    /// The request does not have a JSON `Content-Type`.
+    ///
+    /// Holds the `Content-Type` the request was sent with, if any.
+    UnexpectedContentType(Option<Mime>),
+
+    /// The body is larger than the limit set on it.
+    TooLarge(LimitExceeded),
+
+    /// The body is empty.
+    Empty,
+
+    /// The body could not be read.
+    Io(io::Error),
+
+    /// The body is not valid JSON, or does not match the expected type.
+    Deserialize(serde_json::Error)
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JsonError::UnexpectedContentType(Some(ref mime)) => {
+                write!(f, "Expected a JSON body, but the request has Content-Type {}", mime)
+            },
+            JsonError::UnexpectedContentType(None) => {
+                f.write_str("Expected a JSON body, but the request has no Content-Type")
+            },
+            JsonError::TooLarge(ref err) => fmt::Display::fmt(err, f),
+            JsonError::Empty => f.write_str("Expected a JSON body, but the body is empty"),
+            JsonError::Io(ref err) => write!(f, "Error reading the request body: {}", err),
+            JsonError::Deserialize(ref err) => write!(f, "Invalid JSON body: {}", err)
+        }
+    }
+}
+
+impl StdError for JsonError {
+    fn description(&self) -> &str {
+        match *self {
+            JsonError::UnexpectedContentType(_) => "Request body is not JSON",
+            JsonError::TooLarge(_) => "Request body exceeded the configured limit",
+            JsonError::Empty => "Request body is empty",
+            JsonError::Io(_) => "Error reading the request body",
+            JsonError::Deserialize(_) => "Invalid JSON body"
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            JsonError::TooLarge(ref err) => Some(err),
+            JsonError::Io(ref err) => Some(err),
+            JsonError::Deserialize(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for JsonError {
+    fn from(err: io::Error) -> JsonError {
+        let limit = err.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()).cloned();
+        match limit {
+            Some(limit) => JsonError::TooLarge(limit),
+            None => JsonError::Io(err)
+        }
+    }
+}
+
+impl<'a> Request<'a> {
+    /// Read the body and deserialize it from JSON.
+    ///
+    /// The request must have a JSON `Content-Type`, as checked by `is_json`.
+    /// Any limit set on the body is respected, and is reported as
+    /// `JsonError::TooLarge`.
+    pub fn json_body<T: DeserializeOwned>(&mut self) -> Result<T, JsonError> {
+        if !self.is_json() {
+            return Err(JsonError::UnexpectedContentType(self.content_type()));
+        }
+
+        let bytes = try!(self.get_body_bytes());
+        if bytes.iter().all(|b| b" \t\r\n".contains(b)) {
+            return Err(JsonError::Empty);
+        }
+
+        serde_json::from_slice(&bytes).map_err(JsonError::Deserialize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use request::Body;
+    use {method, test, Headers, Url};
+
+    use super::JsonError;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Point { x: i32, y: i32 }
+
+    fn json_body(content_type: &str, body: &str, limit: Option<u64>) -> Result<Point, JsonError> {
+        let mut headers = Headers::new();
+        headers.set_raw("Content-Type", vec![content_type.as_bytes().to_vec()]);
+
+        let mut reader = body.as_bytes();
+        let mut body = Body::from_reader(&mut reader, Some(body.len() as u64), false);
+        if let Some(limit) = limit { body.set_limit(limit) }
+
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    Some(headers), body);
+        req.json_body()
+    }
+
+    #[test]
+    fn test_json_body() {
+        assert_eq!(json_body("application/json", r#"{"x": 1, "y": 2}"#, None).unwrap(),
+                   Point { x: 1, y: 2 });
+    }
+
+    #[test]
+    fn test_json_body_errors() {
+        match json_body("text/plain", "{}", None) {
+            Err(JsonError::UnexpectedContentType(Some(_))) => (),
+            other => panic!("Unexpected result: {:?}", other)
+        }
+
+        match json_body("application/json", r#"{"x": 1, "y": 2}"#, Some(4)) {
+            Err(JsonError::TooLarge(limit)) => assert_eq!(limit.limit, 4),
+            other => panic!("Unexpected result: {:?}", other)
+        }
+
+        match json_body("application/json", " \n", None) {
+            Err(JsonError::Empty) => (),
+            other => panic!("Unexpected result: {:?}", other)
+        }
+
+        match json_body("application/json", r#"{"x": 1}"#, None) {
+            Err(JsonError::Deserialize(_)) => (),
+            other => panic!("Unexpected result: {:?}", other)
+        }
+    }
+}
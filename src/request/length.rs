@@ -0,0 +1,168 @@
+//! Checking how the length of request bodies is given.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use {IronError, IronResult, Headers, headers, method, status};
+
+use super::Request;
+
+/// The error returned by `Request::expect_content_length` when a request
+/// which needs a body has neither a `Content-Length` nor a chunked body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthRequired;
+
+impl fmt::Display for LengthRequired {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl StdError for LengthRequired {
+    fn description(&self) -> &str {
+        "Request has no Content-Length"
+    }
+}
+
+impl<'a> Request<'a> {
+    /// The `Content-Length` of the request body, if it has one.
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers.get::<headers::ContentLength>().map(|len| len.0)
+    }
+
+    /// Is the body sent with `Transfer-Encoding: chunked`, and so without a
+    /// `Content-Length`?
+    pub fn is_chunked(&self) -> bool {
+        self.headers.get::<headers::TransferEncoding>()
+            .map_or(false, |codings| codings.last() == Some(&headers::Encoding::Chunked))
+    }
+
+    /// Fail with `411 Length Required` unless a `POST`, `PUT` or `PATCH`
+    /// request says how long its body is.
+    ///
+    /// Returns the `Content-Length`, or `None` for chunked bodies and other
+    /// methods without one. A `Content-Length` of `0` is accepted; check for
+    /// it if the body mustn't be empty.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// fn upload(req: &mut Request) -> IronResult<Response> {
+    ///     try!(req.expect_content_length());
+    ///     // ...
+    /// #   Ok(Response::with(status::Created))
+    /// }
+    /// ```
+    pub fn expect_content_length(&self) -> IronResult<Option<u64>> {
+        let len = self.content_length();
+        match self.method {
+            method::Post | method::Put | method::Patch if len.is_none() && !self.is_chunked() => {
+                Err(IronError::new(LengthRequired, status::LengthRequired))
+            },
+            _ => Ok(len)
+        }
+    }
+}
+
+// Check that the headers say unambiguously how the body is framed, since a
+// proxy in front of the server might read them differently and smuggle a
+// second request inside the body.
+//
+// Requests are rejected if they have both `Content-Length` and
+// `Transfer-Encoding`, `Content-Length` values which are invalid or differ,
+// or a `Transfer-Encoding` which doesn't end with `chunked`. Repeating the
+// same `Content-Length` is allowed.
+pub fn check_framing(headers: &Headers) -> Result<(), String> {
+    let lengths = headers.get_raw("Content-Length");
+    let codings = headers.get_raw("Transfer-Encoding");
+
+    if lengths.is_some() && codings.is_some() {
+        return Err("Request has both Content-Length and Transfer-Encoding".into());
+    }
+
+    if let Some(lines) = lengths {
+        let mut values = lines.iter().flat_map(|line| line.split(|&b| b == b','));
+        let first = values.next().and_then(parse_length);
+        if first.is_none() || values.any(|value| parse_length(value) != first) {
+            return Err("Request has an invalid or conflicting Content-Length".into());
+        }
+    }
+
+    if let Some(lines) = codings {
+        let last = lines.iter().flat_map(|line| line.split(|&b| b == b','))
+            .map(|coding| String::from_utf8_lossy(coding).trim().to_ascii_lowercase())
+            .filter(|coding| !coding.is_empty())
+            .last();
+        if last.as_ref().map(|coding| &**coding) != Some("chunked") {
+            return Err("Request has a Transfer-Encoding which doesn't end with chunked".into());
+        }
+    }
+
+    Ok(())
+}
+
+// Parse one `Content-Length` value, allowing only digits around whitespace.
+fn parse_length(value: &[u8]) -> Option<u64> {
+    let value = String::from_utf8_lossy(value);
+    let value = value.trim();
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) { return None }
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use method::Method;
+    use {method, status, test, Headers, Url};
+
+    fn expect_content_length(method: Method, header: Option<(&str, &str)>)
+                             -> Result<Option<u64>, Option<status::Status>> {
+        let headers: Vec<_> = header.into_iter().collect();
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method, url, &headers, |req| {
+            req.expect_content_length().map_err(|err| err.response.status)
+        })
+    }
+
+    #[test]
+    fn test_expect_content_length() {
+        assert_eq!(expect_content_length(method::Post, Some(("Content-Length", "12"))),
+                   Ok(Some(12)));
+        assert_eq!(expect_content_length(method::Put, Some(("Content-Length", "0"))), Ok(Some(0)));
+        assert_eq!(expect_content_length(method::Post,
+                                         Some(("Transfer-Encoding", "gzip, chunked"))),
+                   Ok(None));
+        assert_eq!(expect_content_length(method::Get, None), Ok(None));
+        assert_eq!(expect_content_length(method::Patch, None), Err(Some(status::LengthRequired)));
+        assert_eq!(expect_content_length(method::Post,
+                                         Some(("Transfer-Encoding", "chunked, gzip"))),
+                   Err(Some(status::LengthRequired)));
+    }
+
+    #[test]
+    fn test_check_framing() {
+        let check = |lines: &[(&str, &str)]| {
+            let mut headers = Headers::new();
+            for &(name, value) in lines {
+                let mut values = headers.get_raw(name)
+                    .map_or_else(Vec::new, |values| values.to_vec());
+                values.push(value.as_bytes().to_vec());
+                headers.set_raw(name.to_owned(), values);
+            }
+            super::check_framing(&headers).is_ok()
+        };
+
+        assert!(check(&[]));
+        assert!(check(&[("Content-Length", "12")]));
+        assert!(check(&[("Content-Length", "12"), ("Content-Length", " 12")]));
+        assert!(check(&[("Content-Length", "12, 12")]));
+        assert!(check(&[("Transfer-Encoding", "gzip, Chunked")]));
+
+        assert!(!check(&[("Content-Length", "12"), ("Transfer-Encoding", "chunked")]));
+        assert!(!check(&[("Content-Length", "12"), ("Content-Length", "13")]));
+        assert!(!check(&[("Content-Length", "12, 13")]));
+        assert!(!check(&[("Content-Length", "+12")]));
+        assert!(!check(&[("Content-Length", "")]));
+        assert!(!check(&[("Transfer-Encoding", "chunked, gzip")]));
+        assert!(!check(&[("Transfer-Encoding", "")]));
+    }
+}
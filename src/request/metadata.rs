@@ -0,0 +1,74 @@
+//! Owned snapshots of requests, for work done after responding.
+
+use std::net::SocketAddr;
+
+use {Headers, Url};
+use method::Method;
+
+use super::{HttpVersion, Request, RequestUri};
+
+/// An owned copy of everything about a `Request` except its body and
+/// extensions, made with `Request::metadata`.
+///
+/// Unlike `Request`, which borrows the connection, `RequestMetadata` is `Send`
+/// and `Clone`, so it can be moved to another thread or sent down a channel.
+#[derive(Clone, Debug)]
+pub struct RequestMetadata {
+    /// The requested URL.
+    pub url: Url,
+
+    /// The request target exactly as it appeared in the request line.
+    pub raw_uri: RequestUri,
+
+    /// The originating address of the request.
+    pub remote_addr: SocketAddr,
+
+    /// The local address of the request.
+    pub local_addr: SocketAddr,
+
+    /// The request headers.
+    pub headers: Headers,
+
+    /// The request method.
+    pub method: Method,
+
+    /// The HTTP version of the request.
+    pub version: HttpVersion,
+
+    /// Whether the request arrived over TLS.
+    pub secure: bool
+}
+
+impl<'a> Request<'a> {
+    /// An owned copy of the request's metadata, to hand to deferred work
+    /// such as logging in a background thread.
+    ///
+    /// The body isn't included: it is read straight from the connection, so
+    /// it can't outlive the request. Read it first if it's needed later.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use std::thread;
+    /// # use iron::{method, test, Url};
+    /// # use iron::request::Body;
+    /// # let mut reader = io::empty();
+    /// # let req = test::request(method::Get, Url::parse("http://localhost:3000/users").unwrap(),
+    /// #                         None, Body::from_reader(&mut reader, Some(0), false));
+    /// let metadata = req.metadata();
+    /// thread::spawn(move || {
+    ///     println!("Served {} {}", metadata.method, metadata.url);
+    /// }).join().unwrap();
+    /// ```
+    pub fn metadata(&self) -> RequestMetadata {
+        RequestMetadata {
+            url: self.url.clone(),
+            raw_uri: self.raw_uri.clone(),
+            remote_addr: self.remote_addr,
+            local_addr: self.local_addr,
+            headers: self.headers.clone(),
+            method: self.method.clone(),
+            version: self.version,
+            secure: self.secure
+        }
+    }
+}
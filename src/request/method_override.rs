@@ -0,0 +1,64 @@
+//! Support for clients which tunnel the request method through POST.
+
+use method::Method;
+
+use super::Request;
+
+impl<'a> Request<'a> {
+    /// The method this request should be handled as, taking the
+    /// `X-HTTP-Method-Override` header into account.
+    ///
+    /// Overrides are only honored on `POST` requests, and only name one of the
+    /// standard methods other than `CONNECT`. Method names are case-sensitive,
+    /// so `delete` is not an override. Anything else, including a missing or
+    /// repeated header, falls back to `method`.
+    pub fn effective_method(&self) -> Method {
+        if self.method != Method::Post { return self.method.clone() }
+
+        let value = match self.headers.get_raw("X-HTTP-Method-Override") {
+            Some(values) if values.len() == 1 => ::std::str::from_utf8(&values[0]).ok(),
+            _ => None
+        };
+
+        value.and_then(parse_override).unwrap_or_else(|| self.method.clone())
+    }
+}
+
+fn parse_override(value: &str) -> Option<Method> {
+    match value.trim().parse::<Method>() {
+        Ok(Method::Extension(_)) | Ok(Method::Connect) | Err(_) => None,
+        Ok(method) => Some(method)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {method, test, Url};
+
+    fn effective(method: method::Method, value: Option<&str>) -> method::Method {
+        let headers: Vec<_> = value.into_iter()
+            .map(|value| ("X-HTTP-Method-Override", value)).collect();
+        test::request_with_headers(method, Url::parse("http://localhost:3000/").unwrap(), &headers,
+                                   |req| req.effective_method())
+    }
+
+    #[test]
+    fn test_overrides_post() {
+        assert_eq!(effective(method::Post, Some("DELETE")), method::Delete);
+        assert_eq!(effective(method::Post, Some(" PATCH ")), method::Patch);
+        assert_eq!(effective(method::Post, None), method::Post);
+    }
+
+    #[test]
+    fn test_ignores_other_methods() {
+        assert_eq!(effective(method::Get, Some("DELETE")), method::Get);
+    }
+
+    #[test]
+    fn test_rejects_nonsense() {
+        assert_eq!(effective(method::Post, Some("")), method::Post);
+        assert_eq!(effective(method::Post, Some("FROB")), method::Post);
+        assert_eq!(effective(method::Post, Some("delete")), method::Post);
+        assert_eq!(effective(method::Post, Some("CONNECT")), method::Post);
+    }
+}
@@ -1,26 +1,88 @@
 //! Iron's HTTP Request representation and associated methods.
 
-use std::io::{self, Read};
+use std::cmp::min;
+use std::error::Error as StdError;
+use std::io::{self, BufRead, Read};
 use std::net::SocketAddr;
 use std::fmt::{self, Debug};
-use std::mem::transmute;
+use std::mem;
+use std::time::Duration;
 
-use hyper::uri::RequestUri::{AbsoluteUri, AbsolutePath};
+pub use hyper::uri::RequestUri;
+use hyper::uri::RequestUri::{AbsoluteUri, AbsolutePath, Authority, Star};
 use hyper::net::NetworkStream;
 use hyper::http::h1::HttpReader;
 
 use typemap::TypeMap;
+use url::Host;
 use plugin::Extensible;
 use method::Method;
+pub use hyper::version::HttpVersion;
 
 pub use hyper::server::request::Request as HttpRequest;
 use hyper::buffer;
 
-pub use self::url::Url;
+pub use self::url::{Url, TrailingSlash, PathSegments};
+pub use self::forwarded::{IpNetwork, ForwardedElement};
+pub use self::query::QueryMap;
+pub use self::form::{FormMap, UnexpectedContentType};
+pub use self::timeout::BodyTimedOut;
+pub use self::route::{RouteParams, MatchedRoute};
+pub use self::decode::UnsupportedEncoding;
+pub use self::transfer::UnsupportedTransferCoding;
+#[doc(hidden)]
+pub use self::transfer::transfer_codings;
+pub use self::range::RangeNotSatisfiable;
+pub use self::length::LengthRequired;
+#[doc(hidden)]
+pub use self::length::check_framing;
+pub use self::guard::UnsupportedMediaType;
+pub use self::metadata::RequestMetadata;
+pub use self::upgrade::Upgraded;
+pub use self::tls::TlsInfo;
+pub use tempfile::NamedTempFile;
+#[cfg(feature = "multipart")]
+pub use self::multipart::{Multipart, Field};
+#[cfg(feature = "serde")]
+pub use self::json::JsonError;
+#[cfg(feature = "serde")]
+pub use self::ndjson::{NdjsonStream, NdjsonError};
 
 use {Protocol, Plugin, Headers, Set, headers};
 
 mod url;
+mod forwarded;
+mod copy_headers;
+mod query;
+mod form;
+mod method_override;
+mod content_type;
+mod negotiate;
+mod accept_encoding;
+mod accept_language;
+mod buffered;
+mod timeout;
+mod prefix;
+mod route;
+mod decode;
+mod transfer;
+mod ext;
+mod auth;
+mod conditional;
+mod range;
+mod length;
+mod guard;
+mod metadata;
+mod temp_file;
+mod trailers;
+mod upgrade;
+mod tls;
+#[cfg(feature = "multipart")]
+mod multipart;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+mod ndjson;
 
 /// The `Request` given to all `Middleware`.
 ///
@@ -30,6 +92,12 @@ pub struct Request<'a> {
     /// The requested URL.
     pub url: Url,
 
+    /// The request target exactly as it appeared in the request line.
+    ///
+    /// Unlike `url`, this distinguishes the origin (`AbsolutePath`), absolute
+    /// (`AbsoluteUri`), authority (`Authority`) and asterisk (`Star`) forms.
+    pub raw_uri: RequestUri,
+
     /// The originating address of the request.
     pub remote_addr: SocketAddr,
 
@@ -45,6 +113,28 @@ pub struct Request<'a> {
     /// The request method.
     pub method: Method,
 
+    /// The HTTP version of the request.
+    ///
+    /// HTTP/1.0 clients do not keep connections alive by default and do not
+    /// understand chunked responses.
+    pub version: HttpVersion,
+
+    /// Whether the request arrived over TLS, because it was accepted by a
+    /// server started with `Iron::https`.
+    ///
+    /// Unlike `Request::scheme`, this is set by the server itself, so clients can't
+    /// spoof it with headers. It is `false` for plain HTTP and Unix sockets,
+    /// including requests forwarded by a TLS-terminating proxy.
+    pub secure: bool,
+
+    /// The HTTP version agreed with the client through TLS ALPN, if any.
+    ///
+    /// This is `None` when no version was negotiated, in which case `version`
+    /// is the one to go by. Iron's HTTP/1 server doesn't offer ALPN yet, so
+    /// for now it is always `None`; an `Http20` here will mean the client is
+    /// speaking HTTP/2, which does its own framing.
+    pub negotiated_version: Option<HttpVersion>,
+
     /// Extensible storage for data passed between middleware.
     pub extensions: TypeMap
 }
@@ -55,6 +145,9 @@ impl<'a> Debug for Request<'a> {
 
         try!(writeln!(f, "    url: {:?}", self.url));
         try!(writeln!(f, "    method: {:?}", self.method));
+        try!(writeln!(f, "    version: {:?}", self.version));
+        try!(writeln!(f, "    secure: {:?}", self.secure));
+        try!(writeln!(f, "    negotiated_version: {:?}", self.negotiated_version));
         try!(writeln!(f, "    remote_addr: {:?}", self.remote_addr));
         try!(writeln!(f, "    local_addr: {:?}", self.local_addr));
 
@@ -67,9 +160,26 @@ impl<'a, 'b> Request<'a> {
     /// Create a request from an HttpRequest.
     ///
     /// This constructor consumes the HttpRequest.
+    ///
+    /// Fails if the request target can't be made into a `Url`, or if the
+    /// headers don't say unambiguously how long the body is: when both
+    /// `Content-Length` and `Transfer-Encoding` are present, when the
+    /// `Content-Length` values are invalid or differ, or when
+    /// `Transfer-Encoding` doesn't end with `chunked`. Iron answers such
+    /// requests with `400 Bad Request` and closes the connection, since
+    /// proxies could disagree about where they end.
+    ///
+    /// Transfer codings applied before `chunked`, such as `gzip` in
+    /// `Transfer-Encoding: gzip, chunked`, are undone as the body is read, in
+    /// reverse order. This is separate from any `Content-Encoding`, which is
+    /// left to `decoded_body`. Requests with a transfer coding which can't be
+    /// undone fail with an `UnsupportedTransferCoding`, which Iron answers
+    /// with `501 Not Implemented`.
     pub fn from_http(req: HttpRequest<'a, 'b>, local_addr: SocketAddr, protocol: &Protocol)
                      -> Result<Request<'a>, String> {
-        let (addr, method, headers, uri, _, reader) = req.deconstruct();
+        let (addr, method, headers, uri, version, reader) = req.deconstruct();
+        try!(length::check_framing(&headers));
+        let codings = try!(transfer::transfer_codings(&headers).map_err(|e| e.to_string()));
 
         let url = match uri {
             AbsoluteUri(ref url) => {
@@ -79,49 +189,303 @@ impl<'a, 'b> Request<'a> {
                 }
             },
 
-            AbsolutePath(ref path) => {
-                // Attempt to prepend the Host header (mandatory in HTTP/1.1)
-                let url_string = match headers.get::<headers::Host>() {
-                    Some(ref host) => {
-                        format!("{}://{}:{}{}", protocol.name(), host.hostname, local_addr.port(),
-                                path)
-                    },
-                    None => return Err("No host specified in request".into())
-                };
-
-                match Url::parse(&url_string) {
+            AbsolutePath(ref path) => try!(url_from_host(&headers, local_addr, protocol, path)),
+
+            // The asterisk form targets the server as a whole, so we use the root path.
+            Star => try!(url_from_host(&headers, local_addr, protocol, "/")),
+
+            Authority(ref authority) => {
+                match Url::parse(&format!("{}://{}/", protocol.name(), authority)) {
                     Ok(url) => url,
                     Err(e) => return Err(format!("Couldn't parse requested URL: {}", e))
                 }
-            },
-            _ => return Err("Unsupported request URI".into())
+            }
         };
 
         Ok(Request {
             url: url,
+            raw_uri: uri,
             remote_addr: addr,
             local_addr: local_addr,
             headers: headers,
-            body: Body::new(reader),
+            body: Body::new(reader).with_transfer_codings(&codings),
             method: method,
+            version: version,
+            secure: protocol.is_secure(),
+            // hyper 0.8 doesn't expose ALPN, so nothing is ever negotiated.
+            negotiated_version: None,
             extensions: TypeMap::new()
         })
     }
+
+    /// Read the whole request body into a `Vec<u8>`.
+    ///
+    /// Any limit set on the body is respected, and the body is left drained.
+    /// If the body has been buffered with `buffer_body`, this returns a copy
+    /// of the buffer instead.
+    pub fn get_body_bytes(&mut self) -> io::Result<Vec<u8>> {
+        if let Some(bytes) = self.buffered_body() {
+            return Ok(bytes.to_vec());
+        }
+
+        let mut bytes = Vec::new();
+        try!(self.body.read_to_end(&mut bytes));
+        Ok(bytes)
+    }
+
+    /// Read the whole request body into a `String`.
+    ///
+    /// Returns an `io::Error` of kind `InvalidData` if the body is not valid
+    /// UTF-8. Any limit set on the body is respected, and the body is left
+    /// drained.
+    pub fn get_body_string(&mut self) -> io::Result<String> {
+        let bytes = try!(self.get_body_bytes());
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// The request body, for streaming it into anything which takes a reader,
+    /// such as a parser.
+    ///
+    /// This is the same as `&mut req.body`. Reading the body as it arrives is
+    /// the way to handle bodies too large to hold in memory; any limit set on
+    /// the body still applies.
+    ///
+    /// ```
+    /// # use std::io::Read;
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// fn handler(req: &mut Request) -> IronResult<Response> {
+    ///     let mut hashed = 0u64;
+    ///     for byte in req.body_reader().bytes() {
+    ///         let byte = try!(byte.map_err(|e| IronError::new(e, status::BadRequest)));
+    ///         hashed = hashed.wrapping_mul(31).wrapping_add(byte as u64);
+    ///     }
+    ///     Ok(Response::with((status::Ok, hashed.to_string())))
+    /// }
+    /// ```
+    pub fn body_reader(&mut self) -> &mut Body<'a> {
+        &mut self.body
+    }
+
+    /// Take the body, leaving an empty one in its place, for code which needs
+    /// to own it.
+    ///
+    /// Iron can't discard what's left of a body it no longer has, so the
+    /// connection is closed after the response rather than kept alive. Use
+    /// `body_reader` unless ownership is really needed.
+    pub fn take_body(&mut self) -> Body<'a> {
+        mem::replace(&mut self.body, Body::taken())
+    }
+
+    /// The `User-Agent` header, if the client sent a valid one.
+    pub fn user_agent(&self) -> Option<&str> {
+        self.headers.get::<headers::UserAgent>().map(|user_agent| &*user_agent.0)
+    }
+
+    /// The `Referer` header, if the client sent a valid one.
+    pub fn referer(&self) -> Option<&str> {
+        self.headers.get::<headers::Referer>().map(|referer| &*referer.0)
+    }
+
+    /// Is this an `OPTIONS` request, asking which methods a resource allows?
+    ///
+    /// CORS preflight requests are `OPTIONS` requests too, with an
+    /// `Access-Control-Request-Method` header; `Cors` answers those.
+    pub fn is_options(&self) -> bool {
+        self.method == Method::Options
+    }
+
+    /// Was this request made by a script, as told by `X-Requested-With:
+    /// XMLHttpRequest`, compared without regard to case?
+    ///
+    /// Many JavaScript libraries send this header, so it can be used to
+    /// answer with a fragment of a page rather than the whole of it. It is
+    /// only a hint: `fetch` doesn't send it, and any client can, so never
+    /// rely on it for security.
+    pub fn is_ajax(&self) -> bool {
+        self.headers.get_raw("X-Requested-With").map_or(false, |values| {
+            values.iter().any(|value| {
+                String::from_utf8_lossy(value).trim().eq_ignore_ascii_case("XMLHttpRequest")
+            })
+        })
+    }
+
+    /// The path of the request target exactly as the client sent it, without
+    /// the query string, such as for checking a signature over it.
+    ///
+    /// `url` is normalized as it is parsed: dot segments are resolved and
+    /// some characters are encoded or decoded, so its path may not match the
+    /// bytes on the wire. This is taken from `raw_uri` instead.
+    ///
+    /// Returns `None` unless the target is in origin form (`AbsolutePath`),
+    /// the form clients use to talk to servers directly. hyper has already
+    /// parsed targets in absolute form, so their exact bytes aren't kept.
+    pub fn raw_path(&self) -> Option<&str> {
+        match self.raw_uri {
+            AbsolutePath(ref target) => Some(target.splitn(2, '?').next().unwrap()),
+            _ => None
+        }
+    }
+
+    /// Build the absolute URL of `path` on this server, such as for the
+    /// `Location` of a redirect.
+    ///
+    /// `path` may be absolute, like `/login?next=%2F`, or relative to the
+    /// requested path, like `edit` or `../list`, and is resolved the way a
+    /// browser would. It is always treated as a path, so an input like
+    /// `//example.com` can't send the client elsewhere. Inputs with a scheme,
+    /// like `https://example.com`, aren't paths at all, and give `None`.
+    ///
+    /// The scheme, host and port are those given by `scheme`, `host` and
+    /// `port`. As with those, only set `trust_forwarded` when every request
+    /// arrives through a proxy which sets the forwarded headers, or clients
+    /// could point the URL at any host.
+    pub fn url_for(&self, path: &str, trust_forwarded: bool) -> Option<Url> {
+        let before_path = path.split(|c| c == '/' || c == '?' || c == '#').next().unwrap();
+        if before_path.contains(':') {
+            return None;
+        }
+
+        let path = if path.starts_with('/') {
+            format!("/{}", path.trim_left_matches('/'))
+        } else {
+            format!("./{}", path)
+        };
+
+        let mut base = self.url.clone();
+        base.scheme = self.scheme(trust_forwarded).to_owned();
+        if let Some(host) = self.host(trust_forwarded).and_then(|host| Host::parse(host).ok()) {
+            base.host = host;
+        }
+        base.port = self.port(trust_forwarded).unwrap_or(base.port);
+        base.username = None;
+        base.password = None;
+        base.query = None;
+        base.fragment = None;
+
+        // `path` can't change the scheme or host, so the joined URL converts back.
+        base.into_generic_url().join(&path).ok()
+            .and_then(|joined| Url::from_generic_url(joined).ok())
+    }
+}
+
+// Build the full URL of a request from its path and Host header.
+fn url_from_host(headers: &Headers, local_addr: SocketAddr, protocol: &Protocol, path: &str)
+                 -> Result<Url, String> {
+    // Attempt to prepend the Host header (mandatory in HTTP/1.1)
+    let url_string = match headers.get::<headers::Host>() {
+        // Unix sockets have no port, so use the one the client asked for.
+        Some(ref host) if local_addr.port() == 0 => match host.port {
+            Some(port) => format!("{}://{}:{}{}", protocol.name(), host.hostname, port, path),
+            None => format!("{}://{}{}", protocol.name(), host.hostname, path)
+        },
+        Some(ref host) => {
+            format!("{}://{}:{}{}", protocol.name(), host.hostname, local_addr.port(), path)
+        },
+        None => return Err("No host specified in request".into())
+    };
+
+    match Url::parse(&url_string) {
+        Ok(url) => Ok(url),
+        Err(e) => Err(format!("Couldn't parse requested URL: {}", e))
+    }
 }
 
 /// The body of an Iron request,
+///
+/// `Body` implements `BufRead` as well as `Read`, so it can be read line by
+/// line. Lines count towards the body's limit as they are read, so with a
+/// limit set a single huge line fails with `LimitExceeded` instead of
+/// exhausting memory.
 #[derive(Debug)]
-pub struct Body<'a>(Box<HttpReader<&'a mut Read>>);
+pub struct Body<'a> {
+    reader: Box<HttpReader<Source<'a>>>,
+
+    // The maximum number of bytes which may be read, if any.
+    limit: Option<u64>,
+
+    // The number of bytes read from `reader` so far.
+    read: u64,
+
+    // Bytes read from `reader` by `peek` which have not been consumed yet.
+    peeked: Vec<u8>,
+
+    // The trailers of a chunked body, empty until its end has been read.
+    trailers: Headers,
+
+    // Undoes the transfer codings applied before `chunked`, if there are any.
+    transfer: Option<transfer::TransferDecoder>,
+    trailers_read: bool,
+
+    // Whether this stands in for a body taken by `Request::take_body`, which
+    // may have left the connection anywhere.
+    taken: bool
+}
+
+// Where the bytes of a body come from.
+enum Source<'a> {
+    // The client's connection, whose read timeout can be changed.
+    Connection(&'a mut ConnectionRead),
+    // Any other reader, such as one given to `Body::from_reader`.
+    Reader(&'a mut Read)
+}
+
+trait ConnectionRead: Read {
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()>;
+
+    // Bytes read from the connection which haven't been consumed yet.
+    fn buffered(&self) -> &[u8];
+
+    fn try_clone_stream(&self) -> io::Result<Box<NetworkStream + Send>>;
+
+    fn tls_info(&self) -> Option<TlsInfo>;
+}
+
+impl<'a> ConnectionRead for buffer::BufReader<&'a mut NetworkStream> {
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_read_timeout(dur)
+    }
+
+    fn buffered(&self) -> &[u8] {
+        self.get_buf()
+    }
+
+    fn try_clone_stream(&self) -> io::Result<Box<NetworkStream + Send>> {
+        upgrade::clone_stream(&**self.get_ref())
+    }
+
+    fn tls_info(&self) -> Option<TlsInfo> {
+        tls::stream_info(&**self.get_ref())
+    }
+}
+
+impl<'a> Read for Source<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Source::Connection(ref mut connection) => connection.read(buf),
+            Source::Reader(ref mut reader) => reader.read(buf)
+        }
+    }
+}
 
 impl<'a> Body<'a> {
     /// Create a new reader for use in an Iron request from a hyper HttpReader.
-    pub fn new<'b>(reader: HttpReader<&'a mut buffer::BufReader<&'b mut NetworkStream>>) -> Body<'a> {
-        let transmuted: Box<HttpReader<&mut Read>> = unsafe {
-            transmute(box reader)
+    pub fn new<'b>(reader: HttpReader<&'a mut buffer::BufReader<&'b mut NetworkStream>>)
+                   -> Body<'a> {
+        let reader = match reader {
+            HttpReader::SizedReader(r, len) => {
+                HttpReader::SizedReader(Source::Connection(r), len)
+            },
+            HttpReader::ChunkedReader(r, len) => {
+                HttpReader::ChunkedReader(Source::Connection(r), len)
+            },
+            HttpReader::EofReader(r) => HttpReader::EofReader(Source::Connection(r)),
+            HttpReader::EmptyReader(r) => HttpReader::EmptyReader(Source::Connection(r))
         };
-        Body(transmuted)
+        Body::from_http_reader(Box::new(reader))
     }
     pub fn from_reader(reader: &'a mut Read, len: Option<u64>, chunked: bool) -> Body<'a> {
+        let reader = Source::Reader(reader);
         let http_reader = if len.is_some() && ! chunked {
             HttpReader::SizedReader(reader, len.unwrap())
         }
@@ -134,13 +498,283 @@ impl<'a> Body<'a> {
         else {
             HttpReader::EmptyReader(reader)
         };
-        Body(box http_reader)
+        Body::from_http_reader(Box::new(http_reader))
+    }
+
+    // Undo `codings`, as given by `transfer_codings`, as the body is read.
+    fn with_transfer_codings(mut self, codings: &[String]) -> Body<'a> {
+        self.transfer = transfer::TransferDecoder::new(codings);
+        self
+    }
+
+    fn from_http_reader(reader: Box<HttpReader<Source<'a>>>) -> Body<'a> {
+        Body { reader: reader, limit: None, read: 0, peeked: Vec::new(),
+               trailers: Headers::new(), trailers_read: false, transfer: None, taken: false }
+    }
+
+    // An empty body to stand in for one taken by `Request::take_body`.
+    fn taken() -> Body<'a> {
+        // `io::Empty` has no size, so leaking one allocates nothing.
+        let empty: &'a mut io::Empty = Box::leak(Box::new(io::empty()));
+        let mut body = Body::from_reader(empty, Some(0), false);
+        body.taken = true;
+        body
+    }
+
+    /// Cap the total number of bytes which can be read from this body.
+    ///
+    /// Once more than `max_bytes` would be read, `read` returns an `io::Error`
+    /// of kind `ErrorKind::Other` wrapping a `LimitExceeded`. The limit applies
+    /// to bytes already read, so it can be set after the body has been
+    /// partially consumed.
+    pub fn with_limit(mut self, max_bytes: u64) -> Body<'a> {
+        self.set_limit(max_bytes);
+        self
+    }
+
+    /// Cap the total number of bytes which can be read from this body in place.
+    ///
+    /// See `with_limit` for details.
+    pub fn set_limit(&mut self, max_bytes: u64) {
+        self.limit = Some(max_bytes);
+    }
+
+    /// The maximum number of bytes which can be read from this body, if any.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// The length of the body, if it is known without reading it.
+    ///
+    /// This is the `Content-Length` of the request, or `Some(0)` if it has
+    /// no body at all. It is `None` for chunked bodies, whose length is only
+    /// known once they have been read. The length is of the whole body, so it
+    /// doesn't change as the body is read.
+    pub fn content_length_hint(&self) -> Option<u64> {
+        match *self.reader {
+            HttpReader::SizedReader(_, remaining) => Some(remaining + self.read),
+            HttpReader::EmptyReader(_) => Some(0),
+            HttpReader::ChunkedReader(..) | HttpReader::EofReader(_) => None
+        }
+    }
+
+    /// Is the body known to be empty without reading it, such as for most
+    /// `GET` requests?
+    ///
+    /// A chunked body may turn out to be empty too, but this can't tell.
+    pub fn is_definitely_empty(&self) -> bool {
+        self.content_length_hint() == Some(0)
+    }
+
+    /// The number of bytes of this body consumed so far.
+    ///
+    /// Bytes which have been peeked or buffered by `BufRead` aren't counted
+    /// until they are consumed, though they do count towards the limit.
+    pub fn bytes_read(&self) -> u64 {
+        self.read - self.peeked.len() as u64
+    }
+
+    /// Look at up to the next `n` bytes of the body without consuming them.
+    ///
+    /// The bytes are buffered, so subsequent calls to `read` still return
+    /// them. The returned slice is shorter than `n` if the body ends first.
+    /// Peeked bytes count towards the body's limit.
+    pub fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        let mut peeked = mem::replace(&mut self.peeked, Vec::new());
+        let filled = self.fill(&mut peeked, n);
+        self.peeked = peeked;
+
+        try!(filled);
+        Ok(&self.peeked[..min(n, self.peeked.len())])
+    }
+
+    // Read and discard the rest of the body, so the next request on the
+    // connection starts in the right place. Returns `false` if more than
+    // `max` bytes are left, or reading them fails, in which case the
+    // connection can't be reused.
+    #[doc(hidden)]
+    pub fn drain(&mut self, max: u64) -> bool {
+        if self.taken { return false }
+        if let HttpReader::SizedReader(_, remaining) = *self.reader {
+            if remaining > max { return false }
+        }
+
+        // The limit is for the handler, so read around it.
+        let mut buf = [0; 8 * 1024];
+        let mut drained = 0;
+        loop {
+            let wanted = min(buf.len() as u64, (max - drained).saturating_add(1)) as usize;
+            match self.read_raw(&mut buf[..wanted]) {
+                Ok(0) => return true,
+                Ok(read) if drained + read as u64 > max => return false,
+                Ok(read) => drained += read as u64,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => return false
+            }
+        }
+    }
+
+    // Change the read timeout of the connection the body is read from.
+    //
+    // Returns `false` if the body isn't read from a connection.
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<bool> {
+        match *self.reader.get_ref() {
+            Source::Connection(ref connection) => connection.set_read_timeout(dur).map(|_| true),
+            Source::Reader(_) => Ok(false)
+        }
+    }
+
+    // Return bytes which have been read to the front of the body.
+    fn unread(&mut self, mut bytes: Vec<u8>) {
+        bytes.extend_from_slice(&self.peeked);
+        self.peeked = bytes;
+    }
+
+    // Read from the underlying reader until `buf` holds `n` bytes or the body ends.
+    fn fill(&mut self, buf: &mut Vec<u8>, n: usize) -> io::Result<()> {
+        while buf.len() < n {
+            let start = buf.len();
+            buf.resize(n, 0);
+
+            match self.read_limited(&mut buf[start..]) {
+                Ok(0) => { buf.truncate(start); break },
+                Ok(read) => buf.truncate(start + read),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => buf.truncate(start),
+                Err(e) => { buf.truncate(start); return Err(e) }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Read from the underlying reader, enforcing the limit.
+    fn read_limited(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() { return Ok(0) }
+
+        let buf = match self.limit {
+            Some(limit) if self.read >= limit => {
+                // Any further byte means the limit has been exceeded, so probe
+                // for one to tell a body ending exactly at the limit apart.
+                let mut probe = [0];
+                return match try!(self.read_reader(&mut probe)) {
+                    0 => Ok(0),
+                    _ => Err(io::Error::new(io::ErrorKind::Other, LimitExceeded { limit: limit }))
+                }
+            },
+            Some(limit) => {
+                let remaining = min(limit - self.read, buf.len() as u64) as usize;
+                &mut buf[..remaining]
+            },
+            None => buf
+        };
+
+        let read = try!(self.read_reader(buf));
+        self.read += read as u64;
+        Ok(read)
+    }
+
+    // Read from the underlying reader, undoing any transfer codings.
+    fn read_reader(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.transfer.is_none() { return self.read_raw(buf) }
+
+        let mut raw = [0; 8 * 1024];
+        loop {
+            if let Some(read) = try!(self.transfer.as_mut().unwrap().read(buf)) {
+                return Ok(read);
+            }
+
+            let read = try!(self.read_raw(&mut raw));
+            let decoder = self.transfer.as_mut().unwrap();
+            if read == 0 {
+                decoder.finish();
+            } else {
+                decoder.feed(&raw[..read]);
+            }
+        }
+    }
+
+    // Read from the underlying reader, and then the trailers once the end of
+    // a chunked body is reached.
+    fn read_raw(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = try!(timed_read(&mut self.reader, buf));
+
+        if read == 0 && !self.trailers_read {
+            if let HttpReader::ChunkedReader(_, Some(0)) = *self.reader {
+                self.trailers = try!(trailers::read_trailers(self.reader.get_mut()));
+                self.trailers_read = true;
+            }
+        }
+
+        Ok(read)
+    }
+}
+
+// Read from a connection, reporting an elapsed read timeout as `TimedOut`.
+//
+// Sockets report timeouts as `WouldBlock` on some platforms and `TimedOut` on others.
+fn timed_read<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    match reader.read(buf) {
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out reading the request body"))
+        },
+        result => result
     }
 }
 
 impl<'a> Read for Body<'a> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(buf)
+        if self.peeked.is_empty() {
+            return self.read_limited(buf);
+        }
+
+        let read = min(buf.len(), self.peeked.len());
+        buf[..read].copy_from_slice(&self.peeked[..read]);
+        self.peeked.drain(..read);
+        Ok(read)
+    }
+}
+
+// How much `fill_buf` reads from the connection at once.
+const BUF_SIZE: usize = 8 * 1024;
+
+impl<'a> BufRead for Body<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.peeked.is_empty() {
+            let mut buf = mem::replace(&mut self.peeked, Vec::new());
+            buf.resize(BUF_SIZE, 0);
+
+            let read = self.read_limited(&mut buf);
+            buf.truncate(*read.as_ref().unwrap_or(&0));
+            self.peeked = buf;
+            try!(read);
+        }
+
+        Ok(&self.peeked)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = min(amt, self.peeked.len());
+        self.peeked.drain(..amt);
+    }
+}
+
+/// The error wrapped by the `io::Error` returned when reading more than a
+/// `Body`'s configured limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// The limit which was exceeded, in bytes.
+    pub limit: u64
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Request body exceeded the limit of {} bytes", self.limit)
+    }
+}
+
+impl StdError for LimitExceeded {
+    fn description(&self) -> &str {
+        "Request body exceeded the configured limit"
     }
 }
 
@@ -157,3 +791,220 @@ impl<'a> Extensible for Request<'a> {
 
 impl<'a> Plugin for Request<'a> {}
 impl<'a> Set for Request<'a> {}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, BufRead, Read};
+
+    use {method, test, Url};
+
+    use super::{Body, LimitExceeded, RequestUri};
+
+    #[test]
+    fn test_raw_path() {
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, &[], |req| {
+            req.raw_uri = RequestUri::AbsolutePath("/a/../b%2Fc/%7e?x=%20".to_owned());
+            assert_eq!(req.raw_path(), Some("/a/../b%2Fc/%7e"));
+
+            req.raw_uri = RequestUri::Star;
+            assert_eq!(req.raw_path(), None);
+        });
+    }
+
+    #[test]
+    fn test_is_ajax() {
+        let is_ajax = |value: Option<&str>| {
+            let headers: Vec<_> = value.into_iter()
+                .map(|value| ("X-Requested-With", value)).collect();
+            test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                       &headers, |req| req.is_ajax())
+        };
+
+        assert!(is_ajax(Some("XMLHttpRequest")));
+        assert!(is_ajax(Some("xmlhttprequest")));
+        assert!(!is_ajax(Some("com.example.app")));
+        assert!(!is_ajax(None));
+    }
+
+    #[test]
+    fn test_url_for() {
+        let url = Url::parse("http://localhost:3000/users/1?x=y").unwrap();
+        test::request_with_headers(method::Get, url, &[], |req| {
+            let url_for = |path| req.url_for(path, false).map(|url| url.to_string());
+            assert_eq!(url_for("/login?next=%2F").unwrap(), "http://localhost:3000/login?next=%2F");
+            assert_eq!(url_for("2").unwrap(), "http://localhost:3000/users/2");
+            assert_eq!(url_for("../list").unwrap(), "http://localhost:3000/list");
+            assert_eq!(url_for("//evil.com/x").unwrap(), "http://localhost:3000/evil.com/x");
+            assert_eq!(url_for("edit?from=a:b").unwrap(),
+                       "http://localhost:3000/users/edit?from=a:b");
+            assert_eq!(url_for("https://evil.com"), None);
+            assert_eq!(url_for("javascript:alert(1)"), None);
+        });
+    }
+
+    #[test]
+    fn test_url_for_forwarded() {
+        let url = Url::parse("http://localhost:3000/users/1").unwrap();
+        let headers = [("X-Forwarded-Proto", "https"), ("X-Forwarded-Host", "example.com")];
+        test::request_with_headers(method::Get, url, &headers, |req| {
+            assert_eq!(req.url_for("2", true).unwrap().to_string(),
+                       "https://example.com:443/users/2");
+            assert_eq!(req.url_for("2", false).unwrap().to_string(),
+                       "http://localhost:3000/users/2");
+        });
+    }
+
+    #[test]
+    fn test_limit_allows_body_at_limit() {
+        let mut data: &[u8] = b"hello";
+        let mut body = Body::from_reader(&mut data, None, false).with_limit(5);
+
+        let mut out = String::new();
+        body.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "hello");
+        assert_eq!(body.bytes_read(), 5);
+    }
+
+    #[test]
+    fn test_limit_exceeded() {
+        let mut data: &[u8] = b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let mut body = Body::from_reader(&mut data, None, true).with_limit(5);
+
+        let err = body.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()),
+                   Some(&LimitExceeded { limit: 5 }));
+        assert_eq!(body.bytes_read(), 5);
+    }
+
+    #[test]
+    fn test_peek_does_not_consume() {
+        let mut data: &[u8] = b"{\"json\": true}";
+        let mut body = Body::from_reader(&mut data, None, false);
+
+        assert_eq!(body.peek(1).unwrap(), b"{");
+        assert_eq!(body.peek(3).unwrap(), b"{\"j");
+        assert_eq!(body.bytes_read(), 0);
+
+        let mut out = String::new();
+        body.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "{\"json\": true}");
+        assert_eq!(body.bytes_read(), 14);
+    }
+
+    #[test]
+    fn test_peek_past_end() {
+        let mut data: &[u8] = b"abc";
+        let mut body = Body::from_reader(&mut data, Some(3), false);
+
+        assert_eq!(body.peek(10).unwrap(), b"abc");
+
+        let mut buf = [0; 2];
+        body.read_exact(&mut buf).unwrap();
+        assert_eq!(body.peek(10).unwrap(), b"c");
+    }
+
+    #[test]
+    fn test_content_length_hint() {
+        let mut data: &[u8] = b"abc";
+        let mut body = Body::from_reader(&mut data, Some(3), false);
+        assert_eq!(body.content_length_hint(), Some(3));
+        body.peek(2).unwrap();
+        assert_eq!(body.content_length_hint(), Some(3));
+        assert!(!body.is_definitely_empty());
+
+        let mut data: &[u8] = b"";
+        let body = Body::from_reader(&mut data, Some(0), false);
+        assert!(body.is_definitely_empty());
+
+        let mut data: &[u8] = b"0\r\n\r\n";
+        let body = Body::from_reader(&mut data, None, true);
+        assert_eq!(body.content_length_hint(), None);
+        assert!(!body.is_definitely_empty());
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut data: &[u8] = b"abcdefGET / HTTP/1.1";
+        {
+            let mut body = Body::from_reader(&mut data, Some(6), false).with_limit(2);
+            let mut buf = [0; 2];
+            body.read_exact(&mut buf).unwrap();
+            assert!(body.drain(4));
+        }
+        assert_eq!(data, b"GET / HTTP/1.1");
+
+        let mut data: &[u8] = b"abcdef";
+        assert!(!Body::from_reader(&mut data, Some(6), false).drain(5));
+        let mut data: &[u8] = b"3\r\nabc\r\n3\r\ndef\r\n0\r\n\r\n";
+        assert!(!Body::from_reader(&mut data, None, true).drain(5));
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let mut data: &[u8] = b"{\"a\": 1}\n{\"b\": 2}\n";
+        let body = Body::from_reader(&mut data, None, false);
+
+        let lines = body.lines().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(lines, vec!["{\"a\": 1}", "{\"b\": 2}"]);
+    }
+
+    #[test]
+    fn test_long_line_exceeds_limit() {
+        let long = vec![b'x'; 100];
+        let mut data = &long[..];
+        let mut body = Body::from_reader(&mut data, None, false).with_limit(10);
+
+        let mut line = String::new();
+        let err = body.read_line(&mut line).unwrap_err();
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()),
+                   Some(&LimitExceeded { limit: 10 }));
+        assert_eq!(body.bytes_read(), 10);
+    }
+
+    #[test]
+    fn test_timeout_is_timed_out() {
+        struct Stalled;
+
+        impl Read for Stalled {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::WouldBlock, "Resource temporarily unavailable"))
+            }
+        }
+
+        let mut reader = Stalled;
+        let mut body = Body::from_reader(&mut reader, Some(10), false);
+        assert_eq!(body.read(&mut [0; 10]).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_limit_after_partial_read() {
+        let mut data: &[u8] = b"hello world";
+        let mut body = Body::from_reader(&mut data, None, false);
+
+        let mut buf = [0; 6];
+        body.read_exact(&mut buf).unwrap();
+        body.set_limit(8);
+
+        let mut rest = vec![];
+        assert!(body.read_to_end(&mut rest).is_err());
+        assert_eq!(rest, b"wo");
+        assert_eq!(body.bytes_read(), 8);
+    }
+
+    #[test]
+    fn test_take_body() {
+        let mut data: &[u8] = b"hello world";
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    None, Body::from_reader(&mut data, Some(11), false));
+
+        let mut taken = String::new();
+        req.take_body().read_to_string(&mut taken).unwrap();
+        assert_eq!(taken, "hello world");
+
+        // The connection can't be reused, as Iron can't tell where it was left.
+        assert_eq!(req.body_reader().read(&mut [0; 10]).unwrap(), 0);
+        assert!(!req.body.drain(1024));
+    }
+}
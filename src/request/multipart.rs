@@ -0,0 +1,387 @@
+//! Parsing `multipart/form-data` bodies, such as file uploads.
+
+use std::cmp::min;
+use std::io::{self, Read};
+
+use mime::{Attr, Mime, TopLevel, SubLevel};
+
+use super::Request;
+use headers;
+
+// The most bytes read from the body at once.
+const BUF_SIZE: usize = 8 * 1024;
+
+// The most bytes of headers a part can have.
+const MAX_HEADERS_SIZE: usize = 8 * 1024;
+
+/// A reader of the parts of a `multipart/form-data` body, returned by
+/// `Request::multipart`.
+///
+/// Each call to `next_field` gives the next field, which borrows the
+/// `Multipart` while it is read:
+///
+/// ```no_run
+/// # use std::fs::File;
+/// # use std::io;
+/// # use iron::prelude::*;
+/// fn save_uploads(req: &mut Request) -> io::Result<()> {
+///     let mut multipart = try!(req.multipart());
+///     while let Some(mut field) = try!(multipart.next_field()) {
+///         if field.filename.is_some() {
+///             let mut file = try!(File::create(format!("uploads/{}", field.name)));
+///             try!(io::copy(&mut field, &mut file));
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct Multipart<'r> {
+    body: &'r mut (Read + 'r),
+    // `\r\n--` and the boundary, which ends each part.
+    delimiter: Vec<u8>,
+    // Bytes read from the body but not yet parsed or returned.
+    buf: Vec<u8>,
+    state: State
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // In the preamble or the data of a part.
+    Data,
+    // Just after a delimiter, before the part's headers.
+    Delimiter,
+    // After the final delimiter.
+    Done
+}
+
+/// A field of a `multipart/form-data` body.
+///
+/// A `Field` is a `Read`er of its data, which is streamed from the body
+/// rather than buffered. Any data which isn't read is skipped by the next
+/// call to `Multipart::next_field`.
+pub struct Field<'m, 'r: 'm> {
+    /// The name of the form field.
+    pub name: String,
+
+    /// The name of the uploaded file, for file fields.
+    pub filename: Option<String>,
+
+    /// The `Content-Type` of the field, if it has one. Fields without one are
+    /// `text/plain`.
+    pub content_type: Option<Mime>,
+
+    multipart: &'m mut Multipart<'r>
+}
+
+impl<'a> Request<'a> {
+    /// Read the body as `multipart/form-data`.
+    ///
+    /// If the request isn't `multipart/form-data` with a boundary, the body is
+    /// left unread and an `io::Error` of kind `InvalidInput` is returned.
+    /// Malformed bodies fail with `InvalidData` when the bad part is reached.
+    ///
+    /// Any limit set on `body` applies to the whole body, so it limits the
+    /// total size of all fields.
+    pub fn multipart(&mut self) -> io::Result<Multipart> {
+        let boundary = match self.headers.get::<headers::ContentType>() {
+            Some(&headers::ContentType(Mime(TopLevel::Multipart, SubLevel::FormData,
+                                            ref params))) => {
+                params.iter().find(|&&(ref attr, _)| *attr == Attr::Boundary)
+                    .map(|&(_, ref value)| value.as_str().to_owned())
+            },
+            _ => None
+        };
+
+        match boundary {
+            Some(ref boundary) if !boundary.is_empty() && boundary.len() <= 70 => {
+                Ok(Multipart::new(&mut self.body, boundary))
+            },
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                    "Expected a multipart/form-data body with a boundary"))
+        }
+    }
+}
+
+impl<'r> Multipart<'r> {
+    fn new(body: &'r mut (Read + 'r), boundary: &str) -> Multipart<'r> {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend(boundary.as_bytes());
+
+        Multipart {
+            body: body,
+            delimiter: delimiter,
+            // The first delimiter may start the body, without a line break.
+            buf: b"\r\n".to_vec(),
+            state: State::Data
+        }
+    }
+
+    /// The next field of the body, or `None` once all have been read.
+    pub fn next_field<'m>(&'m mut self) -> io::Result<Option<Field<'m, 'r>>> {
+        // Skip the preamble or the rest of the last field.
+        let mut skipped = [0; 1024];
+        while self.state == State::Data {
+            try!(self.read_data(&mut skipped));
+        }
+
+        if self.state == State::Done { return Ok(None) }
+
+        while self.buf.len() < 2 {
+            try!(self.fill_or_fail());
+        }
+        if self.buf.starts_with(b"--") {
+            self.state = State::Done;
+            return Ok(None);
+        }
+
+        // The delimiter's line may have trailing whitespace, and is followed
+        // by any headers and a blank line.
+        let (line_end, headers_end) = loop {
+            if let Some(line_end) = find(&self.buf, b"\r\n") {
+                if let Some(headers_end) = find(&self.buf[line_end..], b"\r\n\r\n") {
+                    break (line_end, line_end + headers_end);
+                }
+            }
+
+            if self.buf.len() > MAX_HEADERS_SIZE {
+                return Err(invalid_data("Multipart headers are too long"));
+            }
+            try!(self.fill_or_fail());
+        };
+
+        if self.buf[..line_end].iter().any(|&b| b != b' ' && b != b'\t') {
+            return Err(invalid_data("Missing line break after multipart boundary"));
+        }
+
+        let mut name = None;
+        let mut filename = None;
+        let mut content_type = None;
+        if headers_end > line_end {
+            let headers = String::from_utf8_lossy(&self.buf[line_end + 2..headers_end])
+                .into_owned();
+            for header in headers.split("\r\n") {
+                let colon = match header.find(':') {
+                    Some(colon) => colon,
+                    None => return Err(invalid_data("Malformed multipart header"))
+                };
+                let value = header[colon + 1..].trim();
+
+                match &*header[..colon].trim().to_ascii_lowercase() {
+                    "content-disposition" => {
+                        let (kind, params) = parse_params(value);
+                        if kind != "form-data" { continue }
+                        for (key, value) in params {
+                            match &*key {
+                                "name" => name = Some(value),
+                                "filename" => filename = Some(value),
+                                _ => ()
+                            }
+                        }
+                    },
+                    "content-type" => content_type = value.parse().ok(),
+                    _ => ()
+                }
+            }
+        }
+
+        self.buf.drain(..headers_end + 4);
+        self.state = State::Data;
+
+        match name {
+            Some(name) => Ok(Some(Field {
+                name: name,
+                filename: filename,
+                content_type: content_type,
+                multipart: self
+            })),
+            None => Err(invalid_data("Multipart part has no form-data name"))
+        }
+    }
+
+    // Read data up to the next delimiter, moving past it once it's reached.
+    fn read_data(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.state != State::Data { return Ok(0) }
+
+            if let Some(pos) = find(&self.buf, &self.delimiter) {
+                if pos == 0 {
+                    self.buf.drain(..self.delimiter.len());
+                    self.state = State::Delimiter;
+                    return Ok(0);
+                }
+                return Ok(self.take(out, pos));
+            }
+
+            // The end of the buffer may be the start of a delimiter.
+            let safe = self.buf.len().saturating_sub(self.delimiter.len() - 1);
+            if safe > 0 {
+                return Ok(self.take(out, safe));
+            }
+
+            try!(self.fill_or_fail());
+        }
+    }
+
+    fn take(&mut self, out: &mut [u8], available: usize) -> usize {
+        let n = min(out.len(), available);
+        out[..n].copy_from_slice(&self.buf[..n]);
+        self.buf.drain(..n);
+        n
+    }
+
+    // Read more of the body into `buf`, failing at the end of the body since
+    // the final delimiter hasn't been reached.
+    fn fill_or_fail(&mut self) -> io::Result<()> {
+        let start = self.buf.len();
+        self.buf.resize(start + BUF_SIZE, 0);
+        let read = match self.body.read(&mut self.buf[start..]) {
+            Ok(read) => read,
+            Err(e) => {
+                self.buf.truncate(start);
+                return Err(e);
+            }
+        };
+        self.buf.truncate(start + read);
+
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "Multipart body ended before its final boundary"));
+        }
+        Ok(())
+    }
+}
+
+impl<'m, 'r> Read for Field<'m, 'r> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.multipart.read_data(buf)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Split a header like `form-data; name="field"; filename="a.txt"` into its
+// lowercased first value and its parameters, unquoting quoted values.
+fn parse_params(header: &str) -> (String, Vec<(String, String)>) {
+    let end = header.find(';').unwrap_or(header.len());
+    let kind = header[..end].trim().to_ascii_lowercase();
+    let mut rest = &header[end..];
+
+    let mut params = Vec::new();
+    while rest.starts_with(';') {
+        rest = rest[1..].trim();
+        let eq = match rest.find('=') {
+            Some(eq) => eq,
+            None => break
+        };
+        let key = rest[..eq].trim().to_ascii_lowercase();
+        rest = rest[eq + 1..].trim();
+
+        let mut value = String::new();
+        if rest.starts_with('"') {
+            let mut escaped = false;
+            let mut end = rest.len();
+            for (i, c) in rest.char_indices().skip(1) {
+                if escaped {
+                    value.push(c);
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    end = i + 1;
+                    break;
+                } else {
+                    value.push(c);
+                }
+            }
+            rest = &rest[end..];
+            rest = &rest[rest.find(';').unwrap_or(rest.len())..];
+        } else {
+            let end = rest.find(';').unwrap_or(rest.len());
+            value.push_str(rest[..end].trim());
+            rest = &rest[end..];
+        }
+
+        params.push((key, value));
+    }
+
+    (kind, params)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read};
+
+    use request::Body;
+    use {method, test, Headers, Url};
+
+    const BODY: &'static [u8] = b"preamble\r\n\
+        --XyZ \r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Hello\r\n--XyZ\r\n\
+        content-disposition: form-data; name=\"upload\"; filename=\"a \\\"b\\\".txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        line one\r\n--XyNot\r\n\r\n--Xy\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"skipped\"\r\n\
+        \r\n\
+        never read\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    fn fields(content_type: &str, body: &[u8])
+              -> io::Result<Vec<(String, Option<String>, String)>> {
+        let mut headers = Headers::new();
+        headers.set_raw("Content-Type", vec![content_type.as_bytes().to_vec()]);
+
+        let mut reader = body;
+        let len = Some(body.len() as u64);
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    Some(headers), Body::from_reader(&mut reader, len, false));
+
+        let mut multipart = try!(req.multipart());
+        let mut fields = Vec::new();
+        while let Some(mut field) = try!(multipart.next_field()) {
+            let mut data = String::new();
+            if field.name != "skipped" {
+                // Read a byte at a time, so delimiters are split across reads.
+                let mut byte = [0];
+                while try!(field.read(&mut byte)) == 1 {
+                    data.push(byte[0] as char);
+                }
+            }
+            fields.push((field.name.clone(), field.filename.clone(), data));
+        }
+        Ok(fields)
+    }
+
+    #[test]
+    fn test_multipart() {
+        assert_eq!(fields("multipart/form-data; boundary=XyZ", BODY).unwrap(), vec![
+            ("title".to_owned(), None, "Hello".to_owned()),
+            ("upload".to_owned(), Some("a \"b\".txt".to_owned()),
+             "line one\r\n--XyNot\r\n\r\n--Xy".to_owned()),
+            ("skipped".to_owned(), None, "".to_owned())
+        ]);
+    }
+
+    #[test]
+    fn test_malformed_multipart() {
+        let err = fields("multipart/form-data", BODY).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = fields("multipart/form-data; boundary=\"XyZ\"", &BODY[..100]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let body = b"--XyZ\r\nContent-Type: text/plain\r\n\r\nNo name\r\n--XyZ--";
+        let err = fields("multipart/form-data; boundary=XyZ", body).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
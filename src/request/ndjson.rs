@@ -0,0 +1,276 @@
+//! Reading newline-delimited JSON request bodies one record at a time,
+//! behind the `serde` feature.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use super::{Body, Request, LimitExceeded};
+
+// The longest line read by default, in bytes.
+const MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+/// The ways in which reading a record from an `NdjsonStream` can fail.
+///
+/// After `LineTooLong` or `Deserialize` the stream carries on with the next
+/// line, so the caller can choose to skip bad records. After `TooLarge` or
+/// `Io` the body can't be read any further, and the stream ends.
+#[derive(Debug)]
+pub enum NdjsonError {
+    /// A line is longer than the stream's maximum line length. The rest of it
+    /// is skipped.
+    LineTooLong {
+        /// The number of the line, counting from 1.
+        line: usize,
+        /// The maximum line length, in bytes.
+        limit: usize
+    },
+
+    /// The body is larger than the limit set on it.
+    TooLarge(LimitExceeded),
+
+    /// The body could not be read.
+    Io(io::Error),
+
+    /// A line is not valid JSON, or does not match the expected type.
+    Deserialize {
+        /// The number of the line, counting from 1.
+        line: usize,
+        /// The error deserializing it.
+        error: serde_json::Error
+    }
+}
+
+impl fmt::Display for NdjsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NdjsonError::LineTooLong { line, limit } => {
+                write!(f, "Line {} is longer than the limit of {} bytes", line, limit)
+            },
+            NdjsonError::TooLarge(ref err) => fmt::Display::fmt(err, f),
+            NdjsonError::Io(ref err) => write!(f, "Error reading the request body: {}", err),
+            NdjsonError::Deserialize { line, ref error } => {
+                write!(f, "Invalid JSON on line {}: {}", line, error)
+            }
+        }
+    }
+}
+
+impl StdError for NdjsonError {
+    fn description(&self) -> &str {
+        match *self {
+            NdjsonError::LineTooLong { .. } => "Line of the request body is too long",
+            NdjsonError::TooLarge(_) => "Request body exceeded the configured limit",
+            NdjsonError::Io(_) => "Error reading the request body",
+            NdjsonError::Deserialize { .. } => "Invalid JSON line"
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            NdjsonError::TooLarge(ref err) => Some(err),
+            NdjsonError::Io(ref err) => Some(err),
+            NdjsonError::Deserialize { ref error, .. } => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for NdjsonError {
+    fn from(err: io::Error) -> NdjsonError {
+        let limit = err.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()).cloned();
+        match limit {
+            Some(limit) => NdjsonError::TooLarge(limit),
+            None => NdjsonError::Io(err)
+        }
+    }
+}
+
+/// An iterator over the records of a newline-delimited JSON body, returned
+/// by `Request::ndjson_stream`.
+pub struct NdjsonStream<'r, 'a: 'r, T> {
+    body: &'r mut Body<'a>,
+    max_line_length: usize,
+    // The number of lines read so far.
+    line: usize,
+    done: bool,
+    marker: PhantomData<fn() -> T>
+}
+
+impl<'r, 'a, T> NdjsonStream<'r, 'a, T> {
+    /// Set the longest line which is deserialized, in bytes. Longer lines
+    /// give `NdjsonError::LineTooLong`. The default is 1 MiB.
+    pub fn max_line_length(mut self, bytes: usize) -> NdjsonStream<'r, 'a, T> {
+        self.max_line_length = bytes;
+        self
+    }
+
+    // Read the next line without its ending, or `None` at the end of the
+    // body. A line which is too long is read to its end and given as `None`.
+    fn read_line(&mut self) -> io::Result<Option<Option<Vec<u8>>>> {
+        let mut line = Vec::new();
+        let mut too_long = false;
+        let mut empty = true;
+
+        loop {
+            let (used, done) = {
+                let buf = match self.body.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e)
+                };
+                if buf.is_empty() {
+                    if empty { return Ok(None) }
+                    break;
+                }
+
+                let (part, used, done) = match buf.iter().position(|&b| b == b'\n') {
+                    Some(end) => (&buf[..end], end + 1, true),
+                    None => (buf, buf.len(), false)
+                };
+
+                if too_long || line.len() + part.len() > self.max_line_length {
+                    too_long = true;
+                    line.clear();
+                } else {
+                    line.extend_from_slice(part);
+                }
+                (used, done)
+            };
+
+            self.body.consume(used);
+            empty = false;
+            if done { break }
+        }
+
+        if line.last() == Some(&b'\r') { line.pop(); }
+        Ok(Some(if too_long { None } else { Some(line) }))
+    }
+}
+
+impl<'r, 'a, T: DeserializeOwned> Iterator for NdjsonStream<'r, 'a, T> {
+    type Item = Result<T, NdjsonError>;
+
+    fn next(&mut self) -> Option<Result<T, NdjsonError>> {
+        while !self.done {
+            let line = match self.read_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(NdjsonError::from(err)));
+                }
+            };
+            self.line += 1;
+
+            let line = match line {
+                Some(line) => line,
+                None => {
+                    return Some(Err(NdjsonError::LineTooLong {
+                        line: self.line,
+                        limit: self.max_line_length
+                    }));
+                }
+            };
+
+            if line.iter().all(|b| b" \t\r".contains(b)) { continue }
+
+            return Some(serde_json::from_slice(&line).map_err(|error| {
+                NdjsonError::Deserialize { line: self.line, error: error }
+            }));
+        }
+
+        self.done = true;
+        None
+    }
+}
+
+impl<'a> Request<'a> {
+    /// Read the body as newline-delimited JSON, deserializing one record per
+    /// line as the stream is iterated, without reading the whole body first.
+    ///
+    /// Blank lines are skipped. A line which can't be deserialized gives an
+    /// error, after which the stream carries on with the next line, so the
+    /// caller decides whether to stop. Lines are limited to 1 MiB by default,
+    /// which can be changed with `NdjsonStream::max_line_length`, and any
+    /// limit set on the body applies to the stream as a whole.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// fn ingest(req: &mut Request) -> IronResult<Response> {
+    ///     let mut count = 0;
+    ///     for record in req.ndjson_stream::<Vec<u32>>() {
+    ///         let record = try!(record.map_err(|e| IronError::new(e, status::BadRequest)));
+    ///         count += record.len();
+    ///     }
+    ///     Ok(Response::with((status::Ok, count.to_string())))
+    /// }
+    /// ```
+    pub fn ndjson_stream<'r, T: DeserializeOwned>(&'r mut self) -> NdjsonStream<'r, 'a, T> {
+        NdjsonStream {
+            body: &mut self.body,
+            max_line_length: MAX_LINE_LENGTH,
+            line: 0,
+            done: false,
+            marker: PhantomData
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use request::Body;
+    use {method, test, Url};
+
+    use super::NdjsonError;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Point { x: i32, y: i32 }
+
+    fn ndjson(body: &str, limit: Option<u64>, max_line: usize) -> Vec<Result<Point, NdjsonError>> {
+        let mut reader = body.as_bytes();
+        let mut body = Body::from_reader(&mut reader, Some(body.len() as u64), false);
+        if let Some(limit) = limit { body.set_limit(limit) }
+
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    None, body);
+        let records = req.ndjson_stream().max_line_length(max_line).collect();
+        records
+    }
+
+    #[test]
+    fn test_ndjson_stream() {
+        let body = "{\"x\": 1, \"y\": 2}\r\n\n  \n{\"x\": 3}\n{\"x\": 5, \"y\": 6, \"z\": 0}\n\
+                    {\"x\": 7, \"y\": 8}";
+        let records = ndjson(body, None, 20);
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].as_ref().unwrap(), &Point { x: 1, y: 2 });
+        match records[1] {
+            Err(NdjsonError::Deserialize { line: 4, .. }) => (),
+            ref other => panic!("Unexpected result: {:?}", other)
+        }
+        match records[2] {
+            Err(NdjsonError::LineTooLong { line: 5, limit: 20 }) => (),
+            ref other => panic!("Unexpected result: {:?}", other)
+        }
+        assert_eq!(records[3].as_ref().unwrap(), &Point { x: 7, y: 8 });
+
+        assert!(ndjson("\n\n", None, 20).is_empty());
+    }
+
+    #[test]
+    fn test_ndjson_stream_limit() {
+        let records = ndjson("{\"x\": 1, \"y\": 2}\n{\"x\": 3, \"y\": 4}\n", Some(20), 100);
+        assert_eq!(records.len(), 2);
+        assert!(records[0].is_ok());
+        match records[1] {
+            Err(NdjsonError::TooLarge(limit)) => assert_eq!(limit.limit, 20),
+            ref other => panic!("Unexpected result: {:?}", other)
+        }
+    }
+}
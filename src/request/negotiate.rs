@@ -0,0 +1,183 @@
+//! Content negotiation using the `Accept` header.
+
+use mime::{Mime, TopLevel, SubLevel};
+
+use super::Request;
+
+impl<'a> Request<'a> {
+    /// Choose the offered media type the client prefers, according to its
+    /// `Accept` header.
+    ///
+    /// Each offered type is given the quality (`q=`) of the most specific
+    /// media range which matches it, so `text/html;q=0.5, */*` prefers
+    /// anything to HTML. Wildcards such as `*/*` and `text/*` are supported,
+    /// and a quality of `0` means "not acceptable". Ties go to whichever type
+    /// comes first in `offered`.
+    ///
+    /// Returns `None` if the client accepts none of the offered types, in
+    /// which case a `406 Not Acceptable` response is appropriate. A request
+    /// without a usable `Accept` header accepts anything, so gets the first
+    /// offered type.
+    ///
+    /// The response then depends on `Accept`, so should be marked with
+    /// `Response::vary_on(&["Accept"])`.
+    pub fn negotiate(&self, offered: &[Mime]) -> Option<Mime> {
+        let accepted = self.headers.get_raw("Accept").map(|lines| {
+            lines.iter()
+                .filter_map(|line| ::std::str::from_utf8(line).ok())
+                .flat_map(|line| line.split(','))
+                .filter_map(parse_media_range)
+                .collect::<Vec<_>>()
+        }).unwrap_or_else(Vec::new);
+
+        if accepted.is_empty() { return offered.first().cloned() }
+
+        let mut best: Option<(&Mime, u16)> = None;
+        for mime in offered {
+            let quality = match quality_of(mime, &accepted) {
+                Some(quality) if quality > 0 => quality,
+                _ => continue
+            };
+
+            if best.map_or(true, |(_, best_quality)| quality > best_quality) {
+                best = Some((mime, quality));
+            }
+        }
+
+        best.map(|(mime, _)| mime.clone())
+    }
+}
+
+// A media range from an `Accept` header, with its quality in thousandths.
+type MediaRange = (Mime, u16);
+
+// Parse one entry of an `Accept` header, such as `text/html;level=1;q=0.7`.
+//
+// Parameters after `q` are accept extensions, which are ignored.
+fn parse_media_range(entry: &str) -> Option<MediaRange> {
+    let mut parts = entry.split(';').map(|part| part.trim());
+    let mut range = parts.next().unwrap().to_owned();
+    let mut quality = 1000;
+
+    for part in parts {
+        if part.starts_with("q=") || part.starts_with("Q=") {
+            quality = match parse_quality(&part[2..]) {
+                Some(quality) => quality,
+                None => return None
+            };
+            break;
+        }
+
+        range.push(';');
+        range.push_str(part);
+    }
+
+    range.parse::<Mime>().ok().map(|mime| (mime, quality))
+}
+
+// Parse a quality value, such as the `0.8` of `q=0.8`, into thousandths.
+//
+// As RFC 7231 has it, this is `0` or `1` with at most three decimals, and
+// not over `1`, so `1.0000` and `.5` are refused like any other bad value.
+pub fn parse_quality(value: &str) -> Option<u16> {
+    let (whole, decimals) = match value.find('.') {
+        Some(dot) => (&value[..dot], &value[dot + 1..]),
+        None => (value, "")
+    };
+    if decimals.len() > 3 || !decimals.bytes().all(|b| b.is_ascii_digit()) { return None }
+
+    let thousandths = format!("{:0<3}", decimals).parse::<u16>().unwrap();
+    match whole {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None
+    }
+}
+
+// The quality of the most specific range matching `mime`, if any do.
+fn quality_of(mime: &Mime, accepted: &[MediaRange]) -> Option<u16> {
+    accepted.iter()
+        .filter_map(|&(ref range, quality)| specificity(range, mime).map(|s| (s, quality)))
+        .max_by_key(|&(specificity, _)| specificity)
+        .map(|(_, quality)| quality)
+}
+
+// How specific `range` is, if it matches `mime`.
+fn specificity(range: &Mime, mime: &Mime) -> Option<u8> {
+    let Mime(ref top, ref sub, ref params) = *range;
+
+    if *top == TopLevel::Star { return Some(0) }
+    if *top != mime.0 { return None }
+    if *sub == SubLevel::Star { return Some(1) }
+    if *sub != mime.1 { return None }
+    if params.is_empty() { return Some(2) }
+
+    if params.iter().all(|param| mime.2.contains(param)) { Some(3) } else { None }
+}
+
+#[cfg(test)]
+mod test {
+    use mime::Mime;
+
+    use {method, test, Url};
+
+    use super::parse_quality;
+
+    fn negotiate(accept: Option<&str>, offered: &[&str]) -> Option<String> {
+        let headers: Vec<_> = accept.into_iter().map(|value| ("Accept", value)).collect();
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        test::request_with_headers(method::Get, url, &headers, |req| {
+            let offered = offered.iter().map(|m| m.parse::<Mime>().unwrap()).collect::<Vec<_>>();
+            req.negotiate(&offered).map(|m| m.to_string())
+        })
+    }
+
+    #[test]
+    fn test_quality_values() {
+        assert_eq!(negotiate(Some("application/json;q=0.5, text/html"),
+                             &["application/json", "text/html"]),
+                   Some("text/html".to_owned()));
+        assert_eq!(negotiate(Some("text/html;q=0.5, */*"), &["text/html", "application/json"]),
+                   Some("application/json".to_owned()));
+        assert_eq!(negotiate(Some("text/html;q=1.0000, application/json;q=0.5"),
+                             &["text/html", "application/json"]),
+                   Some("application/json".to_owned()));
+    }
+
+    #[test]
+    fn test_parse_quality() {
+        assert_eq!(parse_quality("0"), Some(0));
+        assert_eq!(parse_quality("0.8"), Some(800));
+        assert_eq!(parse_quality("0.125"), Some(125));
+        assert_eq!(parse_quality("1"), Some(1000));
+        assert_eq!(parse_quality("1.000"), Some(1000));
+        assert_eq!(parse_quality("1.0000"), None);
+        assert_eq!(parse_quality("1.5"), None);
+        assert_eq!(parse_quality(".5"), None);
+        assert_eq!(parse_quality("0.1234"), None);
+        assert_eq!(parse_quality("-0"), None);
+        assert_eq!(parse_quality("0.x"), None);
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert_eq!(negotiate(Some("text/*"), &["application/json", "text/plain"]),
+                   Some("text/plain".to_owned()));
+        assert_eq!(negotiate(Some("*/*"), &["application/json", "text/plain"]),
+                   Some("application/json".to_owned()));
+    }
+
+    #[test]
+    fn test_not_acceptable() {
+        assert_eq!(negotiate(Some("image/png"), &["application/json"]), None);
+        assert_eq!(negotiate(Some("*/*, application/json;q=0"), &["application/json"]), None);
+        assert_eq!(negotiate(Some("text/html"), &[]), None);
+    }
+
+    #[test]
+    fn test_missing_or_malformed_accept() {
+        assert_eq!(negotiate(None, &["application/json", "text/html"]),
+                   Some("application/json".to_owned()));
+        assert_eq!(negotiate(Some("nonsense;q"), &["text/html"]), Some("text/html".to_owned()));
+    }
+}
@@ -0,0 +1,98 @@
+//! Stripping a path prefix before passing a request on.
+
+use typemap::Key;
+
+use super::Request;
+
+// The prefixes stripped from a request's path so far, joined together.
+struct StrippedPrefix;
+
+impl Key for StrippedPrefix { type Value = String; }
+
+impl<'a> Request<'a> {
+    /// Remove `prefix`, such as `/api/v1`, from the front of the URL path, so
+    /// a handler mounted there sees the rest of the path.
+    ///
+    /// The prefix matches whole path segments: `/api` matches `/api` and
+    /// `/api/users` but not `/apis`. Segments are compared as they were sent,
+    /// so percent-encoded characters in `prefix` only match the same encoding.
+    /// A trailing slash on `prefix` is ignored, so `/` (or `""`) is no prefix
+    /// at all: it matches every path and strips nothing. Stripping the whole
+    /// path leaves `/`.
+    ///
+    /// Returns `None`, leaving the request unchanged, if the path doesn't
+    /// start with `prefix`, so a dispatcher can try its next route. Only
+    /// `url` is changed; `raw_uri` still holds the path the client sent.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use iron::{method, test, Url};
+    /// # use iron::request::Body;
+    /// # let mut reader = io::empty();
+    /// # let url = Url::parse("http://localhost:3000/api/users").unwrap();
+    /// # let mut req = test::request(method::Get, url, None,
+    /// #                             Body::from_reader(&mut reader, Some(0), false));
+    /// assert!(req.with_stripped_prefix("/admin").is_none());
+    /// assert!(req.with_stripped_prefix("/api").is_some());
+    ///
+    /// assert_eq!(req.url.path, vec!["users"]);
+    /// assert_eq!(req.stripped_prefix(), Some("/api"));
+    /// ```
+    pub fn with_stripped_prefix(&mut self, prefix: &str) -> Option<()> {
+        let segments = prefix.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+        if segments.is_empty() { return Some(()) }
+
+        if segments.len() > self.url.path.len() ||
+           !segments.iter().zip(&self.url.path).all(|(prefix, segment)| prefix == segment) {
+            return None;
+        }
+
+        let rest = self.url.path.split_off(segments.len());
+        self.url.path = if rest.is_empty() { vec![String::new()] } else { rest };
+
+        let stripped = self.extensions.entry::<StrippedPrefix>().or_insert_with(String::new);
+        for segment in segments {
+            stripped.push('/');
+            stripped.push_str(segment);
+        }
+
+        Some(())
+    }
+
+    /// The prefixes removed from the path by `with_stripped_prefix`, such as
+    /// `/api/v1`, if any.
+    pub fn stripped_prefix(&self) -> Option<&str> {
+        self.extensions.get::<StrippedPrefix>().map(|prefix| &**prefix)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {method, test, Url};
+
+    #[test]
+    fn test_strip_prefix() {
+        let strip = |url: &str, prefixes: &[&str]| {
+            test::request_with_headers(method::Get, Url::parse(url).unwrap(), &[], |req| {
+                let matched = prefixes.iter()
+                    .all(|prefix| req.with_stripped_prefix(prefix).is_some());
+                (matched, req.url.path.join("/"), req.stripped_prefix().map(|p| p.to_owned()))
+            })
+        };
+
+        assert_eq!(strip("http://localhost:3000/api/v1/users/", &["/api", "v1/"]),
+                   (true, "users/".to_owned(), Some("/api/v1".to_owned())));
+        assert_eq!(strip("http://localhost:3000/api", &["/api"]),
+                   (true, "".to_owned(), Some("/api".to_owned())));
+        assert_eq!(strip("http://localhost:3000/apis", &["/api"]),
+                   (false, "apis".to_owned(), None));
+        assert_eq!(strip("http://localhost:3000/a%20b/c", &["/a b"]),
+                   (false, "a%20b/c".to_owned(), None));
+        assert_eq!(strip("http://localhost:3000/a%20b/c", &["/a%20b"]),
+                   (true, "c".to_owned(), Some("/a%20b".to_owned())));
+        assert_eq!(strip("http://localhost:3000/api/users", &["/"]),
+                   (true, "api/users".to_owned(), None));
+        assert_eq!(strip("http://localhost:3000/api/users", &["/", "/api", ""]),
+                   (true, "users".to_owned(), Some("/api".to_owned())));
+    }
+}
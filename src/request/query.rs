@@ -0,0 +1,149 @@
+//! Parsing of query strings into a multimap of parameters.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+
+use url::form_urlencoded;
+
+use typemap::Key;
+
+use super::Request;
+
+/// Decoded `application/x-www-form-urlencoded` parameters, such as those in a
+/// query string.
+///
+/// Keys may appear more than once; all of their values are kept, in the order
+/// in which they appeared.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QueryMap {
+    params: HashMap<String, Vec<String>>
+}
+
+impl QueryMap {
+    /// Parse percent-encoded `key=value` pairs separated by `&`.
+    ///
+    /// A `+` in the input is decoded as a space, as in HTML forms.
+    pub fn parse(input: &str) -> QueryMap {
+        QueryMap::parse_bytes(input.as_bytes())
+    }
+
+    /// Like `parse`, but for raw bytes such as a request body.
+    ///
+    /// Invalid UTF-8 in the decoded keys and values is replaced with `U+FFFD`.
+    pub fn parse_bytes(input: &[u8]) -> QueryMap {
+        let mut map = QueryMap::default();
+
+        for (key, value) in form_urlencoded::parse(input) {
+            map.params.entry(key).or_insert_with(Vec::new).push(value);
+        }
+
+        map
+    }
+
+    /// The first value given for `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).first().map(|value| &**value)
+    }
+
+    /// All values given for `key`, in order.
+    ///
+    /// Returns an empty slice if the key is absent.
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.params.get(key).map(|values| &**values).unwrap_or(&[])
+    }
+
+    /// Was `key` given at all?
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.params.contains_key(key)
+    }
+
+    /// The number of distinct keys.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Are there no parameters?
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// Iterate over each distinct key and all of its values, in arbitrary order.
+    pub fn iter(&self) -> Iter<String, Vec<String>> {
+        self.params.iter()
+    }
+}
+
+// Caches the parsed query string of a request, along with the query string
+// it was parsed from.
+struct QueryKey;
+
+impl Key for QueryKey { type Value = (Option<String>, QueryMap); }
+
+impl<'a> Request<'a> {
+    /// The parameters in the query string of the requested URL.
+    ///
+    /// The query string is parsed on first use and cached in `extensions`,
+    /// so later calls within the same chain are cheap; this is why it takes
+    /// `&mut self`. If the query of `url` is changed, such as by middleware
+    /// rewriting the URL, it is parsed again.
+    pub fn query(&mut self) -> &QueryMap {
+        let stale = match self.extensions.get::<QueryKey>() {
+            Some(&(ref parsed, _)) => *parsed != self.url.query,
+            None => true
+        };
+
+        if stale {
+            let map = self.url.query.as_ref()
+                .map_or_else(QueryMap::default, |q| QueryMap::parse(q));
+            self.extensions.insert::<QueryKey>((self.url.query.clone(), map));
+        }
+
+        &self.extensions.get::<QueryKey>().unwrap().1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {method, test, Url};
+
+    use super::QueryMap;
+
+    #[test]
+    fn test_repeated_keys() {
+        let map = QueryMap::parse("tag=a&id=1&tag=b");
+        assert_eq!(map.get("tag"), Some("a"));
+        assert_eq!(map.get_all("tag"), &["a".to_owned(), "b".to_owned()]);
+        assert_eq!(map.get("id"), Some("1"));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_decoding() {
+        let map = QueryMap::parse("q=hello+world&path=%2Ffoo%26bar&empty=");
+        assert_eq!(map.get("q"), Some("hello world"));
+        assert_eq!(map.get("path"), Some("/foo&bar"));
+        assert_eq!(map.get("empty"), Some(""));
+    }
+
+    #[test]
+    fn test_missing_key() {
+        let map = QueryMap::parse("");
+        assert!(map.is_empty());
+        assert_eq!(map.get("missing"), None);
+        assert!(map.get_all("missing").is_empty());
+    }
+
+    #[test]
+    fn test_query_follows_url() {
+        let url = Url::parse("http://localhost:3000/?a=1").unwrap();
+        test::request_with_headers(method::Get, url, &[], |req| {
+            assert_eq!(req.query().get("a"), Some("1"));
+
+            req.url.query = Some("a=2".to_owned());
+            assert_eq!(req.query().get("a"), Some("2"));
+
+            req.url.query = None;
+            assert!(req.query().is_empty());
+        });
+    }
+}
@@ -0,0 +1,180 @@
+//! Parsing `Range` headers for serving partial content.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use method;
+
+use super::Request;
+
+/// The error returned by `Request::range` when none of the requested ranges
+/// overlap the resource, which should be answered with `416 Range Not
+/// Satisfiable` and `Content-Range: bytes */<total length>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeNotSatisfiable {
+    /// The length of the resource the ranges were checked against.
+    pub total_len: u64
+}
+
+impl fmt::Display for RangeNotSatisfiable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No requested range is within {} bytes", self.total_len)
+    }
+}
+
+impl StdError for RangeNotSatisfiable {
+    fn description(&self) -> &str {
+        "Range not satisfiable"
+    }
+}
+
+impl<'a> Request<'a> {
+    /// The byte ranges asked for by the `Range` header of a `GET` request,
+    /// for a resource of `total_len` bytes.
+    ///
+    /// Ranges are inclusive `(first, last)` pairs in the order they were
+    /// asked for, with `last` clamped to the end of the resource. Open ended
+    /// ranges such as `bytes=500-` run to the end, and suffix ranges such as
+    /// `bytes=-500` are the last 500 bytes. Ranges which start past the end
+    /// are dropped; if none are left, `RangeNotSatisfiable` is returned.
+    ///
+    /// Returns `None` if there is no `Range` header, or if it is invalid, has
+    /// no ranges or isn't in bytes, in which case RFC 7233 says to send the
+    /// whole resource.
+    /// The same goes when `if_range_matches` is false.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use iron::{method, test, Headers, Url};
+    /// # use iron::request::Body;
+    /// # let mut headers = Headers::new();
+    /// # headers.set_raw("Range", vec![b"bytes=0-99, 950-, -10".to_vec()]);
+    /// # let mut reader = io::empty();
+    /// # let req = test::request(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+    /// #                         Some(headers), Body::from_reader(&mut reader, Some(0), false));
+    /// // Range: bytes=0-99, 950-, -10
+    /// assert_eq!(req.range(1000), Some(Ok(vec![(0, 99), (950, 999), (990, 999)])));
+    /// ```
+    pub fn range(&self, total_len: u64) -> Option<Result<Vec<(u64, u64)>, RangeNotSatisfiable>> {
+        if self.method != method::Get { return None }
+
+        let header = match self.headers.get_raw("Range") {
+            Some(lines) if lines.len() == 1 => String::from_utf8_lossy(&lines[0]).into_owned(),
+            _ => return None
+        };
+
+        let header = header.trim();
+        let specs = match header.find('=') {
+            Some(eq) if header[..eq].trim().eq_ignore_ascii_case("bytes") => &header[eq + 1..],
+            _ => return None
+        };
+
+        // A header without any ranges, like `bytes=`, is invalid too.
+        if specs.split(',').all(|spec| spec.trim().is_empty()) { return None }
+
+        let mut ranges = Vec::new();
+        for spec in specs.split(',').map(|spec| spec.trim()).filter(|spec| !spec.is_empty()) {
+            match parse_spec(spec) {
+                Some(spec) => ranges.extend(spec.clamp(total_len)),
+                None => return None
+            }
+        }
+
+        if ranges.is_empty() {
+            Some(Err(RangeNotSatisfiable { total_len: total_len }))
+        } else {
+            Some(Ok(ranges))
+        }
+    }
+}
+
+enum RangeSpec {
+    // first-last, or first- to the end.
+    FromTo(u64, Option<u64>),
+    // -length, the last bytes.
+    Suffix(u64)
+}
+
+impl RangeSpec {
+    fn clamp(&self, total_len: u64) -> Option<(u64, u64)> {
+        match *self {
+            RangeSpec::FromTo(first, last) if first < total_len => {
+                Some((first, last.map_or(total_len - 1, |last| last.min(total_len - 1))))
+            },
+            RangeSpec::Suffix(len) if len > 0 && total_len > 0 => {
+                Some((total_len - len.min(total_len), total_len - 1))
+            },
+            _ => None
+        }
+    }
+}
+
+fn parse_spec(spec: &str) -> Option<RangeSpec> {
+    let dash = match spec.find('-') {
+        Some(dash) => dash,
+        None => return None
+    };
+    let (first, last) = (spec[..dash].trim(), spec[dash + 1..].trim());
+
+    let number = |s: &str| {
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse::<u64>().ok()
+        } else {
+            None
+        }
+    };
+
+    if first.is_empty() {
+        return number(last).map(RangeSpec::Suffix);
+    }
+
+    let first = match number(first) {
+        Some(first) => first,
+        None => return None
+    };
+    if last.is_empty() {
+        return Some(RangeSpec::FromTo(first, None));
+    }
+
+    match number(last) {
+        Some(last) if last >= first => Some(RangeSpec::FromTo(first, Some(last))),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use method::Method;
+    use {method, test, Url};
+
+    use super::RangeNotSatisfiable;
+
+    fn range(method: Method, header: &str, total_len: u64)
+             -> Option<Result<Vec<(u64, u64)>, RangeNotSatisfiable>> {
+        test::request_with_headers(method, Url::parse("http://localhost:3000/").unwrap(),
+                                   &[("Range", header)], |req| req.range(total_len))
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(range(method::Get, "bytes=500-", 1000), Some(Ok(vec![(500, 999)])));
+        assert_eq!(range(method::Get, "bytes=-500", 100), Some(Ok(vec![(0, 99)])));
+        assert_eq!(range(method::Get, "Bytes = 0-1999", 1000), Some(Ok(vec![(0, 999)])));
+        assert_eq!(range(method::Get, "bytes=2000-, 0-0", 1000), Some(Ok(vec![(0, 0)])));
+        assert_eq!(range(method::Get, "bytes=1000-1001, -0", 1000),
+                   Some(Err(RangeNotSatisfiable { total_len: 1000 })));
+        assert_eq!(range(method::Get, "bytes=-1", 0),
+                   Some(Err(RangeNotSatisfiable { total_len: 0 })));
+    }
+
+    #[test]
+    fn test_ignored_range() {
+        assert_eq!(range(method::Get, "bytes=5-4", 1000), None);
+        assert_eq!(range(method::Get, "bytes=0-1, x", 1000), None);
+        assert_eq!(range(method::Get, "bytes=+1-2", 1000), None);
+        assert_eq!(range(method::Get, "items=0-1", 1000), None);
+        assert_eq!(range(method::Get, "bytes=", 1000), None);
+        assert_eq!(range(method::Get, "bytes= , ", 1000), None);
+        assert_eq!(range(method::Post, "bytes=0-1", 1000), None);
+    }
+}
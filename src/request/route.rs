@@ -0,0 +1,60 @@
+//! Well-known `extensions` entries for routers.
+
+use std::collections::HashMap;
+
+use typemap::Key;
+
+use super::Request;
+
+/// The `extensions` key under which routers store the parameters they
+/// matched in the path, such as `id` for `/users/:id`.
+///
+/// Storing them here, rather than under a router's own key, lets handlers and
+/// middleware read them with `Request::route_param` whichever router is used.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use std::io;
+/// # use iron::{method, test, Url};
+/// # use iron::request::{Body, MatchedRoute, RouteParams};
+/// # let mut reader = io::empty();
+/// # let mut req = test::request(method::Get, Url::parse("http://localhost:3000/users/7").unwrap(),
+/// #                             None, Body::from_reader(&mut reader, Some(0), false));
+/// // In a router, once `/users/:id` has matched:
+/// let mut params = HashMap::new();
+/// params.insert("id".to_owned(), "7".to_owned());
+/// req.extensions.insert::<RouteParams>(params);
+/// req.extensions.insert::<MatchedRoute>("/users/:id".to_owned());
+///
+/// // In a handler or middleware:
+/// assert_eq!(req.route_param("id"), Some("7"));
+/// assert_eq!(req.matched_route(), Some("/users/:id"));
+/// ```
+pub struct RouteParams;
+
+impl Key for RouteParams { type Value = HashMap<String, String>; }
+
+/// The `extensions` key under which routers store the pattern of the route
+/// which matched, such as `/users/:id`.
+///
+/// Unlike the path itself, the pattern groups requests for the same route,
+/// which is what logging and metrics usually want.
+pub struct MatchedRoute;
+
+impl Key for MatchedRoute { type Value = String; }
+
+impl<'a> Request<'a> {
+    /// The route parameter called `name`, as stored under `RouteParams` by a
+    /// router.
+    pub fn route_param(&self, name: &str) -> Option<&str> {
+        self.extensions.get::<RouteParams>()
+            .and_then(|params| params.get(name))
+            .map(|value| &**value)
+    }
+
+    /// The pattern of the route which matched, as stored under `MatchedRoute`
+    /// by a router.
+    pub fn matched_route(&self) -> Option<&str> {
+        self.extensions.get::<MatchedRoute>().map(|route| &**route)
+    }
+}
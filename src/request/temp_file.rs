@@ -0,0 +1,65 @@
+//! Spooling request bodies to disk.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use tempfile::NamedTempFile;
+
+use super::{Request, LimitExceeded};
+
+impl<'a> Request<'a> {
+    /// Copy the whole body into a new temporary file, for uploads too large
+    /// to hold in memory.
+    ///
+    /// The file is created in the system's temporary directory, readable and
+    /// writable only by the current user, and is returned rewound to its
+    /// start. It is deleted when the `NamedTempFile` is dropped, unless it is
+    /// kept first with `NamedTempFile::persist`, such as to move it into
+    /// place.
+    ///
+    /// Fails with an `io::Error` of kind `ErrorKind::Other` wrapping a
+    /// `LimitExceeded` if the body is longer than `max` bytes, in which case
+    /// the file is deleted. Any limit set on `body` still applies too.
+    pub fn body_to_temp_file(&mut self, max: u64) -> io::Result<NamedTempFile> {
+        let mut file = try!(NamedTempFile::new());
+
+        // Copy one byte past `max` to tell a body of exactly `max` bytes apart.
+        let copied = try!(io::copy(&mut (&mut self.body).take(max.saturating_add(1)), &mut file));
+        if copied > max {
+            return Err(io::Error::new(io::ErrorKind::Other, LimitExceeded { limit: max }));
+        }
+
+        try!(file.seek(SeekFrom::Start(0)));
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use request::{Body, LimitExceeded};
+    use {method, test, Url};
+
+    #[test]
+    fn test_body_to_temp_file() {
+        let mut reader = "hello world".as_bytes();
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    None, Body::from_reader(&mut reader, Some(11), false));
+
+        let mut file = req.body_to_temp_file(11).unwrap();
+        let mut body = String::new();
+        file.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "hello world");
+    }
+
+    #[test]
+    fn test_temp_file_too_large() {
+        let mut reader = "hello world".as_bytes();
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    None, Body::from_reader(&mut reader, Some(11), false));
+
+        let err = req.body_to_temp_file(5).unwrap_err();
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()),
+                   Some(&LimitExceeded { limit: 5 }));
+    }
+}
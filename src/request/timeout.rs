@@ -0,0 +1,119 @@
+//! Reading request bodies with a deadline between reads.
+
+use std::cmp::min;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{self, Read};
+use std::time::Duration;
+
+use super::{Request, LimitExceeded};
+
+/// The error wrapped by the `io::Error` returned when
+/// `Request::read_body_with_timeout` times out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyTimedOut {
+    /// The bytes of the body which were read before the client stalled.
+    pub partial: Vec<u8>
+}
+
+impl fmt::Display for BodyTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timed out reading the request body after {} bytes", self.partial.len())
+    }
+}
+
+impl StdError for BodyTimedOut {
+    fn description(&self) -> &str {
+        "Timed out reading the request body"
+    }
+}
+
+impl<'a> Request<'a> {
+    /// Read the whole body, giving up if the client sends nothing for
+    /// `timeout`.
+    ///
+    /// The timeout applies to each read rather than to the body as a whole,
+    /// so a slow upload succeeds as long as it keeps making progress. If it
+    /// stalls, this fails with an `io::Error` of kind `ErrorKind::TimedOut`
+    /// wrapping a `BodyTimedOut` which holds the bytes read so far.
+    ///
+    /// Fails with an `io::Error` of kind `ErrorKind::Other` wrapping a
+    /// `LimitExceeded` if the body is longer than `max` bytes. Any limit set
+    /// on `body` still applies too.
+    ///
+    /// The timeout replaces the connection's read timeout for the rest of the
+    /// request. It has no effect on bodies which aren't read from a
+    /// connection, such as those made with `Body::from_reader`, unless their
+    /// reader times out by itself.
+    pub fn read_body_with_timeout(&mut self, timeout: Duration, max: u64) -> io::Result<Vec<u8>> {
+        try!(self.body.set_read_timeout(Some(timeout)));
+
+        let mut bytes = Vec::new();
+        let mut buf = [0; 8 * 1024];
+        loop {
+            // Read one byte past `max` to tell a body of exactly `max` bytes apart.
+            let left = (max - bytes.len() as u64).saturating_add(1);
+            let wanted = min(buf.len() as u64, left) as usize;
+            match self.body.read(&mut buf[..wanted]) {
+                Ok(0) => return Ok(bytes),
+                Ok(read) if (bytes.len() + read) as u64 > max => {
+                    return Err(io::Error::new(io::ErrorKind::Other, LimitExceeded { limit: max }));
+                },
+                Ok(read) => bytes.extend_from_slice(&buf[..read]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                              BodyTimedOut { partial: bytes }));
+                },
+                Err(e) => return Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read};
+    use std::time::Duration;
+
+    use request::{Body, LimitExceeded};
+    use {method, test, Url};
+
+    use super::BodyTimedOut;
+
+    // Yields its data, then stalls.
+    struct Stalling(&'static [u8]);
+
+    impl Read for Stalling {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buf) {
+                Ok(0) => Err(io::Error::new(io::ErrorKind::WouldBlock,
+                                            "Resource temporarily unavailable")),
+                result => result
+            }
+        }
+    }
+
+    #[test]
+    fn test_stalled_body_times_out() {
+        let mut reader = Stalling(b"hello");
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    None, Body::from_reader(&mut reader, Some(10), false));
+
+        let err = req.read_body_with_timeout(Duration::from_secs(1), 100).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<BodyTimedOut>()),
+                   Some(&BodyTimedOut { partial: b"hello".to_vec() }));
+    }
+
+    #[test]
+    fn test_body_over_max() {
+        let mut reader: &[u8] = b"hello";
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    None, Body::from_reader(&mut reader, Some(5), false));
+
+        let err = req.read_body_with_timeout(Duration::from_secs(1), 4).unwrap_err();
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()),
+                   Some(&LimitExceeded { limit: 4 }));
+    }
+}
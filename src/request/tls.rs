@@ -0,0 +1,93 @@
+//! Details of the TLS session a request was made over.
+
+use hyper::net::NetworkStream;
+
+use super::{Request, Source};
+
+/// What was negotiated for a connection served by `Iron::https`, as returned
+/// by `Request::tls_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// The protocol version, such as `TLSv1.2`.
+    pub version: String,
+    /// The cipher suite, such as `ECDHE-RSA-AES128-GCM-SHA256`, in OpenSSL's
+    /// naming.
+    pub cipher: Option<String>,
+    /// The client's certificate, PEM encoded.
+    ///
+    /// Only the client's own certificate is available, not the intermediate
+    /// certificates it sent along with it. Clients are only asked for one,
+    /// which has then been verified, once `Iron::client_certificates` is set;
+    /// otherwise this is `None`.
+    pub peer_certificate: Option<String>,
+    /// The common name (`CN`) of the client certificate's subject.
+    pub peer_common_name: Option<String>,
+    /// The DNS names among the client certificate's subject alternative
+    /// names.
+    pub peer_dns_names: Vec<String>
+}
+
+impl<'a> Request<'a> {
+    /// The TLS session this request was made over, or `None` if it wasn't
+    /// served by `Iron::https`.
+    ///
+    /// This only describes the connection to Iron; a request which reached a
+    /// proxy over HTTPS and Iron over plain HTTP has none.
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        match *self.body.reader.get_ref() {
+            Source::Connection(ref connection) => connection.tls_info(),
+            Source::Reader(_) => None
+        }
+    }
+}
+
+// The TLS session of one of the kinds of connection the server accepts.
+#[cfg(feature = "ssl")]
+pub fn stream_info(stream: &NetworkStream) -> Option<TlsInfo> {
+    use hyper::net::HttpStream;
+    use openssl::nid::Nid;
+    use openssl::ssl::SslStream;
+
+    let ssl = match stream.downcast_ref::<SslStream<HttpStream>>() {
+        Some(stream) => stream.ssl(),
+        None => return None
+    };
+
+    let mut info = TlsInfo {
+        version: ssl.version().to_owned(),
+        cipher: ssl.get_current_cipher().map(|cipher| cipher.name().to_owned()),
+        peer_certificate: None,
+        peer_common_name: None,
+        peer_dns_names: Vec::new()
+    };
+
+    if let Some(cert) = ssl.peer_certificate() {
+        let mut pem = Vec::new();
+        info.peer_certificate = cert.write_pem(&mut pem).ok()
+            .and_then(|_| String::from_utf8(pem).ok());
+        info.peer_common_name = cert.subject_name().text_by_nid(Nid::CN).map(|cn| cn.to_string());
+        if let Some(names) = cert.subject_alt_names() {
+            info.peer_dns_names = names.iter()
+                .filter_map(|name| name.dnsname().map(|dns| dns.to_owned()))
+                .collect();
+        }
+    }
+
+    Some(info)
+}
+
+#[cfg(not(feature = "ssl"))]
+pub fn stream_info(_: &NetworkStream) -> Option<TlsInfo> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use {method, test, Url};
+
+    #[test]
+    fn test_tls_info() {
+        test::request_with_headers(method::Get, Url::parse("https://localhost:3000/").unwrap(), &[],
+                                   |req| assert_eq!(req.tls_info(), None));
+    }
+}
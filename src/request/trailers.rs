@@ -0,0 +1,181 @@
+//! Reading the trailers which end chunked request bodies.
+
+use std::io::{self, Read};
+use std::str;
+
+use Headers;
+
+use super::{Body, Request, timed_read};
+
+// The most bytes of trailers read before giving up.
+const MAX_TRAILERS_SIZE: usize = 8 * 1024;
+
+impl<'a> Body<'a> {
+    /// The trailers sent after a chunked body, once it has been read to the
+    /// end.
+    ///
+    /// Trailers are headers whose values are only known once the body has
+    /// been sent, such as a checksum of it. This is `None` until the end of
+    /// the body has been read, and always `None` for bodies which aren't
+    /// chunked. A chunked body without trailers has an empty set.
+    ///
+    /// Reading the body fails with an error of kind `InvalidData` if the
+    /// trailers are malformed or longer than 8 KiB.
+    pub fn trailers(&self) -> Option<&Headers> {
+        if self.trailers_read { Some(&self.trailers) } else { None }
+    }
+}
+
+impl<'a> Request<'a> {
+    /// The trailers sent after the body, such as a checksum of it to verify.
+    ///
+    /// Trailers are only known once the whole body has been read, so this is
+    /// empty until then, as it is for bodies which aren't chunked. They are
+    /// kept apart from `headers` rather than merged into them, since proxies
+    /// may not check them as they do headers. Use `body.trailers()` to tell
+    /// a body which had no trailers from one which hasn't been read yet.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// fn upload(req: &mut Request) -> IronResult<Response> {
+    ///     let body = try!(req.get_body_bytes()
+    ///                        .map_err(|e| IronError::new(e, status::BadRequest)));
+    ///     match req.trailers().get_raw("X-Checksum") {
+    ///         Some(checksum) => {
+    ///             // Check `body` against `checksum`...
+    /// #           let _ = (body, checksum);
+    ///             Ok(Response::with(status::Created))
+    ///         },
+    ///         None => Ok(Response::with((status::BadRequest, "Missing checksum")))
+    ///     }
+    /// }
+    /// ```
+    pub fn trailers(&self) -> &Headers {
+        &self.body.trailers
+    }
+}
+
+// Read the trailer section following the last chunk of a body, up to and
+// including the empty line which ends it.
+pub fn read_trailers<R: Read>(reader: &mut R) -> io::Result<Headers> {
+    let mut headers = Headers::new();
+    let mut total = 0;
+
+    loop {
+        let line = try!(read_line(reader, &mut total));
+        if line.is_empty() { return Ok(headers) }
+
+        let (name, value) = match line.iter().position(|&b| b == b':') {
+            Some(colon) => (&line[..colon], trim(&line[colon + 1..])),
+            None => return Err(invalid("Invalid line in chunked trailers"))
+        };
+
+        let name = match str::from_utf8(name) {
+            Ok(name) if !name.is_empty() && !name.contains(|c: char| c <= ' ' || c == '\x7F') => {
+                name.to_owned()
+            },
+            _ => return Err(invalid("Invalid name in chunked trailers"))
+        };
+
+        let mut values = headers.get_raw(&name).map_or_else(Vec::new, |values| values.to_vec());
+        values.push(value.to_vec());
+        headers.set_raw(name, values);
+    }
+}
+
+// Read a line ending in `\r\n` or `\n`, without its ending.
+fn read_line<R: Read>(reader: &mut R, total: &mut usize) -> io::Result<Vec<u8>> {
+    let mut line = Vec::new();
+    let mut byte = [0];
+
+    loop {
+        match timed_read(reader, &mut byte) {
+            Ok(0) => {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                          "Body ended in its chunked trailers"));
+            },
+            Ok(_) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        }
+
+        *total += 1;
+        if *total > MAX_TRAILERS_SIZE { return Err(invalid("Chunked trailers are too long")) }
+
+        match byte[0] {
+            b'\n' => {
+                if line.last() == Some(&b'\r') { line.pop(); }
+                return Ok(line);
+            },
+            b => line.push(b)
+        }
+    }
+}
+
+fn trim(value: &[u8]) -> &[u8] {
+    let is_space = |b: &u8| *b == b' ' || *b == b'\t';
+    let start = value.iter().position(|b| !is_space(b)).unwrap_or(value.len());
+    let end = value.iter().rposition(|b| !is_space(b)).map_or(start, |end| end + 1);
+    &value[start..end]
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read};
+
+    use request::Body;
+    use {method, test, Url};
+
+    #[test]
+    fn test_trailers() {
+        let mut data: &[u8] =
+            b"5\r\nhello\r\n0\r\nX-Checksum:  abc \r\nX-Count: 1\r\nx-count: 2\r\n\r\nGET";
+        {
+            let mut body = Body::from_reader(&mut data, None, true);
+            let mut out = String::new();
+            body.read_to_string(&mut out).unwrap();
+            assert_eq!(out, "hello");
+
+            let trailers = body.trailers().unwrap();
+            assert_eq!(trailers.get_raw("X-Checksum").unwrap(), &[b"abc".to_vec()][..]);
+            assert_eq!(trailers.get_raw("X-Count").unwrap(), &[b"1".to_vec(), b"2".to_vec()][..]);
+        }
+        assert_eq!(data, b"GET");
+
+        let mut data: &[u8] = b"0\r\n\r\n";
+        let mut body = Body::from_reader(&mut data, None, true);
+        assert!(body.trailers().is_none());
+        body.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(body.trailers().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_request_trailers() {
+        let mut data: &[u8] = b"2\r\nhi\r\n0\r\nX-Checksum: abc\r\n\r\n";
+        let mut req = test::request(method::Post, Url::parse("http://localhost:3000/").unwrap(),
+                                    None, Body::from_reader(&mut data, None, true));
+
+        assert_eq!(req.trailers().len(), 0);
+        assert_eq!(req.get_body_string().unwrap(), "hi");
+        assert_eq!(req.trailers().get_raw("X-Checksum").unwrap(), &[b"abc".to_vec()][..]);
+        assert!(req.headers.get_raw("X-Checksum").is_none());
+    }
+
+    #[test]
+    fn test_malformed_trailers() {
+        let mut data: &[u8] = b"0\r\nnot a header\r\n\r\n";
+        let mut body = Body::from_reader(&mut data, None, true);
+        let err = body.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut data: &[u8] = b"0\r\nX-Checksum: abc";
+        let mut body = Body::from_reader(&mut data, None, true);
+        let err = body.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
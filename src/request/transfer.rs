@@ -0,0 +1,294 @@
+//! Undoing transfer codings, such as `gzip`, applied to chunked request
+//! bodies before `chunked`.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+
+use flate2::write::{GzDecoder, ZlibDecoder};
+
+use Headers;
+
+// How much input each layer decompresses at once. A layer is only fed once
+// everything it produced before has been passed on, so however compressed the
+// body is, only what one step of each layer expands to is held at a time.
+const FEED_SIZE: usize = 512;
+
+// How many codings can be undone before `chunked`. Each layer holds its own
+// decompressor, so a request can't make the server set up any number of them.
+const MAX_CODINGS: usize = 4;
+
+/// The error for a request whose `Transfer-Encoding` uses a coding Iron can't
+/// undo, which is answered with `501 Not Implemented`.
+///
+/// `gzip` (or `x-gzip`) and `deflate` codings can be undone before
+/// `chunked`, stacked in any order up to four deep. A body compressed more
+/// times than that is refused, with `coding` the first one over the limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedTransferCoding {
+    /// The coding which can't be undone, such as `compress`.
+    pub coding: String
+}
+
+impl fmt::Display for UnsupportedTransferCoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unsupported Transfer-Encoding: {}", self.coding)
+    }
+}
+
+impl StdError for UnsupportedTransferCoding {
+    fn description(&self) -> &str {
+        "Unsupported Transfer-Encoding"
+    }
+}
+
+// The codings listed in `Transfer-Encoding` before the final `chunked`, in
+// the order they were applied.
+#[doc(hidden)]
+pub fn transfer_codings(headers: &Headers) -> Result<Vec<String>, UnsupportedTransferCoding> {
+    let mut codings = headers.get_raw("Transfer-Encoding").unwrap_or(&[]).iter()
+        .flat_map(|line| line.split(|&b| b == b','))
+        .map(|coding| String::from_utf8_lossy(coding).trim().to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty())
+        .collect::<Vec<_>>();
+
+    if codings.last().map(|coding| &**coding) == Some("chunked") { codings.pop(); }
+
+    for (i, coding) in codings.iter().enumerate() {
+        match &**coding {
+            "gzip" | "x-gzip" | "deflate" if i < MAX_CODINGS => (),
+            _ => return Err(UnsupportedTransferCoding { coding: coding.clone() })
+        }
+    }
+
+    Ok(codings)
+}
+
+// Decompresses a body as its chunks are read, undoing each transfer coding in
+// reverse order.
+#[derive(Debug)]
+pub struct TransferDecoder {
+    // One decoder per coding, starting with the last applied.
+    layers: Vec<Layer>,
+    // Decompressed bytes which haven't been read yet.
+    output: Vec<u8>,
+    // Whether the whole body has been read.
+    ended: bool
+}
+
+#[derive(Debug)]
+struct Layer {
+    codec: Codec,
+    // Bytes given to this layer which it hasn't decompressed yet.
+    input: Vec<u8>,
+    finished: bool
+}
+
+#[derive(Debug)]
+enum Codec {
+    Gzip(GzDecoder<Vec<u8>>),
+    Deflate(ZlibDecoder<Vec<u8>>)
+}
+
+impl Layer {
+    fn new(codec: Codec) -> Layer {
+        Layer { codec: codec, input: Vec::new(), finished: false }
+    }
+
+    // Decompress up to `FEED_SIZE` bytes of this layer's input.
+    fn step(&mut self) -> io::Result<()> {
+        let fed = self.input.len().min(FEED_SIZE);
+        let data = self.input.drain(..fed).collect::<Vec<_>>();
+        match self.codec {
+            Codec::Gzip(ref mut decoder) => decoder.write_all(&data),
+            Codec::Deflate(ref mut decoder) => decoder.write_all(&data)
+        }
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        try!(match self.codec {
+            Codec::Gzip(ref mut decoder) => decoder.try_finish(),
+            Codec::Deflate(ref mut decoder) => decoder.try_finish()
+        });
+        self.finished = true;
+        Ok(())
+    }
+
+    // Take the bytes this layer has decompressed so far.
+    fn take(&mut self) -> Vec<u8> {
+        let output = match self.codec {
+            Codec::Gzip(ref mut decoder) => decoder.get_mut(),
+            Codec::Deflate(ref mut decoder) => decoder.get_mut()
+        };
+        mem::replace(output, Vec::new())
+    }
+}
+
+impl TransferDecoder {
+    // A decoder for `codings` as given by `transfer_codings`, or `None` if
+    // there is nothing to undo.
+    pub fn new(codings: &[String]) -> Option<TransferDecoder> {
+        if codings.is_empty() { return None }
+
+        let layers = codings.iter().rev().map(|coding| match &**coding {
+            "deflate" => Layer::new(Codec::Deflate(ZlibDecoder::new(Vec::new()))),
+            _ => Layer::new(Codec::Gzip(GzDecoder::new(Vec::new())))
+        }).collect();
+
+        Some(TransferDecoder { layers: layers, output: Vec::new(), ended: false })
+    }
+
+    // Move decompressed bytes into `buf`, returning how many, if there are
+    // any, or `Some(0)` at the end of the body. `None` means more input is
+    // needed.
+    pub fn read(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        while self.output.len() < buf.len() && try!(self.step()) {}
+
+        if self.output.is_empty() {
+            let finished = self.layers.iter().all(|layer| layer.finished);
+            return Ok(if finished { Some(0) } else { None });
+        }
+
+        let read = buf.len().min(self.output.len());
+        buf[..read].copy_from_slice(&self.output[..read]);
+        self.output.drain(..read);
+        Ok(Some(read))
+    }
+
+    // Give the decoder more bytes read from the body.
+    pub fn feed(&mut self, input: &[u8]) {
+        self.layers[0].input.extend_from_slice(input);
+    }
+
+    // Note that the whole body has been read, so each layer can finish once
+    // it has decompressed the rest of its input. A body which was cut short
+    // then fails to read.
+    pub fn finish(&mut self) {
+        self.ended = true;
+    }
+
+    // Pass some bytes on through one layer, returning whether there were any.
+    //
+    // The layer nearest the output with input left goes first, so no layer
+    // is given more until the ones after it have decompressed what it gave
+    // them last time.
+    fn step(&mut self) -> io::Result<bool> {
+        let next = match self.layers.iter().rposition(|layer| !layer.input.is_empty()) {
+            Some(i) => { try!(self.layers[i].step()); i }
+            None => match self.layers.iter().position(|layer| !layer.finished) {
+                Some(i) if self.ended => { try!(self.layers[i].try_finish()); i }
+                _ => return Ok(false)
+            }
+        };
+
+        let data = self.layers[next].take();
+        match self.layers.get_mut(next + 1) {
+            Some(layer) => layer.input.extend_from_slice(&data),
+            None => self.output.extend_from_slice(&data)
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+
+    use flate2::Compression;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+
+    use request::Body;
+    use Headers;
+
+    use super::{transfer_codings, TransferDecoder, UnsupportedTransferCoding};
+
+    fn chunked(data: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for chunk in data.chunks(100) {
+            write!(body, "{:x}\r\n", chunk.len()).unwrap();
+            body.extend_from_slice(chunk);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"0\r\n\r\n");
+        body
+    }
+
+    fn codings(value: &str) -> Result<Vec<String>, UnsupportedTransferCoding> {
+        let mut headers = Headers::new();
+        headers.set_raw("Transfer-Encoding", vec![value.as_bytes().to_vec()]);
+        transfer_codings(&headers)
+    }
+
+    #[test]
+    fn test_transfer_codings() {
+        assert_eq!(codings("chunked").unwrap(), Vec::<String>::new());
+        assert_eq!(codings("GZIP, chunked").unwrap(), ["gzip"]);
+        assert_eq!(codings("gzip, deflate, chunked").unwrap(), ["gzip", "deflate"]);
+        assert_eq!(codings("gzip, deflate, gzip, deflate, chunked").unwrap().len(), 4);
+        assert_eq!(codings("gzip, gzip, gzip, gzip, deflate, chunked"),
+                   Err(UnsupportedTransferCoding { coding: "deflate".to_owned() }));
+        assert_eq!(codings("compress, chunked"),
+                   Err(UnsupportedTransferCoding { coding: "compress".to_owned() }));
+        assert!(codings("chunked, chunked").is_err());
+    }
+
+    #[test]
+    fn test_transfer_decoding() {
+        let text = "Hello, world! ".repeat(500);
+
+        // `deflate` is applied first, then `gzip`.
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(text.as_bytes()).unwrap();
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&zlib.finish().unwrap()).unwrap();
+        let data = chunked(&gzip.finish().unwrap());
+
+        let mut reader = &data[..];
+        let mut body = Body::from_reader(&mut reader, None, true);
+        body.transfer = TransferDecoder::new(&["deflate".to_owned(), "gzip".to_owned()]);
+        let mut out = String::new();
+        body.read_to_string(&mut out).unwrap();
+        assert_eq!(out, text);
+
+        // Bodies cut short fail.
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(text.as_bytes()).unwrap();
+        let mut compressed = gzip.finish().unwrap();
+        let len = compressed.len();
+        compressed.truncate(len - 4);
+        let data = chunked(&compressed);
+
+        let mut reader = &data[..];
+        let mut body = Body::from_reader(&mut reader, None, true);
+        body.transfer = TransferDecoder::new(&codings("gzip, chunked").unwrap());
+        assert!(body.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_stacked_transfer_decoding_is_bounded() {
+        let size = 8 * 1024 * 1024;
+        let mut inner = GzEncoder::new(Vec::new(), Compression::default());
+        inner.write_all(&vec![0; size]).unwrap();
+        let mut outer = GzEncoder::new(Vec::new(), Compression::default());
+        outer.write_all(&inner.finish().unwrap()).unwrap();
+        let data = outer.finish().unwrap();
+
+        let mut decoder = TransferDecoder::new(&["gzip".to_owned(), "gzip".to_owned()]).unwrap();
+        decoder.feed(&data);
+        decoder.finish();
+
+        let mut buf = [0; 8 * 1024];
+        let mut total = 0;
+        loop {
+            let read = decoder.read(&mut buf).unwrap().unwrap();
+            if read == 0 { break }
+            total += read;
+
+            let buffered = decoder.output.len() +
+                decoder.layers.iter().map(|layer| layer.input.len()).sum::<usize>();
+            assert!(buffered <= 1024 * 1024, "{} bytes buffered", buffered);
+        }
+        assert_eq!(total, size);
+    }
+}
@@ -0,0 +1,148 @@
+//! Taking over connections for protocols such as WebSocket.
+
+use std::io::{self, Cursor, Read, Write};
+use std::time::Duration;
+
+use hyper::net::{HttpStream, NetworkStream};
+
+#[cfg(unix)]
+use unix::UnixStream;
+
+use super::{Body, Request, Source};
+
+impl<'a> Request<'a> {
+    /// Does the client ask to switch this connection to another protocol,
+    /// such as a WebSocket handshake?
+    ///
+    /// This is the case when the `Connection` header includes `upgrade` and
+    /// there is an `Upgrade` header, compared without regard to case. Answer
+    /// such requests with `Response::upgrade` to take over the connection.
+    ///
+    /// Use `upgrade_protocols` to check which protocol is wanted.
+    pub fn is_upgrade(&self) -> bool {
+        let connection_upgrade = tokens(self, "Connection")
+            .iter().any(|token| token.eq_ignore_ascii_case("upgrade"));
+        connection_upgrade && !self.upgrade_protocols().is_empty()
+    }
+
+    /// The protocols listed in the `Upgrade` header, in order of preference,
+    /// such as `["websocket"]`.
+    pub fn upgrade_protocols(&self) -> Vec<&str> {
+        tokens(self, "Upgrade")
+    }
+}
+
+// The comma separated tokens in the header called `name`.
+fn tokens<'r>(req: &'r Request, name: &str) -> Vec<&'r str> {
+    req.headers.get_raw(name).unwrap_or(&[]).iter()
+        .filter_map(|line| ::std::str::from_utf8(line).ok())
+        .flat_map(|line| line.split(','))
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+/// A connection taken over from the server by `Response::upgrade`, to speak
+/// another protocol such as WebSocket.
+///
+/// `Upgraded` owns its connection, so it can be moved to another thread. The
+/// server no longer reads from or writes to the connection, which is closed
+/// when `Upgraded` is dropped. Any bytes the client sent straight after its
+/// request, which the server had already read, are read first.
+///
+/// The server's read and write timeouts still apply, so long-lived
+/// connections should change them.
+pub struct Upgraded {
+    stream: Box<NetworkStream + Send>,
+    // Bytes of the new protocol read by the server before the upgrade.
+    buffered: Cursor<Vec<u8>>
+}
+
+impl Upgraded {
+    /// Set the read timeout of the connection. `None` waits forever.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(dur)
+    }
+
+    /// Set the write timeout of the connection. `None` waits forever.
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_write_timeout(dur)
+    }
+}
+
+impl Read for Upgraded {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if (self.buffered.position() as usize) < self.buffered.get_ref().len() {
+            self.buffered.read(buf)
+        } else {
+            self.stream.read(buf)
+        }
+    }
+}
+
+impl Write for Upgraded {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl<'a> Body<'a> {
+    // Take an owned handle to the connection this body is read from, along
+    // with whatever the server has read past the end of the request.
+    #[doc(hidden)]
+    pub fn upgrade(&mut self) -> io::Result<Upgraded> {
+        let connection = match *self.reader.get_ref() {
+            Source::Connection(ref connection) => connection,
+            Source::Reader(_) => {
+                return Err(io::Error::new(io::ErrorKind::Other,
+                                          "Body isn't read from a connection"));
+            }
+        };
+
+        Ok(Upgraded {
+            stream: try!(connection.try_clone_stream()),
+            buffered: Cursor::new(connection.buffered().to_vec())
+        })
+    }
+}
+
+// Open another handle to one of the kinds of connection the server accepts.
+pub fn clone_stream(stream: &NetworkStream) -> io::Result<Box<NetworkStream + Send>> {
+    if let Some(&HttpStream(ref tcp)) = stream.downcast_ref::<HttpStream>() {
+        return Ok(Box::new(HttpStream(try!(tcp.try_clone()))));
+    }
+
+    #[cfg(unix)]
+    {
+        if let Some(unix) = stream.downcast_ref::<UnixStream>() {
+            return Ok(Box::new(try!(unix.try_clone())));
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::Other, "Connections of this kind can't be upgraded"))
+}
+
+#[cfg(test)]
+mod test {
+    use {method, test, Url};
+
+    fn is_upgrade(connection: &str, upgrade: Option<&str>) -> bool {
+        let mut headers = vec![("Connection", connection)];
+        headers.extend(upgrade.map(|upgrade| ("Upgrade", upgrade)));
+        test::request_with_headers(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+                                   &headers, |req| req.is_upgrade())
+    }
+
+    #[test]
+    fn test_is_upgrade() {
+        assert!(is_upgrade("Upgrade", Some("websocket")));
+        assert!(is_upgrade("keep-alive, UPGRADE", Some("WebSocket")));
+        assert!(!is_upgrade("keep-alive", Some("websocket")));
+        assert!(!is_upgrade("Upgrade", None));
+        assert!(!is_upgrade("Upgrade", Some(" ")));
+    }
+}
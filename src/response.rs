@@ -3,17 +3,32 @@
 use std::io::{self, Write};
 use std::fmt::{self, Debug};
 use std::fs::File;
+use std::sync::{Arc, Mutex};
 
-use typemap::TypeMap;
+use typemap::{Key, TypeMap};
 use plugin::Extensible;
 use modifier::{Set, Modifier};
 use hyper::header::Headers;
 
+use cookie::Cookie;
+use method::Method;
+use modifiers::Header;
+use request::{Body, Upgraded};
 use status::{self, Status};
 use {Plugin, headers};
 
 pub use hyper::server::response::Response as HttpResponse;
 use hyper::net::Fresh;
+use hyper::http::h1::HttpWriter;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde_json;
+#[cfg(feature = "serde")]
+use mime::{Mime, TopLevel, SubLevel};
+#[cfg(feature = "serde")]
+use {IronResult, IronError};
 
 /// A `Write`r of HTTP response bodies.
 pub struct ResponseBody<'a>(Box<Write + 'a>);
@@ -38,6 +53,108 @@ impl<'a> Write for ResponseBody<'a> {
 /// Wrapper type to set `Read`ers as response bodies
 pub struct BodyReader<R: Send>(pub R);
 
+/// Wrapper type to stream the chunks produced by an `Iterator` as a response
+/// body.
+///
+/// Chunks are only produced once the response is being written, and each is
+/// sent to the client as soon as it is ready. If the iterator yields an
+/// error, the response is left unfinished so the client can tell the body is
+/// incomplete, and the connection is closed.
+pub struct BodyStream<I>(pub I);
+
+/// Trailers to send after a response body, for values which are only known
+/// once the body has been written, such as a checksum of it.
+///
+/// The names of the trailers are given when the `Trailers` is made, and used
+/// as a modifier it lists them in the `Trailer` header of the response. Keep
+/// a clone to set their values while the body is written, such as from the
+/// iterator of a `BodyStream`:
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::response::{BodyStream, Trailers};
+///
+/// let trailers = Trailers::new(&["X-Row-Count"]);
+/// let counter = trailers.clone();
+///
+/// let rows = (0..3).map(|n| Ok(format!("row {}\n", n).into_bytes()));
+/// let body = BodyStream(rows.chain((0..1).filter_map(move |_| {
+///     counter.set("X-Row-Count", "3");
+///     None
+/// })));
+///
+/// let res = Response::with((status::Ok, body, trailers));
+/// ```
+///
+/// Trailers can only follow a chunked body, so they aren't sent with a
+/// response which has a `Content-Length`, or if the body fails. Those still
+/// unset when the body ends aren't sent either. Clients may ignore trailers,
+/// especially if they didn't ask for them with `TE: trailers`.
+#[derive(Clone, Debug)]
+pub struct Trailers {
+    names: Arc<Vec<String>>,
+    values: Arc<Mutex<Vec<(String, String)>>>
+}
+
+impl Trailers {
+    /// Declare trailers called `names`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a name isn't a valid header name.
+    pub fn new(names: &[&str]) -> Trailers {
+        for name in names {
+            assert!(!name.is_empty() && name.bytes().all(|b| b > b' ' && b < 0x7F && b != b':'),
+                    "Invalid trailer name: {:?}", name);
+        }
+
+        Trailers {
+            names: Arc::new(names.iter().map(|name| name.to_string()).collect()),
+            values: Arc::new(Mutex::new(Vec::new()))
+        }
+    }
+
+    /// Set the value of the trailer called `name`, replacing any set before.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `name` wasn't declared, ignoring case, or `value` contains
+    /// control characters such as line breaks.
+    pub fn set(&self, name: &str, value: &str) {
+        let name = self.names.iter().find(|declared| declared.eq_ignore_ascii_case(name))
+            .unwrap_or_else(|| panic!("Undeclared trailer: {:?}", name));
+        assert!(value.bytes().all(|b| b >= b' ' && b != 0x7F || b == b'\t'),
+                "Invalid value for trailer {:?}: {:?}", name, value);
+
+        let mut values = self.values.lock().unwrap();
+        values.retain(|&(ref set, _)| set != name);
+        values.push((name.clone(), value.to_owned()));
+    }
+
+    // Write the trailer lines, each ending in `\r\n`.
+    fn write_lines(&self, out: &mut Write) -> io::Result<()> {
+        for &(ref name, ref value) in self.values.lock().unwrap().iter() {
+            try!(write!(out, "{}: {}\r\n", name, value));
+        }
+        Ok(())
+    }
+}
+
+// The trailers of a response.
+struct TrailersKey;
+
+impl Key for TrailersKey { type Value = Trailers; }
+
+impl Modifier<Response> for Trailers {
+    /// Send these trailers after the body, listing them in the `Trailer`
+    /// header.
+    fn modify(self, res: &mut Response) {
+        res.headers.set_raw("Trailer", vec![self.names.join(", ").into_bytes()]);
+        res.extensions.insert::<TrailersKey>(self);
+    }
+}
+
 /// A trait which writes the body of an HTTP response.
 pub trait WriteBody: Send {
     /// Writes the body to the provided `ResponseBody`.
@@ -86,6 +203,22 @@ impl <R: io::Read + Send> WriteBody for BodyReader<R> {
     }
 }
 
+impl<I> WriteBody for BodyStream<I> where I: Iterator<Item = io::Result<Vec<u8>>> + Send {
+    fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()> {
+        for chunk in &mut self.0 {
+            let chunk = try!(chunk);
+
+            // An empty chunk would end a chunked body early.
+            if chunk.is_empty() { continue }
+
+            try!(res.write_all(&chunk));
+            try!(res.flush());
+        }
+
+        Ok(())
+    }
+}
+
 /* Needs specialization :(
 impl<R: Read + Send> WriteBody for R {
     fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()> {
@@ -126,42 +259,390 @@ impl Response {
         Response::new().set(m)
     }
 
+    /// Start building a `200 OK` response, one part at a time.
+    ///
+    /// See `ResponseBuilder`.
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder { res: Response::with(status::Ok) }
+    }
+
+    /// Construct a `200 OK` response to an `OPTIONS` request for a resource
+    /// which allows `methods`.
+    ///
+    /// The `Allow` header lists the methods once each, `OPTIONS` included,
+    /// with the standard methods in the order of RFC 7231 followed by `PATCH`
+    /// and then any extension methods.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::method::Method;
+    /// let res = Response::options(&[Method::Post, Method::Get, Method::Head]);
+    /// assert_eq!(res.headers.get_raw("Allow").unwrap()[0], b"GET, HEAD, POST, OPTIONS".to_vec());
+    /// ```
+    pub fn options(methods: &[Method]) -> Response {
+        let mut allowed = methods.to_vec();
+        allowed.push(Method::Options);
+        allowed.sort_by(|a, b| (method_rank(a), a.as_ref()).cmp(&(method_rank(b), b.as_ref())));
+        allowed.dedup();
+
+        Response::with((status::Ok, Header(headers::Allow(allowed))))
+    }
+
+    /// Add `header_names` to the `Vary` header, for a response which depends
+    /// on those request headers, so caches keep a version for each of their
+    /// values.
+    ///
+    /// Names already listed, in any case, aren't added again, and nothing is
+    /// added to `Vary: *`, which covers every header already.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// let mut res = Response::with(status::Ok);
+    /// res.vary_on(&["Accept", "Accept-Encoding"]);
+    /// res.vary_on(&["accept-encoding", "Accept-Language"]);
+    /// assert_eq!(res.headers.get_raw("Vary").unwrap()[0],
+    ///            b"Accept, Accept-Encoding, Accept-Language".to_vec());
+    /// ```
+    pub fn vary_on(&mut self, header_names: &[&str]) {
+        let mut vary = Vec::new();
+        for line in self.headers.get_raw("Vary").unwrap_or(&[]) {
+            for name in String::from_utf8_lossy(line).split(',').map(str::trim) {
+                if !name.is_empty() { vary.push(name.to_owned()) }
+            }
+        }
+        if vary.iter().any(|name| name == "*") { return }
+
+        for &name in header_names {
+            if !vary.iter().any(|existing| existing.eq_ignore_ascii_case(name)) {
+                vary.push(name.to_owned());
+            }
+        }
+        if !vary.is_empty() {
+            self.headers.set_raw("Vary", vec![vary.join(", ").into_bytes()]);
+        }
+    }
+
+    /// Construct a `101 Switching Protocols` response which hands the
+    /// connection over to `on_upgrade`, to speak `protocol` instead of HTTP.
+    ///
+    /// Send it in answer to a request for which `Request::is_upgrade` is
+    /// true, adding any headers the new protocol needs, such as
+    /// `Sec-WebSocket-Accept`. Once the response has been sent,
+    /// `on_upgrade` is given the connection and the server forgets about it.
+    ///
+    /// `on_upgrade` is called on the worker thread which handled the request,
+    /// which can't serve other connections until it returns, so long-lived
+    /// protocols should move the `Upgraded` to a thread of their own. If a
+    /// middleware replaces the response, `on_upgrade` is never called. If the
+    /// connection can't be handed over, as with HTTPS, nor is it, and the
+    /// client is sent `500 Internal Server Error` instead.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// use std::io::{Read, Write};
+    /// use std::thread;
+    ///
+    /// fn echo(req: &mut Request) -> IronResult<Response> {
+    ///     if !req.is_upgrade() || !req.upgrade_protocols().contains(&"echo") {
+    ///         return Ok(Response::with(status::BadRequest));
+    ///     }
+    ///
+    ///     Ok(Response::upgrade("echo", |mut conn| {
+    ///         thread::spawn(move || {
+    ///             let mut buf = [0; 1024];
+    ///             while let Ok(read) = conn.read(&mut buf) {
+    ///                 if read == 0 || conn.write_all(&buf[..read]).is_err() { break }
+    ///             }
+    ///         });
+    ///     }))
+    /// }
+    /// ```
+    pub fn upgrade<F>(protocol: &str, on_upgrade: F) -> Response
+    where F: FnOnce(Upgraded) + Send + 'static {
+        let mut res = Response::with(status::SwitchingProtocols);
+        res.headers.set_raw("Connection", vec![b"Upgrade".to_vec()]);
+        res.headers.set_raw("Upgrade", vec![protocol.as_bytes().to_vec()]);
+        res.extensions.insert::<OnUpgrade>(Box::new(on_upgrade));
+        res
+    }
+
     // `write_back` is used to put all the data added to `self`
     // back onto an `HttpResponse` so that it is sent back to the
     // client.
     //
     // `write_back` consumes the `Response`.
     #[doc(hidden)]
-    pub fn write_back(self, mut http_res: HttpResponse<Fresh>) {
+    pub fn write_back(self, http_res: HttpResponse<Fresh>) {
+        if let Err(e) = self.write(http_res, true) {
+            error!("Error writing response: {}", e);
+        }
+    }
+
+    // Like `write_back`, but for a `HEAD` request: the headers are sent as
+    // they would be for a `GET`, but the body isn't.
+    #[doc(hidden)]
+    pub fn write_back_head(self, http_res: HttpResponse<Fresh>) {
+        if let Err(e) = self.write(http_res, false) {
+            error!("Error writing response: {}", e);
+        }
+    }
+
+    // Like `write_back`, but if this is a response made with `upgrade`, hand
+    // the connection `body` is read from to its callback once it is sent.
+    #[doc(hidden)]
+    pub fn write_back_or_upgrade(mut self, http_res: HttpResponse<Fresh>, body: &mut Body) {
+        let on_upgrade = match self.extensions.remove::<OnUpgrade>() {
+            Some(on_upgrade) if self.status == Some(status::SwitchingProtocols) => on_upgrade,
+            _ => return self.write_back(http_res)
+        };
+
+        // Take the connection before anything is sent, so that if it can't
+        // be handed over the client still gets an ordinary HTTP response.
+        let upgraded = match body.upgrade() {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                error!("Error upgrading connection: {}", e);
+                return Response::with(status::InternalServerError).write_back(http_res);
+            }
+        };
+
+        match self.write_switching_protocols(http_res) {
+            Ok(()) => on_upgrade(upgraded),
+            Err(e) => error!("Error upgrading connection: {}", e)
+        }
+    }
+
+    fn write_switching_protocols(self, mut http_res: HttpResponse<Fresh>) -> io::Result<()> {
+        *http_res.headers_mut() = self.headers;
+        *http_res.status_mut() = status::SwitchingProtocols;
+        http_res.headers_mut().remove::<headers::ContentLength>();
+
+        let (_, writer, _, headers) = try!(http_res.start()).deconstruct();
+        try!(writer.into_inner().flush());
+
+        // Stop the server reading another request from the connection. The
+        // response has been sent, so this is only seen by the server.
+        headers.set(headers::Connection::close());
+        Ok(())
+    }
+
+    /// Write the response to `out` as an HTTP/1.1 message, exactly as the
+    /// server would send it, such as to compare with a golden file in tests.
+    ///
+    /// Default headers are added just as they are by the server, including
+    /// `Date`, and the body is sent chunked unless it has a `Content-Length`.
+    /// The body is taken from the response, so it is only written once.
+    ///
+    /// ```
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// let mut res = Response::with((status::Ok, "Hello!"));
+    ///
+    /// let mut out = Vec::new();
+    /// res.write_to(&mut out).unwrap();
+    /// assert!(out.starts_with(b"HTTP/1.1 200 OK\r\n"));
+    /// assert!(out.ends_with(b"\r\n\r\nHello!"));
+    /// ```
+    pub fn write_to<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let res = Response {
+            status: self.status,
+            headers: self.headers.clone(),
+            extensions: TypeMap::new(),
+            body: self.body.take()
+        };
+
+        let mut headers = Headers::new();
+        res.write(HttpResponse::new(out, &mut headers), true)
+    }
+
+    fn write(self, mut http_res: HttpResponse<Fresh>, send_body: bool) -> io::Result<()> {
         *http_res.headers_mut() = self.headers;
 
         // Default to a 404 if no response code was set
         *http_res.status_mut() = self.status.clone().unwrap_or(status::NotFound);
 
-        let out = match self.body {
-            Some(body) => write_with_body(http_res, body),
+        let trailers = self.extensions.get::<TrailersKey>().cloned();
+
+        match self.body {
+            Some(body) => write_with_body(http_res, body, send_body, trailers),
             None => {
-                http_res.headers_mut().set(headers::ContentLength(0));
+                // A `HEAD` handler may give the length of the body it isn't sending.
+                if send_body || !http_res.headers().has::<headers::ContentLength>() {
+                    http_res.headers_mut().set(headers::ContentLength(0));
+                }
                 http_res.start().and_then(|res| res.end())
             }
-        };
-
-        if let Err(e) = out {
-            error!("Error writing response: {}", e);
         }
     }
 }
 
-fn write_with_body(mut res: HttpResponse<Fresh>, mut body: Box<WriteBody>)
-                   -> io::Result<()> {
+// The callback given to `Response::upgrade`.
+struct OnUpgrade;
+
+impl Key for OnUpgrade { type Value = Box<FnOnce(Upgraded) + Send>; }
+
+// The position of standard methods in the canonical order of `Allow`.
+fn method_rank(method: &Method) -> u8 {
+    match *method {
+        Method::Get => 0,
+        Method::Head => 1,
+        Method::Post => 2,
+        Method::Put => 3,
+        Method::Delete => 4,
+        Method::Connect => 5,
+        Method::Options => 6,
+        Method::Trace => 7,
+        Method::Patch => 8,
+        Method::Extension(_) => 9
+    }
+}
+
+fn write_with_body(mut res: HttpResponse<Fresh>, mut body: Box<WriteBody>, send_body: bool,
+                   trailers: Option<Trailers>) -> io::Result<()> {
     let content_type = res.headers().get::<headers::ContentType>()
                            .map_or_else(|| headers::ContentType("text/plain".parse().unwrap()),
                                         |cx| cx.clone());
     res.headers_mut().set(content_type);
 
     let mut raw_res = try!(res.start());
-    try!(body.write_body(&mut ResponseBody::new(&mut raw_res)));
-    raw_res.end()
+    if !send_body {
+        // Skip hyper's body writer, which would end a chunked body.
+        let (_, writer, _, _) = raw_res.deconstruct();
+        return writer.into_inner().flush();
+    }
+
+    let written = body.write_body(&mut ResponseBody::new(&mut raw_res));
+
+    match (written, trailers) {
+        (Ok(()), Some(trailers)) => {
+            // hyper ends chunked bodies without trailers, so end it here.
+            match raw_res.deconstruct().1 {
+                HttpWriter::ChunkedWriter(out) => {
+                    try!(out.write_all(b"0\r\n"));
+                    try!(trailers.write_lines(out));
+                    try!(out.write_all(b"\r\n"));
+                    out.flush()
+                },
+                writer => writer.end().map(|_| ()).map_err(io::Error::from)
+            }
+        },
+        (Ok(()), None) => raw_res.end(),
+        (Err(e), _) => {
+            // Ending the response would terminate the body, making a truncated
+            // response look complete, so leave it unfinished and have the
+            // server close the connection instead of reading another request.
+            let (_, _, _, headers) = raw_res.deconstruct();
+            headers.set(headers::Connection::close());
+            Err(e)
+        }
+    }
+}
+
+/// A builder for a `Response`, made with `Response::builder`.
+///
+/// Each method applies a modifier, as `Response::with` does, so the builder
+/// accepts the same bodies and headers, and `set` takes any other modifier.
+/// The response starts as a `200 OK` without headers or a body, and parts
+/// set later replace those set earlier.
+///
+/// ```
+/// # use iron::prelude::*;
+/// # use iron::status;
+/// use iron::headers::{CacheControl, CacheDirective};
+/// use iron::cookie::Cookie;
+///
+/// let res = Response::builder().body("Hello!").build();
+/// assert_eq!(res.status, Some(status::Ok));
+///
+/// let res = Response::builder()
+///     .status(status::Created)
+///     .header(CacheControl(vec![CacheDirective::NoStore]))
+///     .cookie(Cookie::new("theme", "dark").unwrap())
+///     .body(vec![1, 2, 3])
+///     .build();
+/// assert!(res.headers.has::<CacheControl>());
+/// ```
+pub struct ResponseBuilder {
+    res: Response
+}
+
+impl ResponseBuilder {
+    /// Set the status.
+    pub fn status(self, status: Status) -> ResponseBuilder {
+        self.set(status)
+    }
+
+    /// Set a header, replacing any other of the same name.
+    pub fn header<H>(self, header: H) -> ResponseBuilder
+    where H: headers::Header + headers::HeaderFormat {
+        self.set(Header(header))
+    }
+
+    /// Set the body, from anything which can be the body of a `Response`,
+    /// such as a `&str`, a `Vec<u8>`, a `File` or a `BodyStream`.
+    ///
+    /// Like the other modifiers for bodies, this also sets headers such as
+    /// `Content-Length` where it can.
+    pub fn body<B: Modifier<Response>>(self, body: B) -> ResponseBuilder {
+        self.set(body)
+    }
+
+    /// Add a `Set-Cookie` header for `cookie`, keeping those already added.
+    pub fn cookie(self, cookie: Cookie) -> ResponseBuilder {
+        self.set(cookie)
+    }
+
+    /// Apply any other modifier.
+    pub fn set<M: Modifier<Response>>(mut self, modifier: M) -> ResponseBuilder {
+        self.res.set_mut(modifier);
+        self
+    }
+
+    /// Finish building the response.
+    pub fn build(self) -> Response {
+        self.res
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Response {
+    /// Construct a response with `status` whose body is `value` serialized
+    /// as JSON, behind the `serde` feature.
+    ///
+    /// `Content-Type` is set to `application/json` and `Content-Length` to
+    /// the length of the JSON.
+    ///
+    /// ```
+    /// # #[cfg(feature = "serde")] {
+    /// # use iron::prelude::*;
+    /// # use iron::status;
+    /// use std::collections::BTreeMap;
+    ///
+    /// fn handler(_: &mut Request) -> IronResult<Response> {
+    ///     let mut point = BTreeMap::new();
+    ///     point.insert("x", 1);
+    ///     point.insert("y", 2);
+    ///     Response::json(status::Ok, &point)
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// The value is serialized straight into the buffer which becomes the
+    /// body, since its length must be known before it is sent. Stream values
+    /// too large to hold in memory with a `BodyStream` instead.
+    ///
+    /// Fails with `500 Internal Server Error` wrapping the
+    /// `serde_json::Error` if `value` can't be serialized, such as a map
+    /// whose keys aren't strings.
+    pub fn json<T: Serialize>(status: Status, value: &T) -> IronResult<Response> {
+        let body = try!(serde_json::to_vec(value).map_err(|err| {
+            IronError::new(err, status::InternalServerError)
+        }));
+
+        Ok(Response::with((status, Mime(TopLevel::Application, SubLevel::Json, vec![]), body)))
+    }
 }
 
 impl Debug for Response {
@@ -193,3 +674,195 @@ impl Extensible for Response {
 
 impl Plugin for Response {}
 impl Set for Response {}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{self, Write};
+
+    use hyper::header::{Connection, Headers, Date, HttpDate};
+    use time;
+
+    use super::{Response, HttpResponse, BodyStream, Trailers};
+    use status;
+
+    fn write(res: Response) -> String {
+        let mut out = Vec::new();
+        {
+            let mut headers = Headers::new();
+            res.write_back(HttpResponse::new(&mut out, &mut headers));
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_stream_is_chunked() {
+        let chunks = vec![Ok(b"hello".to_vec()), Ok(Vec::new()), Ok(b" world".to_vec())];
+        let out = write(Response::with((status::Ok, BodyStream(chunks.into_iter()))));
+
+        assert!(out.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(out.ends_with("\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_write_to_matches_write_back() {
+        let response = || {
+            let chunks = vec![Ok(b"hello".to_vec())];
+            let mut res = Response::with((status::Ok, BodyStream(chunks.into_iter())));
+            res.headers.set(Date(HttpDate(time::at_utc(time::Timespec::new(0, 0)))));
+            res
+        };
+
+        // Headers are written in no particular order.
+        let lines = |out: String| {
+            let mut lines: Vec<String> = out.split("\r\n").map(String::from).collect();
+            lines.sort();
+            lines
+        };
+
+        let mut res = response();
+        let mut out = Vec::new();
+        res.write_to(&mut out).unwrap();
+        assert_eq!(lines(String::from_utf8(out).unwrap()), lines(write(response())));
+        assert!(res.body.is_none());
+    }
+
+    #[test]
+    fn test_trailers() {
+        let trailers = Trailers::new(&["X-Checksum", "X-Count"]);
+        let setter = trailers.clone();
+        let chunks = vec![Ok(b"hello".to_vec())].into_iter().chain((0..1).filter_map(move |_| {
+            setter.set("x-checksum", "abc");
+            None
+        }));
+        let out = write(Response::with((status::Ok, BodyStream(chunks), trailers)));
+
+        assert!(out.contains("Trailer: X-Checksum, X-Count\r\n"));
+        assert!(out.ends_with("\r\n\r\n5\r\nhello\r\n0\r\nX-Checksum: abc\r\n\r\n"));
+
+        // Sized bodies can't have trailers.
+        let trailers = Trailers::new(&["X-Checksum"]);
+        trailers.set("X-Checksum", "abc");
+        let out = write(Response::with((status::Ok, "hello", trailers)));
+        assert!(out.ends_with("\r\n\r\nhello"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json() {
+        use std::collections::HashMap;
+
+        #[derive(Serialize)]
+        struct Point { x: i32, y: i32 }
+
+        let out = write(Response::json(status::Created, &Point { x: 1, y: 2 }).unwrap());
+        assert!(out.starts_with("HTTP/1.1 201 Created\r\n"));
+        assert!(out.contains("Content-Type: application/json\r\n"));
+        assert!(out.contains("Content-Length: 13\r\n"));
+        assert!(out.ends_with("\r\n\r\n{\"x\":1,\"y\":2}"));
+
+        let mut invalid = HashMap::new();
+        invalid.insert((1, 2), "tuple keys aren't strings");
+        let err = Response::json(status::Ok, &invalid).unwrap_err();
+        assert_eq!(err.response.status, Some(status::InternalServerError));
+    }
+
+    #[test]
+    fn test_builder() {
+        use cookie::Cookie;
+        use headers::ContentType;
+
+        let out = write(Response::builder().body("Hello!").build());
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.ends_with("\r\n\r\nHello!"));
+
+        let res = Response::builder()
+            .status(status::NotFound)
+            .header(ContentType::html())
+            .cookie(Cookie::new("a", "1").unwrap())
+            .cookie(Cookie::new("b", "2").unwrap())
+            .body("Gone")
+            .status(status::Gone)
+            .build();
+        assert_eq!(res.status, Some(status::Gone));
+        assert_eq!(res.headers.get::<ContentType>(), Some(&ContentType::html()));
+        let out = write(res);
+        assert!(out.contains("Set-Cookie: a=1\r\nSet-Cookie: b=2\r\n"));
+        assert!(out.ends_with("\r\n\r\nGone"));
+    }
+
+    #[test]
+    fn test_stream_error_leaves_body_unfinished() {
+        let chunks = vec![Ok(b"hello".to_vec()), Err(io::Error::new(io::ErrorKind::Other, "oops"))];
+        let mut out = Vec::new();
+        let mut headers = Headers::new();
+        {
+            let res = Response::with((status::Ok, BodyStream(chunks.into_iter())));
+            res.write_back(HttpResponse::new(&mut out, &mut headers));
+        }
+
+        assert!(String::from_utf8(out).unwrap().ends_with("\r\n\r\n5\r\nhello\r\n"));
+        // The server then closes the connection instead of reading another
+        // request from it.
+        assert_eq!(headers.get::<Connection>(), Some(&Connection::close()));
+
+        // Streams which succeed don't close it.
+        let chunks = vec![Ok(b"hello".to_vec())];
+        let out = write(Response::with((status::Ok, BodyStream(chunks.into_iter()))));
+        assert!(!out.contains("Connection: close"));
+    }
+
+    #[test]
+    fn test_head_has_no_body() {
+        let mut out = Vec::new();
+        {
+            let mut headers = Headers::new();
+            let chunks = vec![Ok(b"hello".to_vec())];
+            Response::with((status::Ok, BodyStream(chunks.into_iter())))
+                .write_back_head(HttpResponse::new(&mut out, &mut headers));
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(out.ends_with("\r\n\r\n"));
+        assert!(!out.contains("hello"));
+
+        let mut out = Vec::new();
+        {
+            let mut headers = Headers::new();
+            Response::with((status::Ok, "hello"))
+                .write_back_head(HttpResponse::new(&mut out, &mut headers));
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Content-Length: 5\r\n"));
+        assert!(out.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = env::temp_dir().join(format!("iron-from-file-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        File::create(dir.join("page.html")).unwrap().write_all(b"<p>Hi</p>").unwrap();
+
+        let out = write(Response::from_file(dir.join("page.html")).unwrap());
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.contains("Content-Length: 9\r\n"));
+        assert!(out.contains("Content-Type: text/html\r\n"));
+        assert!(out.contains("Last-Modified: "));
+        assert!(out.ends_with("\r\n\r\n<p>Hi</p>"));
+
+        let err = Response::from_file(dir.join("missing.html")).unwrap_err();
+        assert_eq!(err.response.status, Some(status::NotFound));
+        let err = Response::from_file(&dir).unwrap_err();
+        assert_eq!(err.response.status, Some(status::NotFound));
+
+        #[cfg(unix)]
+        {
+            ::std::os::unix::fs::symlink(dir.join("page.html"), dir.join("link.html")).unwrap();
+            let err = Response::from_file(dir.join("link.html")).unwrap_err();
+            assert_eq!(err.response.status, Some(status::Forbidden));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Graceful shutdown for running servers.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hyper::net::{NetworkListener, HttpStream};
+
+use error::HttpResult;
+
+/// A handle used to gracefully shut down a server.
+///
+/// Returned by `Iron::http_with_shutdown`.
+pub struct ShutdownSignal {
+    state: Arc<ShutdownState>,
+    addr: SocketAddr,
+    threads: usize
+}
+
+impl ShutdownSignal {
+    #[doc(hidden)]
+    pub fn new(state: Arc<ShutdownState>, addr: SocketAddr, threads: usize) -> ShutdownSignal {
+        ShutdownSignal { state: state, addr: addr, threads: threads }
+    }
+
+    /// Stop accepting new connections and wait for in-flight requests to finish.
+    ///
+    /// The listening socket is closed once every acceptor thread has noticed
+    /// the shutdown. Waits at most `grace` for requests which are already
+    /// being handled, returning `true` if all of them completed in time.
+    ///
+    /// Responses written after the shutdown has started are sent with
+    /// `Connection: close`, so kept-alive connections wind down as well.
+    pub fn shutdown(self, grace: Duration) -> bool {
+        self.state.shutting_down.store(true, Ordering::SeqCst);
+
+        // Drop our handle on the socket, then wake every acceptor blocked in
+        // `accept` so they can drop theirs.
+        self.state.listener.lock().unwrap().take();
+        let wake_addr = connectable(self.addr);
+        for _ in 0..self.threads {
+            if TcpStream::connect(wake_addr).is_err() { break }
+        }
+
+        self.state.wait_idle(grace)
+    }
+}
+
+// The state shared between a server and its `ShutdownSignal`.
+pub struct ShutdownState {
+    shutting_down: AtomicBool,
+    listener: Mutex<Option<Arc<TcpListener>>>,
+
+    in_flight: AtomicUsize,
+    lock: Mutex<()>,
+    idle: Condvar
+}
+
+impl ShutdownState {
+    pub fn new() -> ShutdownState {
+        ShutdownState {
+            shutting_down: AtomicBool::new(false),
+            listener: Mutex::new(None),
+            in_flight: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            idle: Condvar::new()
+        }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    // Mark a request as in flight until the returned guard is dropped.
+    pub fn start_request(&self) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(self)
+    }
+
+    fn wait_idle(&self, grace: Duration) -> bool {
+        let deadline = Instant::now() + grace;
+        let mut guard = self.lock.lock().unwrap();
+
+        while self.in_flight.load(Ordering::SeqCst) > 0 {
+            let now = Instant::now();
+            if now >= deadline { return false }
+            guard = self.idle.wait_timeout(guard, deadline - now).unwrap().0;
+        }
+
+        true
+    }
+}
+
+// Tracks a request being handled; see `ShutdownState::start_request`.
+//
+// Finishing happens on drop so panicking handlers are accounted for too.
+pub struct InFlight<'a>(&'a ShutdownState);
+
+impl<'a> Drop for InFlight<'a> {
+    fn drop(&mut self) {
+        let state = self.0;
+        if state.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 && state.is_shutting_down() {
+            let _guard = state.lock.lock().unwrap();
+            state.idle.notify_all();
+        }
+    }
+}
+
+// A TCP listener which stops accepting connections once shut down.
+//
+// The socket is only held strongly by the `ShutdownState` and by the clones
+// used by each acceptor thread, so it is closed once all of them let go.
+pub struct GracefulListener {
+    listener: Option<Arc<TcpListener>>,
+    weak: Weak<TcpListener>,
+    state: Arc<ShutdownState>
+}
+
+impl GracefulListener {
+    pub fn bind(addr: SocketAddr, state: Arc<ShutdownState>) -> HttpResult<GracefulListener> {
+        let listener = Arc::new(try!(TcpListener::bind(addr)));
+        let weak = Arc::downgrade(&listener);
+        *state.listener.lock().unwrap() = Some(listener);
+
+        Ok(GracefulListener { listener: None, weak: weak, state: state })
+    }
+
+    // Give up this acceptor's handle on the socket and never accept again.
+    fn stop(&mut self) -> ! {
+        self.listener.take();
+        loop { thread::park() }
+    }
+}
+
+impl Clone for GracefulListener {
+    fn clone(&self) -> GracefulListener {
+        GracefulListener {
+            listener: self.weak.upgrade(),
+            weak: self.weak.clone(),
+            state: self.state.clone()
+        }
+    }
+}
+
+impl NetworkListener for GracefulListener {
+    type Stream = HttpStream;
+
+    fn accept(&mut self) -> HttpResult<HttpStream> {
+        if self.state.is_shutting_down() { self.stop() }
+
+        let stream = match self.listener {
+            Some(ref listener) => try!(listener.accept()).0,
+            None => self.stop()
+        };
+
+        if self.state.is_shutting_down() {
+            drop(stream);
+            self.stop()
+        }
+
+        Ok(HttpStream(stream))
+    }
+
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        match self.weak.upgrade() {
+            Some(listener) => listener.local_addr(),
+            None => Err(io::Error::new(io::ErrorKind::NotConnected, "Listener has been shut down"))
+        }
+    }
+}
+
+// An address which can be connected to in order to reach a socket bound to `addr`.
+fn connectable(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), addr.port())
+        },
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), addr.port())
+        },
+        _ => addr
+    }
+}
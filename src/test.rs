@@ -0,0 +1,265 @@
+//! Helpers for testing handlers, either directly without a server or over
+//! real connections to a server started with `run`.
+//!
+//! ```
+//! # use std::io;
+//! use iron::prelude::*;
+//! use iron::{method, status, test, Handler, Url};
+//! use iron::request::Body;
+//!
+//! fn hello(_: &mut Request) -> IronResult<Response> {
+//!     Ok(Response::with((status::Ok, "Hello!")))
+//! }
+//!
+//! let mut reader = io::empty();
+//! let mut req = test::request(method::Get, Url::parse("http://localhost:3000/").unwrap(),
+//!                             None, Body::from_reader(&mut reader, Some(0), false));
+//!
+//! let res = hello.handle(&mut req).unwrap();
+//! assert_eq!(res.status, Some(status::Ok));
+//! ```
+//!
+//! End-to-end tests can send requests with any HTTP client:
+//!
+//! ```
+//! # use std::io::{Read, Write};
+//! # use std::net::TcpStream;
+//! # use std::time::Duration;
+//! use iron::prelude::*;
+//! use iron::{status, test};
+//!
+//! let server = test::run(|_: &mut Request| Ok(Response::with((status::Ok, "Hello!"))));
+//!
+//! let mut stream = TcpStream::connect(server.addr()).unwrap();
+//! stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+//! let mut res = String::new();
+//! stream.read_to_string(&mut res).unwrap();
+//! assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+//! assert!(res.ends_with("\r\n\r\nHello!"));
+//!
+//! server.shutdown(Duration::from_secs(1));
+//! ```
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use hyper::uri::RequestUri;
+
+use request::{Body, HttpVersion};
+use method::Method;
+use {Iron, Handler, Listening, ShutdownSignal, Request, Response, Url, Headers, TypeMap, headers};
+
+/// Build a `Request` for `url` without a connection.
+///
+/// The request is made with HTTP/1.1 from `127.0.0.1:0`, and is received on
+/// loopback at the port of `url`. It is `secure` if `url` is `https`. A
+/// `Host` header is added from `url` unless `headers` already contains one.
+pub fn request<'a>(method: Method, url: Url, headers: Option<Headers>, body: Body<'a>)
+                   -> Request<'a> {
+    let loopback = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let mut headers = headers.unwrap_or_else(Headers::new);
+
+    if !headers.has::<headers::Host>() {
+        headers.set(headers::Host { hostname: url.host.serialize(), port: Some(url.port) });
+    }
+
+    let mut path = format!("/{}", url.path.join("/"));
+    if let Some(ref query) = url.query {
+        path.push('?');
+        path.push_str(query);
+    }
+
+    let secure = url.scheme == "https";
+
+    Request {
+        local_addr: SocketAddr::new(loopback, url.port),
+        remote_addr: SocketAddr::new(loopback, 0),
+        raw_uri: RequestUri::AbsolutePath(path),
+        url: url,
+        headers: headers,
+        body: body,
+        method: method,
+        version: HttpVersion::Http11,
+        secure: secure,
+        negotiated_version: None,
+        extensions: TypeMap::new()
+    }
+}
+
+/// Call `f` with a `Request` for `url` built by `request`, which has an empty
+/// body and the given `(name, value)` headers.
+///
+/// A name given more than once gets a line for each of its values, in
+/// order. This suits testing anything which only looks at the head of a
+/// request.
+///
+/// ```
+/// use iron::{method, test, Url};
+///
+/// let url = Url::parse("http://localhost:3000/").unwrap();
+/// let headers = [("Accept", "text/html"), ("Accept", "*/*")];
+/// let lines = test::request_with_headers(method::Get, url, &headers, |req| {
+///     req.headers.get_raw("Accept").unwrap().len()
+/// });
+/// assert_eq!(lines, 2);
+/// ```
+pub fn request_with_headers<F, T>(method: Method, url: Url, headers: &[(&str, &str)], f: F) -> T
+    where F: FnOnce(&mut Request) -> T {
+    let mut raw = Headers::new();
+    for &(name, value) in headers {
+        let mut lines = raw.get_raw(name).map_or_else(Vec::new, |lines| lines.to_vec());
+        lines.push(value.as_bytes().to_vec());
+        raw.set_raw(name.to_owned(), lines);
+    }
+
+    let mut reader = io::empty();
+    f(&mut request(method, url, Some(raw), Body::from_reader(&mut reader, Some(0), false)))
+}
+
+/// The first value of the header `name` of `res`, if it has one.
+///
+/// Values which aren't valid UTF-8 are converted lossily.
+pub fn response_header(res: &Response, name: &str) -> Option<String> {
+    res.headers.get_raw(name).map(|values| String::from_utf8_lossy(&values[0]).into_owned())
+}
+
+/// Start a server for `handler` on a free port on loopback, for tests which
+/// make real requests.
+///
+/// The server uses two threads and Iron's default timeouts.
+///
+/// ## Panics
+///
+/// Panics if the server can't be started.
+pub fn run<H: Handler>(handler: H) -> RunningServer {
+    let loopback = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let (listening, signal) = Iron::new(handler).threads(2)
+        .http_with_shutdown(SocketAddr::new(loopback, 0))
+        .expect("Could not start test server.");
+
+    RunningServer { listening: listening, signal: Some(signal) }
+}
+
+/// A server started by `run`.
+///
+/// The server is shut down when this is dropped, without waiting for requests
+/// still being handled.
+pub struct RunningServer {
+    listening: Listening,
+    signal: Option<ShutdownSignal>
+}
+
+impl RunningServer {
+    /// The address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.listening.socket
+    }
+
+    /// Stop the server, waiting at most `grace` for requests still being
+    /// handled. Returns `true` if they all finished.
+    ///
+    /// See `ShutdownSignal::shutdown`.
+    pub fn shutdown(mut self, grace: Duration) -> bool {
+        self.stop(grace)
+    }
+
+    fn stop(&mut self, grace: Duration) -> bool {
+        let finished = self.signal.take().map_or(true, |signal| signal.shutdown(grace));
+        // Dropping `Listening` would wait for its acceptor threads, which
+        // are left parked after a shutdown.
+        let _ = self.listening.close();
+        finished
+    }
+}
+
+impl Drop for RunningServer {
+    fn drop(&mut self) {
+        self.stop(Duration::from_secs(0));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    use prelude::*;
+    use request::{Body, HttpVersion};
+    use {method, headers, status, Url};
+
+    use super::{request, request_with_headers, response_header, run};
+
+    #[test]
+    fn test_request() {
+        let mut reader = "a=1".as_bytes();
+        let url = Url::parse("http://example.com:8080/foo/bar?a=1").unwrap();
+        let body = Body::from_reader(&mut reader, Some(3), false);
+        let mut req = request(method::Post, url, None, body);
+
+        assert_eq!(req.method, method::Post);
+        assert_eq!(req.version, HttpVersion::Http11);
+        assert!(!req.secure);
+        assert_eq!(req.local_addr.port(), 8080);
+        assert_eq!(req.raw_uri.to_string(), "/foo/bar?a=1");
+        assert_eq!(req.headers.get::<headers::Host>().map(|h| &*h.hostname), Some("example.com"));
+
+        let mut body = String::new();
+        req.body.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "a=1");
+    }
+
+    #[test]
+    fn test_request_https() {
+        let mut reader = io::empty();
+        let url = Url::parse("https://example.com:8443/").unwrap();
+        let req = request(method::Get, url, None, Body::from_reader(&mut reader, Some(0), false));
+
+        assert!(req.secure);
+        assert_eq!(req.local_addr.port(), 8443);
+        let host = req.headers.get::<headers::Host>().unwrap();
+        assert_eq!((&*host.hostname, host.port), ("example.com", Some(8443)));
+    }
+
+    #[test]
+    fn test_request_with_headers() {
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        let headers = [("X-A", "1"), ("x-a", "2"), ("X-B", "3")];
+        request_with_headers(method::Get, url, &headers, |req| {
+            assert_eq!(req.headers.get_raw("X-A").unwrap(), &[b"1".to_vec(), b"2".to_vec()][..]);
+            assert_eq!(req.headers.get_raw("X-B").unwrap(), &[b"3".to_vec()][..]);
+            assert!(req.headers.has::<headers::Host>());
+
+            let mut body = String::new();
+            req.body.read_to_string(&mut body).unwrap();
+            assert_eq!(body, "");
+        });
+    }
+
+    #[test]
+    fn test_response_header() {
+        let mut res = Response::new();
+        res.headers.set_raw("X-A", vec![b"1".to_vec(), b"2".to_vec()]);
+        assert_eq!(response_header(&res, "x-a"), Some("1".to_owned()));
+        assert_eq!(response_header(&res, "X-B"), None);
+    }
+
+    #[test]
+    fn test_run() {
+        let server = run(|req: &mut Request| {
+            Ok(Response::with((status::Ok, req.local_addr.port().to_string())))
+        });
+        let port = server.addr().port();
+        assert!(port != 0);
+
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.ends_with(&format!("\r\n\r\n{}", port)));
+
+        assert!(server.shutdown(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,187 @@
+//! Serving requests over Unix domain sockets.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::net::{NetworkListener, NetworkStream};
+
+use error::HttpResult;
+
+// Unix sockets have no IP address, so this stands in for both ends.
+pub fn unspecified_addr() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0)
+}
+
+// A listener on a Unix domain socket.
+#[derive(Clone)]
+pub struct UnixListener(Arc<net::UnixListener>);
+
+impl UnixListener {
+    // Bind to `path`, replacing a socket file left behind by a server which
+    // is no longer running.
+    pub fn bind(path: &Path) -> HttpResult<UnixListener> {
+        match net::UnixListener::bind(path) {
+            Ok(listener) => Ok(UnixListener(Arc::new(listener))),
+            Err(ref e) if e.kind() == io::ErrorKind::AddrInUse && is_stale(path) => {
+                try!(fs::remove_file(path));
+                Ok(UnixListener(Arc::new(try!(net::UnixListener::bind(path)))))
+            },
+            Err(e) => Err(e.into())
+        }
+    }
+}
+
+// Is `path` a socket which no server is listening on anymore? Anything else
+// there, such as a regular file, is left alone.
+fn is_stale(path: &Path) -> bool {
+    match fs::symlink_metadata(path) {
+        Ok(ref metadata) if metadata.file_type().is_socket() => (),
+        _ => return false
+    }
+
+    match net::UnixStream::connect(path) {
+        Ok(_) => false,
+        Err(e) => e.kind() == io::ErrorKind::ConnectionRefused
+    }
+}
+
+impl NetworkListener for UnixListener {
+    type Stream = UnixStream;
+
+    fn accept(&mut self) -> HttpResult<UnixStream> {
+        Ok(UnixStream(try!(self.0.accept()).0))
+    }
+
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        Ok(unspecified_addr())
+    }
+}
+
+// A connection accepted on a Unix domain socket.
+pub struct UnixStream(net::UnixStream);
+
+impl UnixStream {
+    pub fn try_clone(&self) -> io::Result<UnixStream> {
+        self.0.try_clone().map(UnixStream)
+    }
+}
+
+impl Clone for UnixStream {
+    fn clone(&self) -> UnixStream {
+        UnixStream(self.0.try_clone().unwrap())
+    }
+}
+
+impl Read for UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl NetworkStream for UnixStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        Ok(unspecified_addr())
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.0.shutdown(how)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::os::unix::net;
+    use std::path::PathBuf;
+    use std::process;
+
+    use prelude::*;
+    use status;
+
+    use super::UnixListener;
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("iron-test-{}-{}.sock", process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_unix() {
+        let path = socket_path("serve");
+        let handler = |req: &mut Request| {
+            Ok(Response::with((status::Ok, format!("{} {}", req.url, req.remote_addr))))
+        };
+        let mut listening = Iron::new(handler).threads(1).unix(&path).unwrap();
+
+        let mut stream = net::UnixStream::connect(&path).unwrap();
+        write!(stream, "GET /x HTTP/1.1\r\nHost: localhost:8080\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+
+        listening.close().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(res.ends_with("\r\n\r\nhttp://localhost:8080/x 0.0.0.0:0"));
+    }
+
+    #[test]
+    fn test_bind_replaces_stale_socket() {
+        let path = socket_path("stale");
+        drop(net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let listener = UnixListener::bind(&path).unwrap();
+        assert!(net::UnixStream::connect(&path).is_ok());
+        drop(listener);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_bind_keeps_live_socket() {
+        let path = socket_path("live");
+        let _live = net::UnixListener::bind(&path).unwrap();
+        assert!(UnixListener::bind(&path).is_err());
+        assert!(net::UnixStream::connect(&path).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_bind_keeps_other_files() {
+        let path = socket_path("file");
+        File::create(&path).unwrap().write_all(b"data").unwrap();
+        assert!(UnixListener::bind(&path).is_err());
+
+        let mut data = String::new();
+        File::open(&path).unwrap().read_to_string(&mut data).unwrap();
+        assert_eq!(data, "data");
+        fs::remove_file(&path).unwrap();
+    }
+}